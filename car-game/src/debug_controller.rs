@@ -1,6 +1,7 @@
 use nalgebra::{Rotation, Unit, Vector3};
 use utils::Camera;
-use winit::keyboard::KeyCode;
+
+use crate::input::{Action, Input};
 
 /// A simple stand-in camera controller for flying around the scene creative mode style
 pub struct DebugCameraController {
@@ -37,20 +38,18 @@ impl DebugCameraController {
         }
     }
 
-    pub fn handle_key_event(&mut self, key: KeyCode, pressed: bool) {
-        match key {
-            KeyCode::KeyW => self.w_pressed = pressed,
-            KeyCode::KeyA => self.a_pressed = pressed,
-            KeyCode::KeyS => self.s_pressed = pressed,
-            KeyCode::KeyD => self.d_pressed = pressed,
-            KeyCode::Space => self.space_pressed = pressed,
-            KeyCode::ShiftLeft | KeyCode::ShiftRight => self.shift_pressed = pressed,
-            KeyCode::ArrowUp => self.up_pressed = pressed,
-            KeyCode::ArrowLeft => self.left_pressed = pressed,
-            KeyCode::ArrowDown => self.down_pressed = pressed,
-            KeyCode::ArrowRight => self.right_pressed = pressed,
-            _ => {}
-        }
+    /// Picks up which movement actions are held
+    pub fn handle_input(&mut self, input: &Input) {
+        self.w_pressed = input.is_held(Action::FreecamForward);
+        self.a_pressed = input.is_held(Action::FreecamLeft);
+        self.s_pressed = input.is_held(Action::FreecamBack);
+        self.d_pressed = input.is_held(Action::FreecamRight);
+        self.space_pressed = input.is_held(Action::FreecamUp);
+        self.shift_pressed = input.is_held(Action::FreecamDown);
+        self.up_pressed = input.is_held(Action::LookUp);
+        self.down_pressed = input.is_held(Action::LookDown);
+        self.left_pressed = input.is_held(Action::LookLeft);
+        self.right_pressed = input.is_held(Action::LookRight);
     }
 
     pub fn update_camera(&mut self, adjusted_dt: f32, camera: &mut Camera) {
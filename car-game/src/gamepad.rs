@@ -0,0 +1,192 @@
+use crate::{input::Action, platform};
+
+/// Gamepad buttons the game uses, named by their position in the standard layout
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GamepadButton {
+    /// A on Xbox pads, cross on PlayStation ones
+    South,
+    /// Y on Xbox pads, triangle on PlayStation ones
+    North,
+    Start,
+    DpadUp,
+    DpadDown,
+}
+
+/// What a gamepad is doing this frame, before any deadzone or curve
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GamepadState {
+    /// Left stick from -1 (left) to 1 (right)
+    pub left_stick_x: f32,
+    /// Left stick from -1 (down) to 1 (up)
+    pub left_stick_y: f32,
+    /// Triggers from 0 (released) to 1 (fully pulled)
+    pub left_trigger: f32,
+    pub right_trigger: f32,
+    pub pressed: Vec<GamepadButton>,
+}
+
+/// Triggers pulled less than this count as released, so a worn trigger doesn't rest slightly pressed
+const TRIGGER_DEADZONE: f32 = 0.05;
+/// How far the stick has to be pushed up or down to move through a menu
+const MENU_STICK_THRESHOLD: f32 = 0.5;
+
+/// Driving with a gamepad, read from whichever one is connected
+///
+/// The right trigger accelerates, the left one brakes and the left stick steers. South is the handbrake, north
+/// cycles the camera and start pauses. In menus the d-pad or left stick moves the selection, south picks and start
+/// goes back. Unlike keys these aren't rebindable
+pub struct GamepadInput {
+    gamepads: platform::Gamepads,
+    state: GamepadState,
+    /// Menu actions from the last update
+    menu_actions: Vec<Action>,
+    /// Stick travel from the center that is ignored, from 0 to 1
+    pub deadzone: f32,
+    /// Exponent the stick's travel past the deadzone is raised to. Above 1 gives finer control near the center
+    pub steering_curve: f32,
+}
+impl GamepadInput {
+    pub fn new() -> GamepadInput {
+        GamepadInput {
+            gamepads: platform::Gamepads::new(),
+            state: GamepadState::default(),
+            menu_actions: Vec::new(),
+            deadzone: 0.0,
+            steering_curve: 1.0,
+        }
+    }
+
+    /// Reads the gamepad, returning the actions of any buttons that were just pressed
+    ///
+    /// While no gamepad is connected everything reads as released
+    pub fn update(&mut self) -> Vec<Action> {
+        let state = self.gamepads.poll().unwrap_or_default();
+        let just_pressed: Vec<GamepadButton> = state
+            .pressed
+            .iter()
+            .filter(|button| !self.state.pressed.contains(button))
+            .copied()
+            .collect();
+        let actions = just_pressed
+            .iter()
+            .filter_map(|button| match button {
+                GamepadButton::North => Some(Action::CycleCamera),
+                GamepadButton::Start => Some(Action::Pause),
+                _ => None,
+            })
+            .collect();
+
+        self.menu_actions = just_pressed
+            .iter()
+            .filter_map(|button| match button {
+                GamepadButton::DpadUp => Some(Action::MenuUp),
+                GamepadButton::DpadDown => Some(Action::MenuDown),
+                GamepadButton::South => Some(Action::MenuSelect),
+                _ => None,
+            })
+            .collect();
+        // the stick moves once each time it's pushed past the threshold
+        let stick = |state: &GamepadState| {
+            let y = state.left_stick_y;
+            (y > MENU_STICK_THRESHOLD) as i32 - (y < -MENU_STICK_THRESHOLD) as i32
+        };
+        let direction = stick(&state);
+        if direction != stick(&self.state) {
+            match direction {
+                1 => self.menu_actions.push(Action::MenuUp),
+                -1 => self.menu_actions.push(Action::MenuDown),
+                _ => {}
+            }
+        }
+
+        self.state = state;
+        actions
+    }
+
+    /// Menu actions from buttons pressed or the stick pushed in the last update. Start still pauses through `update`,
+    /// which goes back in menus
+    pub fn menu_actions(&self) -> &[Action] {
+        &self.menu_actions
+    }
+
+    /// Steering from -1 (full left) to 1 (full right), after the deadzone and response curve
+    pub fn steer(&self) -> f32 {
+        stick(self.state.left_stick_x, self.deadzone, self.steering_curve)
+    }
+
+    pub fn throttle(&self) -> f32 {
+        trigger(self.state.right_trigger)
+    }
+
+    pub fn brake(&self) -> f32 {
+        trigger(self.state.left_trigger)
+    }
+
+    pub fn handbrake(&self) -> bool {
+        self.state.pressed.contains(&GamepadButton::South)
+    }
+}
+
+/// Stick position with the deadzone taken out and the rest of its travel shaped by the curve, keeping its sign
+fn stick(x: f32, deadzone: f32, curve: f32) -> f32 {
+    let travel = ((x.abs() - deadzone) / (1.0 - deadzone)).clamp(0.0, 1.0);
+    travel.powf(curve).copysign(x)
+}
+
+fn trigger(value: f32) -> f32 {
+    ((value - TRIGGER_DEADZONE) / (1.0 - TRIGGER_DEADZONE)).clamp(0.0, 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-5, "{a} isn't near {b}");
+    }
+
+    #[test]
+    fn stick_deadzone() {
+        for x in [0.0, 0.1, -0.1, 0.2, -0.2] {
+            assert_eq!(stick(x, 0.2, 1.5), 0.0);
+        }
+        assert!(stick(0.21, 0.2, 1.0) > 0.0);
+    }
+
+    #[test]
+    fn stick_full_deflection() {
+        for curve in [0.2, 1.0, 1.5, 5.0] {
+            assert_near(stick(1.0, 0.15, curve), 1.0);
+            assert_near(stick(-1.0, 0.15, curve), -1.0);
+        }
+        // past the edge, as some pads report
+        assert_near(stick(1.05, 0.15, 1.5), 1.0);
+    }
+
+    #[test]
+    fn stick_keeps_its_sign() {
+        for x in [0.3, 0.5, 0.9] {
+            let right = stick(x, 0.15, 1.5);
+            assert!(right > 0.0);
+            assert_near(stick(-x, 0.15, 1.5), -right);
+        }
+    }
+
+    #[test]
+    fn stick_curve_shapes_the_middle() {
+        // halfway through the travel past the deadzone
+        let x = 0.6;
+        assert_near(stick(x, 0.2, 1.0), 0.5);
+        assert_near(stick(x, 0.2, 2.0), 0.25);
+        assert_near(stick(x, 0.2, 0.5), 0.5f32.sqrt());
+        assert_near(stick(x, 0.0, 1.0), x);
+    }
+
+    #[test]
+    fn trigger_deadzone() {
+        assert_eq!(trigger(0.0), 0.0);
+        assert_eq!(trigger(TRIGGER_DEADZONE), 0.0);
+        assert_near(trigger(1.0), 1.0);
+        assert_near(trigger((1.0 + TRIGGER_DEADZONE) / 2.0), 0.5);
+    }
+}
@@ -0,0 +1,469 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use winit::keyboard::KeyCode;
+
+/// Something the player can do with a key, independent of which key it's bound to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Action {
+    Accelerate,
+    /// Brakes, and reverses once stopped
+    Brake,
+    SteerLeft,
+    SteerRight,
+    Handbrake,
+
+    FreecamForward,
+    FreecamBack,
+    FreecamLeft,
+    FreecamRight,
+    FreecamUp,
+    FreecamDown,
+    LookUp,
+    LookDown,
+    LookLeft,
+    LookRight,
+
+    ZoomIn,
+    ZoomOut,
+    RollLeft,
+    RollRight,
+    ExposureUp,
+    ExposureDown,
+    FocusFar,
+    FocusNear,
+    /// Stronger depth of field
+    ApertureUp,
+    ApertureDown,
+    TakePhoto,
+
+    MenuUp,
+    MenuDown,
+    MenuSelect,
+
+    /// Also goes back in menus and leaves photo mode
+    Pause,
+    ToggleFreecam,
+    CycleCamera,
+    CycleGpsDestination,
+    ToggleMinimapRotation,
+    ToggleSpeedUnit,
+    ToggleDebugText,
+    RecordTelemetryCsv,
+    RecordTelemetryJson,
+    /// Only does anything in debug builds
+    CycleTelemetryGraph,
+}
+
+/// When an action is used. Actions used at the same time can't share a key
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ActionContext {
+    /// Whenever the game is running
+    Global,
+    /// Driving the car
+    Driving,
+    /// Flying the free camera, both while driving and in photo mode
+    Freecam,
+    /// Adjusting the camera in photo mode, where global actions other than pausing aren't used
+    Photo,
+    /// Moving around the menus, where global actions other than pausing aren't used
+    Menu,
+}
+
+impl Action {
+    pub const ALL: [Action; 39] = [
+        Action::Accelerate,
+        Action::Brake,
+        Action::SteerLeft,
+        Action::SteerRight,
+        Action::Handbrake,
+        Action::FreecamForward,
+        Action::FreecamBack,
+        Action::FreecamLeft,
+        Action::FreecamRight,
+        Action::FreecamUp,
+        Action::FreecamDown,
+        Action::LookUp,
+        Action::LookDown,
+        Action::LookLeft,
+        Action::LookRight,
+        Action::ZoomIn,
+        Action::ZoomOut,
+        Action::RollLeft,
+        Action::RollRight,
+        Action::ExposureUp,
+        Action::ExposureDown,
+        Action::FocusFar,
+        Action::FocusNear,
+        Action::ApertureUp,
+        Action::ApertureDown,
+        Action::TakePhoto,
+        Action::MenuUp,
+        Action::MenuDown,
+        Action::MenuSelect,
+        Action::Pause,
+        Action::ToggleFreecam,
+        Action::CycleCamera,
+        Action::CycleGpsDestination,
+        Action::ToggleMinimapRotation,
+        Action::ToggleSpeedUnit,
+        Action::ToggleDebugText,
+        Action::RecordTelemetryCsv,
+        Action::RecordTelemetryJson,
+        Action::CycleTelemetryGraph,
+    ];
+
+    pub fn context(&self) -> ActionContext {
+        match self {
+            Action::Accelerate
+            | Action::Brake
+            | Action::SteerLeft
+            | Action::SteerRight
+            | Action::Handbrake => ActionContext::Driving,
+            Action::FreecamForward
+            | Action::FreecamBack
+            | Action::FreecamLeft
+            | Action::FreecamRight
+            | Action::FreecamUp
+            | Action::FreecamDown
+            | Action::LookUp
+            | Action::LookDown
+            | Action::LookLeft
+            | Action::LookRight => ActionContext::Freecam,
+            Action::ZoomIn
+            | Action::ZoomOut
+            | Action::RollLeft
+            | Action::RollRight
+            | Action::ExposureUp
+            | Action::ExposureDown
+            | Action::FocusFar
+            | Action::FocusNear
+            | Action::ApertureUp
+            | Action::ApertureDown
+            | Action::TakePhoto => ActionContext::Photo,
+            Action::MenuUp | Action::MenuDown | Action::MenuSelect => ActionContext::Menu,
+            Action::Pause
+            | Action::ToggleFreecam
+            | Action::CycleCamera
+            | Action::CycleGpsDestination
+            | Action::ToggleMinimapRotation
+            | Action::ToggleSpeedUnit
+            | Action::ToggleDebugText
+            | Action::RecordTelemetryCsv
+            | Action::RecordTelemetryJson
+            | Action::CycleTelemetryGraph => ActionContext::Global,
+        }
+    }
+
+    /// Whether the two actions can be triggered at the same time, so binding them to the same key would be ambiguous
+    pub fn overlaps(&self, other: Action) -> bool {
+        use ActionContext::*;
+        match (self.context(), other.context()) {
+            (a, b) if a == b => true,
+            // photo mode flies the free camera too
+            (Freecam, Photo) | (Photo, Freecam) => true,
+            (Global, Photo | Menu) => *self == Action::Pause,
+            (Photo | Menu, Global) => other == Action::Pause,
+            (a, b) => a == Global || b == Global,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::Accelerate => "Accelerate",
+            Action::Brake => "Brake/reverse",
+            Action::SteerLeft => "Steer left",
+            Action::SteerRight => "Steer right",
+            Action::Handbrake => "Handbrake",
+            Action::FreecamForward => "Freecam forward",
+            Action::FreecamBack => "Freecam back",
+            Action::FreecamLeft => "Freecam left",
+            Action::FreecamRight => "Freecam right",
+            Action::FreecamUp => "Freecam up",
+            Action::FreecamDown => "Freecam down",
+            Action::LookUp => "Look up",
+            Action::LookDown => "Look down",
+            Action::LookLeft => "Look left",
+            Action::LookRight => "Look right",
+            Action::ZoomIn => "Photo zoom in",
+            Action::ZoomOut => "Photo zoom out",
+            Action::RollLeft => "Photo roll left",
+            Action::RollRight => "Photo roll right",
+            Action::ExposureUp => "Photo exposure up",
+            Action::ExposureDown => "Photo exposure down",
+            Action::FocusFar => "Photo focus farther",
+            Action::FocusNear => "Photo focus nearer",
+            Action::ApertureUp => "Photo depth of field stronger",
+            Action::ApertureDown => "Photo depth of field weaker",
+            Action::TakePhoto => "Take photo",
+            Action::MenuUp => "Menu up",
+            Action::MenuDown => "Menu down",
+            Action::MenuSelect => "Menu select",
+            Action::Pause => "Pause",
+            Action::ToggleFreecam => "Toggle freecam",
+            Action::CycleCamera => "Cycle camera",
+            Action::CycleGpsDestination => "Cycle GPS destination",
+            Action::ToggleMinimapRotation => "Toggle minimap rotation",
+            Action::ToggleSpeedUnit => "Toggle speed units",
+            Action::ToggleDebugText => "Toggle debug text",
+            Action::RecordTelemetryCsv => "Record telemetry (CSV)",
+            Action::RecordTelemetryJson => "Record telemetry (JSON lines)",
+            Action::CycleTelemetryGraph => "Cycle telemetry graph",
+        }
+    }
+
+    fn default_keys(&self) -> Vec<KeyCode> {
+        match self {
+            Action::Accelerate => vec![KeyCode::KeyW],
+            Action::Brake => vec![KeyCode::KeyS],
+            Action::SteerLeft => vec![KeyCode::KeyA],
+            Action::SteerRight => vec![KeyCode::KeyD],
+            Action::Handbrake => vec![KeyCode::ShiftLeft],
+            Action::FreecamForward => vec![KeyCode::KeyW],
+            Action::FreecamBack => vec![KeyCode::KeyS],
+            Action::FreecamLeft => vec![KeyCode::KeyA],
+            Action::FreecamRight => vec![KeyCode::KeyD],
+            Action::FreecamUp => vec![KeyCode::Space],
+            Action::FreecamDown => vec![KeyCode::ShiftLeft, KeyCode::ShiftRight],
+            Action::LookUp => vec![KeyCode::ArrowUp],
+            Action::LookDown => vec![KeyCode::ArrowDown],
+            Action::LookLeft => vec![KeyCode::ArrowLeft],
+            Action::LookRight => vec![KeyCode::ArrowRight],
+            Action::ZoomIn => vec![KeyCode::KeyZ],
+            Action::ZoomOut => vec![KeyCode::KeyX],
+            Action::RollLeft => vec![KeyCode::KeyQ],
+            Action::RollRight => vec![KeyCode::KeyE],
+            Action::ExposureUp => vec![KeyCode::KeyR],
+            Action::ExposureDown => vec![KeyCode::KeyF],
+            Action::FocusFar => vec![KeyCode::KeyT],
+            Action::FocusNear => vec![KeyCode::KeyG],
+            Action::ApertureUp => vec![KeyCode::KeyY],
+            Action::ApertureDown => vec![KeyCode::KeyH],
+            Action::TakePhoto => vec![KeyCode::Enter],
+            Action::MenuUp => vec![KeyCode::ArrowUp],
+            Action::MenuDown => vec![KeyCode::ArrowDown],
+            Action::MenuSelect => vec![KeyCode::Enter, KeyCode::Space],
+            Action::Pause => vec![KeyCode::Escape],
+            Action::ToggleFreecam => vec![KeyCode::Tab],
+            Action::CycleCamera => vec![KeyCode::KeyC],
+            Action::CycleGpsDestination => vec![KeyCode::KeyG],
+            Action::ToggleMinimapRotation => vec![KeyCode::KeyM],
+            Action::ToggleSpeedUnit => vec![KeyCode::KeyU],
+            Action::ToggleDebugText => vec![KeyCode::F1],
+            Action::RecordTelemetryCsv => vec![KeyCode::F2],
+            Action::RecordTelemetryJson => vec![KeyCode::F3],
+            Action::CycleTelemetryGraph => vec![KeyCode::F4],
+        }
+    }
+}
+
+/// Which keys trigger each action, any number of keys can be bound to one action
+///
+/// Saved as a map from action to keys. Actions missing from a saved map keep their default keys, so new actions get
+/// bound without wiping the player's changes
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(
+    from = "BTreeMap<Action, Vec<KeyCode>>",
+    into = "BTreeMap<Action, Vec<KeyCode>>"
+)]
+pub struct KeyBindings {
+    keys: BTreeMap<Action, Vec<KeyCode>>,
+}
+impl KeyBindings {
+    pub fn keys(&self, action: Action) -> &[KeyCode] {
+        self.keys.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    /// Every action the key is bound to
+    pub fn actions(&self, key: KeyCode) -> impl Iterator<Item = Action> + '_ {
+        self.keys
+            .iter()
+            .filter(move |(_, keys)| keys.contains(&key))
+            .map(|(action, _)| *action)
+    }
+
+    /// Binds a key to an action, unbinding it from any actions it would conflict with, which are returned
+    pub fn bind(&mut self, action: Action, key: KeyCode) -> Vec<Action> {
+        let mut unbound = Vec::new();
+        for (other, keys) in self.keys.iter_mut() {
+            if *other != action && other.overlaps(action) && keys.contains(&key) {
+                keys.retain(|k| *k != key);
+                unbound.push(*other);
+            }
+        }
+        let keys = self.keys.entry(action).or_default();
+        if !keys.contains(&key) {
+            keys.push(key);
+        }
+        unbound
+    }
+
+    pub fn clear(&mut self, action: Action) {
+        self.keys.insert(action, Vec::new());
+    }
+
+    /// Keys bound to more than one action that can be triggered at the same time
+    pub fn conflicts(&self) -> Vec<(KeyCode, Action, Action)> {
+        let mut conflicts = Vec::new();
+        for (i, (a, a_keys)) in self.keys.iter().enumerate() {
+            for (b, b_keys) in self.keys.iter().skip(i + 1) {
+                if a.overlaps(*b) {
+                    conflicts.extend(
+                        a_keys
+                            .iter()
+                            .filter(|key| b_keys.contains(key))
+                            .map(|key| (*key, *a, *b)),
+                    );
+                }
+            }
+        }
+        conflicts
+    }
+}
+impl Default for KeyBindings {
+    fn default() -> Self {
+        KeyBindings {
+            keys: Action::ALL
+                .iter()
+                .map(|action| (*action, action.default_keys()))
+                .collect(),
+        }
+    }
+}
+impl From<BTreeMap<Action, Vec<KeyCode>>> for KeyBindings {
+    fn from(saved: BTreeMap<Action, Vec<KeyCode>>) -> Self {
+        let mut bindings = KeyBindings::default();
+        bindings.keys.extend(saved);
+        bindings
+    }
+}
+impl From<KeyBindings> for BTreeMap<Action, Vec<KeyCode>> {
+    fn from(bindings: KeyBindings) -> Self {
+        bindings.keys
+    }
+}
+
+/// Short readable name of a key, like "W" or "ArrowUp"
+pub fn key_name(key: KeyCode) -> String {
+    let name = format!("{key:?}");
+    name.strip_prefix("Key")
+        .or_else(|| name.strip_prefix("Digit"))
+        .unwrap_or(&name)
+        .to_string()
+}
+
+/// Turns key events into actions, using the player's bindings
+pub struct Input {
+    pub bindings: KeyBindings,
+    held_keys: Vec<KeyCode>,
+}
+impl Input {
+    pub fn new() -> Input {
+        Input {
+            bindings: KeyBindings::default(),
+            held_keys: Vec::new(),
+        }
+    }
+
+    /// Records the key's state, returning whether it was just pressed (as opposed to released or repeating)
+    pub fn handle_key_event(&mut self, key: KeyCode, pressed: bool) -> bool {
+        let was_held = self.held_keys.contains(&key);
+        if pressed && !was_held {
+            self.held_keys.push(key);
+        } else if !pressed {
+            self.held_keys.retain(|k| *k != key);
+        }
+        pressed && !was_held
+    }
+
+    /// Whether any key bound to the action is held down
+    pub fn is_held(&self, action: Action) -> bool {
+        self.bindings
+            .keys(action)
+            .iter()
+            .any(|key| self.held_keys.contains(key))
+    }
+
+    /// Lets go of every key, for when key releases can't be seen such as after losing focus
+    pub fn release_all(&mut self) {
+        self.held_keys.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_have_no_conflicts() {
+        let bindings = KeyBindings::default();
+        assert_eq!(bindings.conflicts(), []);
+        assert!(
+            Action::ALL
+                .iter()
+                .all(|action| !bindings.keys(*action).is_empty())
+        );
+    }
+
+    #[test]
+    fn binding_unbinds_overlapping_actions() {
+        let mut bindings = KeyBindings::default();
+        // global actions can be used while driving
+        let unbound = bindings.bind(Action::Handbrake, KeyCode::KeyC);
+        assert_eq!(unbound, [Action::CycleCamera]);
+        assert!(bindings.keys(Action::CycleCamera).is_empty());
+        assert_eq!(
+            bindings.keys(Action::Handbrake),
+            [KeyCode::ShiftLeft, KeyCode::KeyC]
+        );
+        assert_eq!(bindings.conflicts(), []);
+
+        // the freecam isn't flown while driving
+        let unbound = bindings.bind(Action::Accelerate, KeyCode::Space);
+        assert!(unbound.is_empty());
+        assert_eq!(bindings.keys(Action::FreecamUp), [KeyCode::Space]);
+    }
+
+    #[test]
+    fn freecam_and_photo_overlap() {
+        assert!(Action::FreecamUp.overlaps(Action::ZoomIn));
+        assert!(Action::TakePhoto.overlaps(Action::LookLeft));
+        assert!(!Action::Accelerate.overlaps(Action::ZoomIn));
+        assert!(!Action::TakePhoto.overlaps(Action::MenuSelect));
+        // global actions only get in the way of photo mode and menus through pause
+        assert!(Action::Pause.overlaps(Action::TakePhoto));
+        assert!(Action::MenuUp.overlaps(Action::Pause));
+        assert!(!Action::CycleGpsDestination.overlaps(Action::FocusNear));
+        assert!(Action::CycleCamera.overlaps(Action::FreecamForward));
+
+        let mut bindings = KeyBindings::default();
+        let unbound = bindings.bind(Action::TakePhoto, KeyCode::KeyW);
+        assert_eq!(unbound, [Action::FreecamForward]);
+        assert_eq!(bindings.keys(Action::Accelerate), [KeyCode::KeyW]);
+    }
+
+    #[test]
+    fn conflicts_are_found() {
+        let mut bindings = KeyBindings::default();
+        bindings.keys.insert(Action::ZoomIn, vec![KeyCode::KeyW]);
+        assert_eq!(
+            bindings.conflicts(),
+            [(KeyCode::KeyW, Action::FreecamForward, Action::ZoomIn)]
+        );
+    }
+
+    #[test]
+    fn partial_maps_keep_defaults() {
+        let bindings: KeyBindings =
+            serde_json::from_str(r#"{ "Accelerate": ["ArrowUp"], "Pause": [] }"#).unwrap();
+        assert_eq!(bindings.keys(Action::Accelerate), [KeyCode::ArrowUp]);
+        assert!(bindings.keys(Action::Pause).is_empty());
+        assert_eq!(bindings.keys(Action::Brake), [KeyCode::KeyS]);
+
+        let json = serde_json::to_string(&bindings).unwrap();
+        assert_eq!(
+            serde_json::from_str::<KeyBindings>(&json).unwrap(),
+            bindings
+        );
+    }
+}
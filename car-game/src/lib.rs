@@ -1,119 +1,205 @@
 mod debug_controller;
 mod framerate;
-mod web_interface;
+mod gamepad;
+mod input;
+mod menu;
+mod photo_mode;
+mod platform;
+mod rebind_menu;
+mod settings;
+mod touch;
 
 use std::sync::Arc;
 
-use render::RenderState;
-use sim::GameSimulation;
+use assets::Assets;
+use audio::Audio;
+use render::{RenderState, Screenshot};
+#[cfg(debug_assertions)]
+use sim::PhysicsDebugLayer;
+use sim::{CarController, GameSimulation, TelemetryFormat, TelemetryRecorder};
+use utils::{RenderSnapshot, SpeedUnit};
 use wasm_bindgen::prelude::*;
+#[cfg(target_arch = "wasm32")]
 use web_sys::js_sys::JsString;
 use winit::{
     application::ApplicationHandler,
     event::*,
     event_loop::{ActiveEventLoop, EventLoop},
-    keyboard::{Key, KeyCode, NamedKey, PhysicalKey},
-    window::Window,
+    keyboard::PhysicalKey,
+    window::{Window, WindowAttributes},
 };
 
 use debug_controller::DebugCameraController;
 use framerate::FramerateCounter;
+use gamepad::GamepadInput;
+use input::{Action, Input};
+use menu::{GameState, Menu, MenuAction, MenuScreen};
+use photo_mode::PhotoMode;
+use rebind_menu::{RebindMenu, RebindMenuOutcome};
+use settings::Settings;
+use touch::{TouchButton, TouchControls};
 
+/// Web entry point, drawing into the canvas with the given id
+#[cfg(target_arch = "wasm32")]
 #[wasm_bindgen]
 pub fn run_game(canvas_id: JsString) -> Result<(), wasm_bindgen::JsValue> {
-    console_error_panic_hook::set_once();
-    console_log::init_with_level(if cfg!(debug_assertions) {
-        log::Level::Debug
-    } else {
-        log::Level::Info
-    })
-    .expect_throw("Failed to initialize console logging");
+    platform::init_logging();
+    run(platform::window_attributes(&canvas_id.as_string().unwrap()));
+    Ok(())
+}
+
+/// Native entry point, opening a desktop window
+#[cfg(not(target_arch = "wasm32"))]
+pub fn run_native() {
+    platform::init_logging();
+    run(platform::window_attributes());
+}
+
+fn run(window_attributes: WindowAttributes) {
     log::info!("Starting car game");
 
     let event_loop = EventLoop::with_user_event()
         .build()
         .expect_throw("Failed to create event loop");
-    let mut app = App::new(&event_loop, canvas_id.as_string().unwrap());
+    let mut app = App::new(&event_loop, window_attributes);
     event_loop
         .run_app(&mut app)
         .expect_throw("Failure during event loop");
+}
 
-    Ok(())
+/// Sent into the event loop once the assets are loaded and the renderer is set up with them
+pub struct Loaded {
+    render_state: RenderState,
+    assets: Assets,
+}
+
+/// Loads the assets while showing how far along it is, then sets up the renderer with them
+async fn load(window: Arc<Window>) -> Option<Loaded> {
+    let assets =
+        match Assets::load(|progress| platform::set_loading_text(&progress.to_string())).await {
+            Ok(assets) => assets,
+            Err(e) => {
+                log::error!("{e:#}");
+                platform::set_loading_text(&format!("{e:#}"));
+                return None;
+            }
+        };
+    let render_state = RenderState::new(window, &assets)
+        .await
+        .expect_throw("Unable to create render_state");
+    platform::show_loading(false);
+    Some(Loaded {
+        render_state,
+        assets,
+    })
 }
 
 pub struct App {
-    canvas_id: String,
-    proxy: Option<winit::event_loop::EventLoopProxy<RenderState>>,
+    window_attributes: WindowAttributes,
+    proxy: Option<winit::event_loop::EventLoopProxy<Loaded>>,
     render_state: Option<RenderState>,
-    paused: bool,
+    /// Models the sim is built from, kept to start new runs with
+    assets: Option<Assets>,
+    state: GameState,
+    menu: Menu,
+    /// Free camera for taking photos, entered from the pause menu
+    photo_mode: Option<PhotoMode>,
+    /// Screen for changing key bindings, entered from the menus
+    rebind_menu: Option<RebindMenu>,
+    /// The sim as of the last step, redrawn while frozen in photo mode
+    last_snapshot: Option<RenderSnapshot>,
+    /// Draw the frozen scene once while paused, after leaving photo mode or changing how it looks
+    redraw_paused_frame: bool,
+    settings: Settings,
+    input: Input,
+    touch_controls: TouchControls,
+    gamepad: GamepadInput,
+    audio: Audio,
 
-    sim: GameSimulation,
+    /// Created once the assets are loaded
+    sim: Option<GameSimulation>,
     fps_counter: FramerateCounter,
     debug_text_shown: bool,
     debug_camera_activated: bool,
     debug_camera_controller: DebugCameraController,
+    telemetry_recorder: Option<TelemetryRecorder>,
 }
 
 impl App {
-    pub fn new(event_loop: &EventLoop<RenderState>, canvas_id: String) -> Self {
+    pub fn new(event_loop: &EventLoop<Loaded>, window_attributes: WindowAttributes) -> Self {
         let proxy = Some(event_loop.create_proxy());
         let fps_counter = FramerateCounter::new(40);
+        let settings = Settings::load();
+        let mut input = Input::new();
+        settings.apply_to_input(&mut input);
+        let mut gamepad = GamepadInput::new();
+        settings.apply_to_gamepad(&mut gamepad);
+        let mut audio = Audio::new();
+        settings.apply_to_audio(&mut audio);
         Self {
-            canvas_id,
+            window_attributes,
             proxy,
             render_state: None,
-            sim: GameSimulation::new(),
-            paused: false,
+            assets: None,
+            sim: None,
+            state: GameState::MainMenu,
+            menu: Menu::new(MenuScreen::Main),
+            photo_mode: None,
+            rebind_menu: None,
+            last_snapshot: None,
+            redraw_paused_frame: false,
+            settings,
+            input,
+            touch_controls: TouchControls::new(),
+            gamepad,
+            audio,
             fps_counter,
             debug_text_shown: false,
             debug_camera_activated: false,
             debug_camera_controller: DebugCameraController::new(),
+            telemetry_recorder: None,
         }
     }
 }
 
-impl ApplicationHandler<RenderState> for App {
+impl ApplicationHandler<Loaded> for App {
     fn resumed(&mut self, event_loop: &ActiveEventLoop) {
         log::debug!("Application resumed");
-        let mut window_attributes = Window::default_attributes();
-
-        use wasm_bindgen::JsCast;
-        use winit::platform::web::WindowAttributesExtWebSys;
-
-        let window = wgpu::web_sys::window().expect_throw("Failed to get window");
-        let document = window.document().expect_throw("Failed to get document");
-        let canvas = document
-            .get_element_by_id(self.canvas_id.as_str())
-            .expect_throw("Failed to find canvas in document");
-        let html_canvas_element = canvas.unchecked_into();
-        window_attributes = window_attributes.with_canvas(Some(html_canvas_element));
-
-        let window = Arc::new(event_loop.create_window(window_attributes).unwrap_throw());
 
-        // using the event loop to create render state asyncronously and send it into the event loop
+        // using the event loop to load the game and send it into the event loop
         if let Some(proxy) = self.proxy.take() {
-            wasm_bindgen_futures::spawn_local(async move {
-                assert!(
-                    proxy
-                        .send_event(
-                            RenderState::new(window)
-                                .await
-                                .expect("Unable to create render_state")
-                        )
-                        .is_ok()
-                )
-            });
+            let window = Arc::new(
+                event_loop
+                    .create_window(self.window_attributes.clone())
+                    .unwrap_throw(),
+            );
+            platform::load_game(window, proxy);
         }
     }
 
-    fn user_event(&mut self, _event_loop: &ActiveEventLoop, mut event: RenderState) {
+    fn user_event(&mut self, _event_loop: &ActiveEventLoop, event: Loaded) {
         // This is where proxy.send_event() ends up
-        event.window.request_redraw();
-        event.handle_resize(
-            event.window.inner_size().width,
-            event.window.inner_size().height,
+        let Loaded {
+            mut render_state,
+            assets,
+        } = event;
+        render_state.window.request_redraw();
+        render_state.handle_resize(
+            render_state.window.inner_size().width,
+            render_state.window.inner_size().height,
         );
-        self.render_state = Some(event);
+        self.settings.apply_to_renderer(&mut render_state);
+        self.touch_controls.resize(
+            render_state.window.inner_size().width,
+            render_state.window.inner_size().height,
+            render_state.window.scale_factor() as f32,
+        );
+        let mut sim = GameSimulation::new(self.settings.car, &assets);
+        self.settings.apply_to_sim(&mut sim);
+        show_menu(self.state, &self.menu, &self.settings, &sim);
+        self.render_state = Some(render_state);
+        self.assets = Some(assets);
+        self.sim = Some(sim);
     }
 
     fn window_event(
@@ -122,35 +208,104 @@ impl ApplicationHandler<RenderState> for App {
         _window_id: winit::window::WindowId,
         event: WindowEvent,
     ) {
-        let render_state = match &mut self.render_state {
-            Some(rs) => rs,
-            None => return,
+        let (Some(render_state), Some(assets), Some(sim)) =
+            (&mut self.render_state, &self.assets, &mut self.sim)
+        else {
+            return;
         };
 
+        // actions triggered by this event, handled after it
+        let mut actions: Vec<Action> = Vec::new();
+        let mut menu_actions: Vec<MenuAction> = Vec::new();
         match event {
             WindowEvent::CloseRequested => event_loop.exit(),
-            WindowEvent::Resized(size) => render_state.handle_resize(size.width, size.height),
+            WindowEvent::Resized(size) => {
+                render_state.handle_resize(size.width, size.height);
+                self.touch_controls.resize(
+                    size.width,
+                    size.height,
+                    render_state.window.scale_factor() as f32,
+                );
+            }
             WindowEvent::RedrawRequested => {
                 // where the magic happens
 
                 // delta time in seconds
                 let dt = self.fps_counter.tick();
-                let render_snapshot = if !self.paused {
-                    // delta time in expected frame time (60fps)
-                    let adjusted_dt = dt * 60.0;
+                // delta time in expected frame time (60fps)
+                let adjusted_dt = dt * 60.0;
+                if self.state == GameState::Loading {
+                    // the loading screen is up, so the new run can be set up behind it
+                    log::info!(
+                        "Starting {} run in the {}",
+                        self.settings.mode.name(),
+                        self.settings.car.name()
+                    );
+                    *sim = GameSimulation::new(self.settings.car, assets);
+                    self.settings.apply_to_sim(sim);
+                    self.state = GameState::Playing;
+                    show_menu(self.state, &self.menu, &self.settings, sim);
+                }
+
+                let gamepad_actions = self.gamepad.update();
+                let menu_clicks = platform::take_menu_clicks();
+                if self.photo_mode.is_none() && self.rebind_menu.is_none() {
+                    // outside of driving only pausing is used, which goes back in menus
+                    actions.extend(gamepad_actions.into_iter().filter(|action| {
+                        self.state == GameState::Playing || *action == Action::Pause
+                    }));
+                    if self.state != GameState::Playing {
+                        menu_actions.extend(navigate_menu(
+                            &mut self.menu,
+                            self.state,
+                            &self.settings,
+                            sim,
+                            self.gamepad.menu_actions(),
+                        ));
+                        menu_actions.extend(
+                            menu_clicks
+                                .into_iter()
+                                .filter_map(|index| self.menu.click(index, &self.settings)),
+                        );
+                    }
+                }
+                // the car idles behind the main menu
+                let sim_running = matches!(self.state, GameState::Playing | GameState::MainMenu);
+                if sim_running {
+                    let driving = self.state == GameState::Playing;
+                    if driving {
+                        update_car_controller(
+                            &mut sim.controller,
+                            &self.input,
+                            &self.touch_controls,
+                            &self.gamepad,
+                        );
+                    }
+                    let snapshot = sim.step(adjusted_dt, driving && !self.debug_camera_activated);
+                    if let Some(recorder) = &mut self.telemetry_recorder {
+                        recorder.record(sim.telemetry());
+                    }
+                    self.audio.set_info(&sim.audio_info());
 
-                    let snapshot = self.sim.step(adjusted_dt, !self.debug_camera_activated);
+                    if driving
+                        && let Some(laps) = self.settings.mode.laps()
+                        && sim.completed_laps().len() >= laps
+                    {
+                        log::info!("Finished {laps} laps");
+                        self.state = GameState::Results;
+                        self.menu.open(MenuScreen::Results);
+                        show_menu(self.state, &self.menu, &self.settings, sim);
+                    }
 
                     if self.debug_camera_activated {
                         self.debug_camera_controller
                             .update_camera(adjusted_dt, &mut render_state.scene.camera);
                     } else {
-                        self.sim
-                            .update_camera(adjusted_dt, &mut render_state.scene.camera);
+                        sim.update_camera(adjusted_dt, &mut render_state.scene.camera);
                     }
 
                     if self.debug_text_shown {
-                        web_interface::set_debug_text(
+                        platform::set_debug_text(
                             format!(
                                 "fps: {:.2}\nview: {}\n\n{}\n{}",
                                 self.fps_counter.fps(),
@@ -160,65 +315,414 @@ impl ApplicationHandler<RenderState> for App {
                                     "car"
                                 },
                                 render_state.get_debug_string(),
-                                self.sim.get_debug_string(),
+                                sim.get_debug_string(),
                             )
                             .as_str(),
                         );
                     }
 
-                    Some(snapshot)
+                    self.last_snapshot = Some(snapshot);
+                } else if let Some(photo_mode) = &mut self.photo_mode
+                    && let Some(snapshot) = &self.last_snapshot
+                {
+                    photo_mode.update_camera(
+                        adjusted_dt,
+                        snapshot.car_transform.translation.vector.into(),
+                        &mut render_state.scene.camera,
+                    );
+                    render_state.photo.settings = Some(photo_mode.settings);
+                    platform::set_photo_mode_info(
+                        &photo_mode.info_text(&render_state.scene.camera),
+                    );
+                }
+
+                self.audio.set_paused(self.state != GameState::Playing);
+                self.audio.pump();
+
+                let menu_overlay = platform::menu_overlay();
+                if render_state.hud.menu_overlay != menu_overlay {
+                    render_state.hud.menu_overlay = menu_overlay;
+                    self.redraw_paused_frame = true;
+                }
+                // only redrawing the frozen scene while paused when it can change
+                let render_snapshot = if sim_running
+                    || self.photo_mode.is_some()
+                    || std::mem::take(&mut self.redraw_paused_frame)
+                {
+                    self.last_snapshot.as_ref()
                 } else {
                     None
                 };
-
+                render_state.hud.touch_overlay = self.touch_controls.overlay();
                 render_state
                     .render(render_snapshot)
                     .expect_throw("Render failed");
+                if let Some(screenshot) = render_state.photo.take_screenshot() {
+                    save_screenshot(&screenshot);
+                }
             }
             WindowEvent::KeyboardInput {
                 event:
                     KeyEvent {
                         physical_key: PhysicalKey::Code(code),
-                        ref logical_key,
                         state: key_state,
                         ..
                     },
                 ..
             } => {
                 let pressed = key_state.is_pressed();
-                match (code, pressed) {
-                    (KeyCode::Escape, true) => {
-                        log::debug!("Toggled pause");
-                        self.paused = !self.paused;
-                        web_interface::show_pause_menu(self.paused);
+                let just_pressed = self.input.handle_key_event(code, pressed);
+                // back to the keyboard
+                self.touch_controls.active = false;
+                // browsers only allow audio to start after the player interacts with the page
+                self.audio.resume();
+                let pressed_actions: Vec<Action> = if just_pressed {
+                    self.input.bindings.actions(code).collect()
+                } else {
+                    Vec::new()
+                };
+                if let Some(rebind_menu) = &mut self.rebind_menu {
+                    if just_pressed {
+                        match rebind_menu.handle_key_press(
+                            code,
+                            &pressed_actions,
+                            &mut self.settings.key_bindings,
+                        ) {
+                            RebindMenuOutcome::Stay => {}
+                            RebindMenuOutcome::Changed => {
+                                self.settings.apply_to_input(&mut self.input);
+                                self.settings.save();
+                            }
+                            RebindMenuOutcome::Close => {
+                                log::debug!("Left controls menu");
+                                self.rebind_menu = None;
+                                platform::show_key_bindings(false);
+                                show_menu(self.state, &self.menu, &self.settings, sim);
+                            }
+                        }
+                        if let Some(rebind_menu) = &self.rebind_menu {
+                            platform::set_key_bindings_text(
+                                &rebind_menu.text(&self.settings.key_bindings),
+                            );
+                        }
                     }
-                    (KeyCode::Tab, true) => {
-                        log::debug!("Switched camera mode");
-                        self.debug_camera_activated = !self.debug_camera_activated;
+                    render_state.handle_window_event(&event);
+                    return;
+                }
+                if let Some(photo_mode) = &mut self.photo_mode {
+                    if pressed_actions.contains(&Action::Pause) {
+                        log::debug!("Left photo mode");
+                        photo_mode.restore_camera(&mut render_state.scene.camera);
+                        render_state.photo.settings = None;
+                        self.photo_mode = None;
+                        self.redraw_paused_frame = true;
+                        platform::show_photo_mode(false);
+                        show_menu(self.state, &self.menu, &self.settings, sim);
+                    } else {
+                        if pressed_actions.contains(&Action::TakePhoto) {
+                            render_state.photo.request_screenshot();
+                        }
+                        photo_mode.handle_input(&self.input);
                     }
-                    _ => {}
+                    render_state.handle_window_event(&event);
+                    return;
                 }
-                if pressed && matches!(logical_key, Key::Named(NamedKey::F1)) {
-                    log::debug!("Toggled debug text");
-                    self.debug_text_shown = !self.debug_text_shown;
-                    web_interface::show_debug_text(self.debug_text_shown);
+
+                if self.state == GameState::Playing {
+                    actions.extend(pressed_actions);
+                } else {
+                    // the menus take over the keyboard while they're shown
+                    menu_actions.extend(navigate_menu(
+                        &mut self.menu,
+                        self.state,
+                        &self.settings,
+                        sim,
+                        &pressed_actions,
+                    ));
+                    actions.extend(pressed_actions.into_iter().filter(|a| *a == Action::Pause));
                 }
+                #[cfg(debug_assertions)]
+                if pressed {
+                    use winit::keyboard::KeyCode;
 
-                self.debug_camera_controller.handle_key_event(code, pressed);
-                self.sim.controller.handle_key_event(code, pressed);
+                    // number keys toggle the physics debug view layers
+                    const LAYER_KEYS: [KeyCode; PhysicsDebugLayer::ALL.len()] = [
+                        KeyCode::Digit1,
+                        KeyCode::Digit2,
+                        KeyCode::Digit3,
+                        KeyCode::Digit4,
+                        KeyCode::Digit5,
+                        KeyCode::Digit6,
+                        KeyCode::Digit7,
+                    ];
+                    if let Some(i) = LAYER_KEYS.iter().position(|key| *key == code) {
+                        let layer = PhysicsDebugLayer::ALL[i];
+                        sim.physics_debug.toggle(layer);
+                        log::debug!(
+                            "Physics debug {layer:?}: {}",
+                            sim.physics_debug.is_enabled(layer)
+                        );
+                    }
+                }
+
+                self.debug_camera_controller.handle_input(&self.input);
+            }
+            WindowEvent::Touch(touch) if self.state == GameState::Playing => {
+                self.audio.resume();
+                match self.touch_controls.handle_touch(&touch) {
+                    Some(TouchButton::Pause) => actions.push(Action::Pause),
+                    Some(TouchButton::Camera) => actions.push(Action::CycleCamera),
+                    None => {}
+                }
             }
             WindowEvent::Focused(focused) => {
                 log::debug!("Focused: {focused}");
                 if focused == false {
-                    if !self.paused {
-                        web_interface::show_pause_menu(true);
-                        self.paused = true;
+                    // key releases while unfocused are never seen
+                    self.input.release_all();
+                    self.touch_controls.release_all();
+                    if self.state == GameState::Playing {
+                        actions.push(Action::Pause);
                     }
                 }
             }
             _ => {}
         }
 
+        for action in actions {
+            match action {
+                Action::Pause => match self.state {
+                    GameState::Playing => {
+                        log::debug!("Paused");
+                        self.state = GameState::Paused;
+                        self.menu.open(MenuScreen::Pause);
+                        // touches ending under the menu are never seen
+                        self.touch_controls.release_all();
+                        show_menu(self.state, &self.menu, &self.settings, sim);
+                    }
+                    _ => menu_actions.extend(self.menu.back()),
+                },
+                Action::ToggleFreecam => {
+                    log::debug!("Switched camera mode");
+                    self.debug_camera_activated = !self.debug_camera_activated;
+                }
+                Action::CycleCamera => {
+                    sim.cycle_camera_rig();
+                    self.settings.camera.rig = sim.camera_rigs.current;
+                    self.settings.save();
+                }
+                Action::CycleGpsDestination => sim.cycle_gps_destination(),
+                Action::ToggleMinimapRotation => {
+                    let graphics = &mut self.settings.graphics;
+                    graphics.minimap_heading_up = !graphics.minimap_heading_up;
+                    log::debug!("Minimap heading up: {}", graphics.minimap_heading_up);
+                    self.settings.apply_to_renderer(render_state);
+                    self.settings.save();
+                }
+                Action::ToggleSpeedUnit => {
+                    self.settings.speed_unit = match self.settings.speed_unit {
+                        SpeedUnit::Kmh => SpeedUnit::Mph,
+                        SpeedUnit::Mph => SpeedUnit::Kmh,
+                    };
+                    log::debug!("Speed unit: {:?}", self.settings.speed_unit);
+                    self.settings.apply_to_renderer(render_state);
+                    self.settings.save();
+                }
+                Action::ToggleDebugText => {
+                    log::debug!("Toggled debug text");
+                    self.debug_text_shown = !self.debug_text_shown;
+                    platform::show_debug_text(self.debug_text_shown);
+                }
+                Action::RecordTelemetryCsv => {
+                    toggle_telemetry_recording(&mut self.telemetry_recorder, TelemetryFormat::Csv);
+                }
+                Action::RecordTelemetryJson => {
+                    toggle_telemetry_recording(
+                        &mut self.telemetry_recorder,
+                        TelemetryFormat::JsonLines,
+                    );
+                }
+                #[cfg(debug_assertions)]
+                Action::CycleTelemetryGraph => {
+                    let graph = &mut render_state.hud.graph;
+                    graph.cycle_channel();
+                    log::debug!("Telemetry graph: {:?}", graph.channel);
+                }
+                // held actions, read below
+                _ => {}
+            }
+        }
+
         render_state.handle_window_event(&event);
+
+        for action in menu_actions {
+            self.handle_menu_action(action);
+        }
+    }
+}
+
+impl App {
+    /// Does what the picked menu item says, saving any settings it changed
+    fn handle_menu_action(&mut self, action: MenuAction) {
+        log::debug!("Menu action: {action:?}");
+        let old_settings = self.settings.clone();
+        match action {
+            MenuAction::Play | MenuAction::Restart => self.state = GameState::Loading,
+            MenuAction::Resume => self.state = GameState::Playing,
+            MenuAction::MainMenu => {
+                self.state = GameState::MainMenu;
+                self.menu.open(MenuScreen::Main);
+            }
+            MenuAction::Settings => self.menu.open(MenuScreen::Settings),
+            MenuAction::Back => self.menu.open(match self.state {
+                GameState::MainMenu => MenuScreen::Main,
+                _ => MenuScreen::Pause,
+            }),
+            MenuAction::SelectCar => self.settings.car = self.settings.car.next(),
+            MenuAction::SelectMode => self.settings.mode = self.settings.mode.next(),
+            MenuAction::CycleCamera => {
+                if let Some(sim) = &mut self.sim {
+                    sim.cycle_camera_rig();
+                    self.settings.camera.rig = sim.camera_rigs.current;
+                }
+            }
+            MenuAction::PhotoMode => {
+                if let Some(render_state) = &self.render_state {
+                    log::debug!("Entered photo mode");
+                    self.photo_mode = Some(PhotoMode::new(&render_state.scene.camera));
+                    platform::show_menu(None);
+                    platform::show_photo_mode(true);
+                }
+                return;
+            }
+            MenuAction::Controls => {
+                log::debug!("Entered controls menu");
+                let rebind_menu = RebindMenu::new();
+                platform::set_key_bindings_text(&rebind_menu.text(&self.settings.key_bindings));
+                self.rebind_menu = Some(rebind_menu);
+                platform::show_menu(None);
+                platform::show_key_bindings(true);
+                return;
+            }
+            MenuAction::ToggleVsync => {
+                self.settings.graphics.vsync = !self.settings.graphics.vsync;
+            }
+            MenuAction::ToggleShadows => {
+                self.settings.graphics.shadows = !self.settings.graphics.shadows;
+            }
+            MenuAction::ToggleMinimap => {
+                self.settings.graphics.minimap = !self.settings.graphics.minimap;
+            }
+            MenuAction::ToggleSpeedUnit => {
+                self.settings.speed_unit = match self.settings.speed_unit {
+                    SpeedUnit::Kmh => SpeedUnit::Mph,
+                    SpeedUnit::Mph => SpeedUnit::Kmh,
+                };
+            }
+            MenuAction::ToggleTractionControl => {
+                let assists = &mut self.settings.assists;
+                assists.traction_control = !assists.traction_control;
+                if let Some(sim) = &mut self.sim {
+                    self.settings.apply_to_sim(sim);
+                }
+            }
+            MenuAction::CycleVolume => {
+                // in tenths, wrapping from full back to muted
+                let tenths = (self.settings.audio.volume * 10.0).round() as u32;
+                self.settings.audio.volume = ((tenths + 1) % 11) as f32 / 10.0;
+                self.settings.apply_to_audio(&mut self.audio);
+            }
+        }
+        if self.settings != old_settings {
+            if let Some(render_state) = &mut self.render_state {
+                self.settings.apply_to_renderer(render_state);
+            }
+            self.redraw_paused_frame = true;
+            self.settings.save();
+        }
+        if let Some(sim) = &self.sim {
+            show_menu(self.state, &self.menu, &self.settings, sim);
+        }
+    }
+}
+
+/// Shows the menu for the game's state, or hides it while driving
+fn show_menu(state: GameState, menu: &Menu, settings: &Settings, sim: &GameSimulation) {
+    if state == GameState::Playing {
+        platform::show_menu(None);
+    } else {
+        platform::show_menu(Some(&menu.view(state, settings, sim.completed_laps())));
+    }
+}
+
+/// Moves around the menu with the pressed actions, returning the actions of any items picked
+fn navigate_menu(
+    menu: &mut Menu,
+    state: GameState,
+    settings: &Settings,
+    sim: &GameSimulation,
+    pressed_actions: &[Action],
+) -> Vec<MenuAction> {
+    let picked: Vec<MenuAction> = pressed_actions
+        .iter()
+        .filter_map(|action| menu.handle_action(*action, settings))
+        .collect();
+    if picked.is_empty() && !pressed_actions.is_empty() {
+        // showing the new selection
+        show_menu(state, menu, settings, sim);
+    }
+    picked
+}
+
+/// Combines the driving inputs from the keyboard, touch controls and gamepad
+fn update_car_controller(
+    controller: &mut CarController,
+    input: &Input,
+    touch: &TouchControls,
+    gamepad: &GamepadInput,
+) {
+    let key_axis = |action: Action| -> f32 { if input.is_held(action) { 1.0 } else { 0.0 } };
+    controller.throttle = key_axis(Action::Accelerate)
+        .max(touch.throttle())
+        .max(gamepad.throttle());
+    controller.brake = key_axis(Action::Brake)
+        .max(touch.brake())
+        .max(gamepad.brake());
+    controller.steer = (key_axis(Action::SteerRight) - key_axis(Action::SteerLeft)
+        + touch.steer()
+        + gamepad.steer())
+    .clamp(-1.0, 1.0);
+    controller.handbrake = input.is_held(Action::Handbrake) || gamepad.handbrake();
+}
+
+/// Offers a photo to the user as a PNG download, or saves it on native
+fn save_screenshot(screenshot: &Screenshot) {
+    log::info!(
+        "Saving photo ({}x{}, {} KB)",
+        screenshot.width,
+        screenshot.height,
+        screenshot.png.len() / 1024
+    );
+    platform::save_file("car-game-photo.png", "image/png", &screenshot.png);
+}
+
+/// Starts recording telemetry in the given format, or stops and saves the current recording
+fn toggle_telemetry_recording(recorder: &mut Option<TelemetryRecorder>, format: TelemetryFormat) {
+    if let Some(finished) = recorder.take() {
+        log::info!(
+            "Stopped recording telemetry ({} ticks)",
+            finished.n_records()
+        );
+        let file_name = format!("telemetry.{}", finished.format.file_extension());
+        platform::save_file(
+            &file_name,
+            finished.format.mime_type(),
+            finished.contents().as_bytes(),
+        );
+        if finished.format == format {
+            return;
+        }
     }
+    log::info!("Started recording telemetry ({format:?})");
+    *recorder = Some(TelemetryRecorder::new(format));
 }
@@ -0,0 +1,8 @@
+#[cfg(not(target_arch = "wasm32"))]
+fn main() {
+    car_game::run_native();
+}
+
+// the web build starts from `run_game` in the library instead
+#[cfg(target_arch = "wasm32")]
+fn main() {}
@@ -0,0 +1,274 @@
+use crate::{input::Action, settings::Settings};
+use serde::{Deserialize, Serialize};
+
+/// What the game is doing, deciding whether the sim runs and which menu is shown
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum GameState {
+    /// Title screen, with the car sitting idle behind it
+    MainMenu,
+    /// Setting up a new run, shown for a frame or more before it starts
+    Loading,
+    Playing,
+    Paused,
+    /// A run is over, showing how it went
+    Results,
+}
+
+/// The rules of a run
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameMode {
+    /// Drive around with no goal
+    #[default]
+    FreeRoam,
+    /// Set the fastest time over a few laps
+    TimeTrial,
+}
+impl GameMode {
+    pub const ALL: [GameMode; 2] = [GameMode::FreeRoam, GameMode::TimeTrial];
+
+    pub fn next(&self) -> GameMode {
+        let i = Self::ALL.iter().position(|mode| mode == self).unwrap();
+        Self::ALL[(i + 1) % Self::ALL.len()]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            GameMode::FreeRoam => "Free roam",
+            GameMode::TimeTrial => "Time trial",
+        }
+    }
+
+    /// Laps until the run is over, if it ends at all
+    pub fn laps(&self) -> Option<usize> {
+        match self {
+            GameMode::FreeRoam => None,
+            GameMode::TimeTrial => Some(3),
+        }
+    }
+}
+
+/// Which list of items a menu is showing
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MenuScreen {
+    Main,
+    Pause,
+    Settings,
+    Results,
+}
+
+/// What picking a menu item does
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MenuAction {
+    /// Start a new run with the selected car and mode
+    Play,
+    Resume,
+    Restart,
+    CycleCamera,
+    SelectCar,
+    SelectMode,
+    Settings,
+    Controls,
+    PhotoMode,
+    /// Leave the run for the main menu
+    MainMenu,
+    /// Leave the settings for the screen they were opened from
+    Back,
+
+    ToggleVsync,
+    ToggleShadows,
+    ToggleMinimap,
+    ToggleSpeedUnit,
+    ToggleTractionControl,
+    CycleVolume,
+}
+
+struct MenuItem {
+    label: String,
+    action: MenuAction,
+}
+
+/// Everything the page needs to draw a menu, it holds no menu state of its own
+#[derive(Serialize)]
+pub struct MenuView {
+    pub state: GameState,
+    pub title: String,
+    /// Text under the title, one line each
+    pub lines: Vec<String>,
+    pub items: Vec<String>,
+    pub selected: usize,
+}
+
+/// The menus, navigated with the menu actions or by clicking items
+pub struct Menu {
+    pub screen: MenuScreen,
+    selected: usize,
+}
+impl Menu {
+    pub fn new(screen: MenuScreen) -> Menu {
+        Menu {
+            screen,
+            selected: 0,
+        }
+    }
+
+    /// Switches screens, selecting the first item
+    pub fn open(&mut self, screen: MenuScreen) {
+        self.screen = screen;
+        self.selected = 0;
+    }
+
+    fn items(&self, settings: &Settings) -> Vec<MenuItem> {
+        let item = |label: &str, action| MenuItem {
+            label: label.to_string(),
+            action,
+        };
+        let on_off = |on: bool| if on { "On" } else { "Off" };
+        match self.screen {
+            MenuScreen::Main => vec![
+                item("Play", MenuAction::Play),
+                item(
+                    &format!("Car: {}", settings.car.name()),
+                    MenuAction::SelectCar,
+                ),
+                item(
+                    &format!("Mode: {}", settings.mode.name()),
+                    MenuAction::SelectMode,
+                ),
+                item("Settings", MenuAction::Settings),
+                item("Controls", MenuAction::Controls),
+            ],
+            MenuScreen::Pause => vec![
+                item("Resume", MenuAction::Resume),
+                item("Restart", MenuAction::Restart),
+                item(
+                    &format!("Camera: {}", settings.camera.rig.name()),
+                    MenuAction::CycleCamera,
+                ),
+                item("Photo mode", MenuAction::PhotoMode),
+                item("Settings", MenuAction::Settings),
+                item("Controls", MenuAction::Controls),
+                item("Main menu", MenuAction::MainMenu),
+            ],
+            MenuScreen::Settings => vec![
+                item(
+                    &format!("Vsync: {}", on_off(settings.graphics.vsync)),
+                    MenuAction::ToggleVsync,
+                ),
+                item(
+                    &format!("Shadows: {}", on_off(settings.graphics.shadows)),
+                    MenuAction::ToggleShadows,
+                ),
+                item(
+                    &format!("Minimap: {}", on_off(settings.graphics.minimap)),
+                    MenuAction::ToggleMinimap,
+                ),
+                item(
+                    &format!("Speed units: {}", settings.speed_unit.label()),
+                    MenuAction::ToggleSpeedUnit,
+                ),
+                item(
+                    &format!(
+                        "Traction control: {}",
+                        on_off(settings.assists.traction_control)
+                    ),
+                    MenuAction::ToggleTractionControl,
+                ),
+                item(
+                    &format!("Volume: {:.0}%", settings.audio.volume * 100.0),
+                    MenuAction::CycleVolume,
+                ),
+                item("Back", MenuAction::Back),
+            ],
+            MenuScreen::Results => vec![
+                item("Restart", MenuAction::Restart),
+                item("Main menu", MenuAction::MainMenu),
+            ],
+        }
+    }
+
+    /// Handles a menu action, returning the action of the item it picked if any. Going back is left to pausing
+    pub fn handle_action(&mut self, action: Action, settings: &Settings) -> Option<MenuAction> {
+        let n_items = self.items(settings).len();
+        if n_items == 0 {
+            return None;
+        }
+        match action {
+            Action::MenuUp => {
+                self.selected = (self.selected + n_items - 1) % n_items;
+                None
+            }
+            Action::MenuDown => {
+                self.selected = (self.selected + 1) % n_items;
+                None
+            }
+            Action::MenuSelect => self.click(self.selected, settings),
+            _ => None,
+        }
+    }
+
+    /// Picks the item at the index, returning its action
+    pub fn click(&mut self, index: usize, settings: &Settings) -> Option<MenuAction> {
+        let item = self.items(settings).into_iter().nth(index)?;
+        self.selected = index;
+        Some(item.action)
+    }
+
+    /// What leaving the current screen does, such as when pausing
+    pub fn back(&self) -> Option<MenuAction> {
+        match self.screen {
+            MenuScreen::Pause => Some(MenuAction::Resume),
+            MenuScreen::Settings => Some(MenuAction::Back),
+            MenuScreen::Main | MenuScreen::Results => None,
+        }
+    }
+
+    /// What the page should show, given the results of the run
+    pub fn view(&self, state: GameState, settings: &Settings, laps: &[f32]) -> MenuView {
+        if state == GameState::Loading {
+            return MenuView {
+                state,
+                title: "Loading".to_string(),
+                lines: vec![format!("{}, {}", settings.car.name(), settings.mode.name())],
+                items: Vec::new(),
+                selected: 0,
+            };
+        }
+        let (title, lines) = match self.screen {
+            MenuScreen::Main => (
+                "Car game".to_string(),
+                vec!["Use the arrow keys and enter, or click".to_string()],
+            ),
+            MenuScreen::Pause => ("Paused".to_string(), Vec::new()),
+            MenuScreen::Settings => ("Settings".to_string(), Vec::new()),
+            MenuScreen::Results => {
+                let mut lines: Vec<String> = laps
+                    .iter()
+                    .enumerate()
+                    .map(|(i, time)| format!("Lap {}: {}", i + 1, format_time(*time)))
+                    .collect();
+                lines.push(format!("Total: {}", format_time(laps.iter().sum())));
+                if let Some(best) = laps.iter().copied().reduce(f32::min) {
+                    lines.push(format!("Best lap: {}", format_time(best)));
+                }
+                (settings.mode.name().to_string(), lines)
+            }
+        };
+        MenuView {
+            state,
+            title,
+            lines,
+            items: self
+                .items(settings)
+                .into_iter()
+                .map(|item| item.label)
+                .collect(),
+            selected: self.selected,
+        }
+    }
+}
+
+/// Time in seconds as minutes, seconds and hundredths, like 1:05.32
+fn format_time(seconds: f32) -> String {
+    let minutes = (seconds / 60.0).floor();
+    format!("{}:{:05.2}", minutes as u32, seconds - minutes * 60.0)
+}
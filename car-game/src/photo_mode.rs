@@ -0,0 +1,165 @@
+use nalgebra::{Point3, Rotation3, Unit, Vector3};
+use render::PhotoSettings;
+use utils::{Camera, OcclusionFade};
+
+use crate::{
+    debug_controller::DebugCameraController,
+    input::{Action, Input},
+};
+
+/// How far the camera can fly from the car, in meters
+const MAX_RADIUS: f32 = 15.0;
+const MIN_FOVY: f32 = 10.0f32.to_radians();
+const MAX_FOVY: f32 = 100.0f32.to_radians();
+const MAX_ROLL: f32 = 45.0f32.to_radians();
+/// Exposure compensation range, in stops
+const MAX_EXPOSURE: f32 = 3.0;
+const MIN_FOCUS_DISTANCE: f32 = 0.5;
+const MAX_FOCUS_DISTANCE: f32 = 200.0;
+const MAX_APERTURE: f32 = 16.0;
+
+/// Adjustment speeds, per frame at 60fps
+const FOVY_SPEED: f32 = 0.5f32.to_radians();
+const ROLL_SPEED: f32 = 0.5f32.to_radians();
+const EXPOSURE_SPEED: f32 = 0.02;
+/// Fraction of the focus distance moved per frame, so focusing is as fine up close as it is far away
+const FOCUS_SPEED: f32 = 0.015;
+const APERTURE_SPEED: f32 = 0.1;
+
+/// Where the camera was before entering photo mode, to put it back after
+struct SavedCamera {
+    eye: Point3<f32>,
+    target: Point3<f32>,
+    up: Vector3<f32>,
+    fovy: f32,
+    occlusion_fade: Option<OcclusionFade>,
+}
+
+/// Free camera for framing photos while the game is frozen, with adjustable lens settings
+pub struct PhotoMode {
+    camera_controller: DebugCameraController,
+    pub settings: PhotoSettings,
+    roll: f32,
+    saved_camera: SavedCamera,
+
+    zoom_in_pressed: bool,
+    zoom_out_pressed: bool,
+    roll_left_pressed: bool,
+    roll_right_pressed: bool,
+    exposure_up_pressed: bool,
+    exposure_down_pressed: bool,
+    focus_far_pressed: bool,
+    focus_near_pressed: bool,
+    aperture_up_pressed: bool,
+    aperture_down_pressed: bool,
+}
+impl PhotoMode {
+    pub fn new(camera: &Camera) -> PhotoMode {
+        let mut settings = PhotoSettings::new();
+        // starting focused on whatever is being looked at, usually the car
+        settings.focus_distance = nalgebra::distance(&camera.eye, &camera.target)
+            .clamp(MIN_FOCUS_DISTANCE, MAX_FOCUS_DISTANCE);
+        PhotoMode {
+            camera_controller: DebugCameraController::new(),
+            settings,
+            roll: 0.0,
+            saved_camera: SavedCamera {
+                eye: camera.eye,
+                target: camera.target,
+                up: camera.up,
+                fovy: camera.fovy,
+                occlusion_fade: camera.occlusion_fade,
+            },
+            zoom_in_pressed: false,
+            zoom_out_pressed: false,
+            roll_left_pressed: false,
+            roll_right_pressed: false,
+            exposure_up_pressed: false,
+            exposure_down_pressed: false,
+            focus_far_pressed: false,
+            focus_near_pressed: false,
+            aperture_up_pressed: false,
+            aperture_down_pressed: false,
+        }
+    }
+
+    /// Picks up which camera and lens actions are held
+    pub fn handle_input(&mut self, input: &Input) {
+        self.zoom_in_pressed = input.is_held(Action::ZoomIn);
+        self.zoom_out_pressed = input.is_held(Action::ZoomOut);
+        self.roll_left_pressed = input.is_held(Action::RollLeft);
+        self.roll_right_pressed = input.is_held(Action::RollRight);
+        self.exposure_up_pressed = input.is_held(Action::ExposureUp);
+        self.exposure_down_pressed = input.is_held(Action::ExposureDown);
+        self.focus_far_pressed = input.is_held(Action::FocusFar);
+        self.focus_near_pressed = input.is_held(Action::FocusNear);
+        self.aperture_up_pressed = input.is_held(Action::ApertureUp);
+        self.aperture_down_pressed = input.is_held(Action::ApertureDown);
+        self.camera_controller.handle_input(input);
+    }
+
+    pub fn update_camera(&mut self, adjusted_dt: f32, car_pos: Point3<f32>, camera: &mut Camera) {
+        let axis = |positive: bool, negative: bool| {
+            (positive as i32 - negative as i32) as f32 * adjusted_dt
+        };
+
+        camera.fovy = (camera.fovy
+            + axis(self.zoom_out_pressed, self.zoom_in_pressed) * FOVY_SPEED)
+            .clamp(MIN_FOVY, MAX_FOVY);
+        self.roll = (self.roll
+            + axis(self.roll_right_pressed, self.roll_left_pressed) * ROLL_SPEED)
+            .clamp(-MAX_ROLL, MAX_ROLL);
+        let settings = &mut self.settings;
+        settings.exposure = (settings.exposure
+            + axis(self.exposure_up_pressed, self.exposure_down_pressed) * EXPOSURE_SPEED)
+            .clamp(-MAX_EXPOSURE, MAX_EXPOSURE);
+        settings.focus_distance = (settings.focus_distance
+            * (1.0 + axis(self.focus_far_pressed, self.focus_near_pressed) * FOCUS_SPEED))
+            .clamp(MIN_FOCUS_DISTANCE, MAX_FOCUS_DISTANCE);
+        settings.aperture = (settings.aperture
+            + axis(self.aperture_up_pressed, self.aperture_down_pressed) * APERTURE_SPEED)
+            .clamp(0.0, MAX_APERTURE);
+
+        // flying slower when zoomed in
+        let move_scale = camera.fovy / Camera::DEFAULT_FOVY;
+        self.camera_controller
+            .update_camera(adjusted_dt * move_scale, camera);
+
+        // keeping within reach of the car
+        let offset = camera.eye - car_pos;
+        if offset.magnitude() > MAX_RADIUS {
+            let clamped_eye = car_pos + offset.normalize() * MAX_RADIUS;
+            camera.target += clamped_eye - camera.eye;
+            camera.eye = clamped_eye;
+        }
+
+        let facing = Unit::new_normalize(camera.target - camera.eye);
+        camera.up = Rotation3::from_axis_angle(&facing, self.roll) * Vector3::y();
+        camera.occlusion_fade = None;
+    }
+
+    /// Puts the camera back where it was before photo mode
+    pub fn restore_camera(&self, camera: &mut Camera) {
+        camera.eye = self.saved_camera.eye;
+        camera.target = self.saved_camera.target;
+        camera.up = self.saved_camera.up;
+        camera.fovy = self.saved_camera.fovy;
+        camera.occlusion_fade = self.saved_camera.occlusion_fade;
+    }
+
+    /// Current settings, for showing alongside the controls
+    pub fn info_text(&self, camera: &Camera) -> String {
+        format!(
+            "FOV: {:.0}°\nRoll: {:.0}°\nExposure: {:+.1} EV\nFocus: {:.1} m\nAperture: {}",
+            camera.fovy.to_degrees(),
+            self.roll.to_degrees(),
+            self.settings.exposure,
+            self.settings.focus_distance,
+            if self.settings.aperture > 0.0 {
+                format!("{:.1}", self.settings.aperture)
+            } else {
+                "off".to_string()
+            },
+        )
+    }
+}
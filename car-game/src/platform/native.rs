@@ -0,0 +1,236 @@
+use std::{
+    cell::{Cell, RefCell},
+    path::PathBuf,
+    sync::Arc,
+};
+
+use gilrs::{Axis, Button, EventType, GamepadId, Gilrs, GilrsBuilder};
+use instant::Instant;
+use utils::MenuOverlay;
+use winit::{
+    dpi::LogicalSize,
+    event_loop::EventLoopProxy,
+    window::{Window, WindowAttributes},
+};
+
+use crate::{
+    Loaded,
+    gamepad::{GamepadButton, GamepadState},
+    menu::MenuView,
+};
+
+/// Text that changes every frame is only logged this often, in seconds
+const TEXT_LOG_INTERVAL: f32 = 1.0;
+
+thread_local! {
+    /// The menu being shown, which the HUD draws
+    static MENU: RefCell<Option<MenuOverlay>> = const { RefCell::new(None) };
+    static DEBUG_TEXT_SHOWN: Cell<bool> = const { Cell::new(false) };
+    static LAST_TEXT_LOG: Cell<Option<Instant>> = const { Cell::new(None) };
+}
+
+/// Whether enough time has passed to log frequently updated text again
+fn text_log_due() -> bool {
+    LAST_TEXT_LOG.with(|last| {
+        let due = last
+            .get()
+            .is_none_or(|time| time.elapsed().as_secs_f32() >= TEXT_LOG_INTERVAL);
+        if due {
+            last.set(Some(Instant::now()));
+        }
+        due
+    })
+}
+
+/// There is no page to draw the menu on, so the HUD draws it over the game instead
+pub fn show_menu(view: Option<&MenuView>) {
+    MENU.set(view.map(|view| MenuOverlay {
+        title: view.title.clone(),
+        lines: view.lines.clone(),
+        items: view.items.clone(),
+        selected: view.selected,
+    }));
+}
+
+/// The menu for the HUD to draw, if one is shown
+pub fn menu_overlay() -> Option<MenuOverlay> {
+    MENU.with_borrow(Clone::clone)
+}
+
+/// Menu items can only be clicked on the web
+pub fn take_menu_clicks() -> Vec<usize> {
+    Vec::new()
+}
+
+/// Loading is logged as it goes, so there is nothing to hide
+pub fn show_loading(_show: bool) {}
+
+pub fn set_loading_text(string: &str) {
+    log::info!("{string}");
+}
+
+pub fn show_debug_text(show: bool) {
+    DEBUG_TEXT_SHOWN.set(show);
+}
+
+/// There is no overlay to put the debug text in, so it goes to the log
+pub fn set_debug_text(string: &str) {
+    if DEBUG_TEXT_SHOWN.get() && text_log_due() {
+        log::info!("{string}");
+    }
+}
+
+/// Writes `contents` to a file in the working directory
+pub fn save_file(file_name: &str, _mime_type: &str, contents: &[u8]) {
+    match std::fs::write(file_name, contents) {
+        Ok(()) => log::info!("Saved {file_name}"),
+        Err(e) => log::error!("Failed to save {file_name}: {e}"),
+    }
+}
+
+pub fn show_photo_mode(show: bool) {
+    if show {
+        log::info!("Photo mode, press enter to take a photo or escape to go back");
+    }
+}
+
+pub fn set_photo_mode_info(string: &str) {
+    if text_log_due() {
+        log::info!("{}", string.replace('\n', ", "));
+    }
+}
+
+pub fn show_key_bindings(show: bool) {
+    if show {
+        log::info!("Controls");
+    }
+}
+
+pub fn set_key_bindings_text(string: &str) {
+    log::info!("\n{string}");
+}
+
+/// Where the settings file goes, in the platform's usual config directory
+fn settings_path() -> Option<PathBuf> {
+    Some(dirs::config_dir()?.join("car-game").join("settings.json"))
+}
+
+/// The saved settings, if there are any
+pub fn load_settings() -> Option<String> {
+    std::fs::read_to_string(settings_path()?).ok()
+}
+
+pub fn save_settings(contents: &str) {
+    let Some(path) = settings_path() else {
+        log::warn!("Failed to save settings, no config directory found");
+        return;
+    };
+    let result = path
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|()| std::fs::write(&path, contents));
+    if let Err(e) = result {
+        log::error!("Failed to save settings to {}: {e}", path.display());
+    }
+}
+
+pub fn init_logging() {
+    env_logger::Builder::new()
+        .filter_level(if cfg!(debug_assertions) {
+            log::LevelFilter::Debug
+        } else {
+            log::LevelFilter::Info
+        })
+        // the graphics stack is very chatty
+        .filter_module("wgpu_core", log::LevelFilter::Warn)
+        .filter_module("wgpu_hal", log::LevelFilter::Warn)
+        .filter_module("naga", log::LevelFilter::Warn)
+        .parse_default_env()
+        .init();
+}
+
+pub fn window_attributes() -> WindowAttributes {
+    Window::default_attributes()
+        .with_title("Car game")
+        .with_inner_size(LogicalSize::new(1280, 720))
+}
+
+/// Loads the game, sending it into the event loop
+pub fn load_game(window: Arc<Window>, proxy: EventLoopProxy<Loaded>) {
+    if let Some(loaded) = pollster::block_on(crate::load(window)) {
+        assert!(proxy.send_event(loaded).is_ok());
+    }
+}
+
+/// Reads gamepads with gilrs
+pub struct Gamepads {
+    /// Missing if the platform's gamepad backend failed to start
+    gilrs: Option<Gilrs>,
+    /// The gamepad being read from, if any are connected
+    active: Option<GamepadId>,
+}
+impl Gamepads {
+    pub fn new() -> Gamepads {
+        // the game applies its own deadzone
+        let gilrs = match GilrsBuilder::new().with_default_filters(false).build() {
+            Ok(gilrs) => Some(gilrs),
+            Err(e) => {
+                log::warn!("Gamepads are unavailable: {e}");
+                None
+            }
+        };
+        let active = gilrs.as_ref().and_then(|gilrs| {
+            let (id, gamepad) = gilrs.gamepads().next()?;
+            log::info!("Using gamepad {}", gamepad.name());
+            Some(id)
+        });
+        Gamepads { gilrs, active }
+    }
+
+    /// The state of the active gamepad, switching to another one if it was unplugged
+    pub fn poll(&mut self) -> Option<GamepadState> {
+        let gilrs = self.gilrs.as_mut()?;
+        while let Some(event) = gilrs.next_event() {
+            match event.event {
+                EventType::Connected => {
+                    let name = gilrs.gamepad(event.id).name().to_string();
+                    log::info!("Gamepad connected: {name}");
+                    if self.active.is_none() {
+                        log::info!("Using gamepad {name}");
+                        self.active = Some(event.id);
+                    }
+                }
+                EventType::Disconnected => {
+                    log::info!("Gamepad disconnected: {}", gilrs.gamepad(event.id).name());
+                    if self.active == Some(event.id) {
+                        self.active = gilrs.gamepads().next().map(|(id, gamepad)| {
+                            log::info!("Using gamepad {}", gamepad.name());
+                            id
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let gamepad = gilrs.connected_gamepad(self.active?)?;
+        let trigger = |button| gamepad.button_data(button).map_or(0.0, |data| data.value());
+        Some(GamepadState {
+            left_stick_x: gamepad.value(Axis::LeftStickX),
+            left_stick_y: gamepad.value(Axis::LeftStickY),
+            left_trigger: trigger(Button::LeftTrigger2),
+            right_trigger: trigger(Button::RightTrigger2),
+            pressed: [
+                (Button::South, GamepadButton::South),
+                (Button::North, GamepadButton::North),
+                (Button::Start, GamepadButton::Start),
+                (Button::DPadUp, GamepadButton::DpadUp),
+                (Button::DPadDown, GamepadButton::DpadDown),
+            ]
+            .into_iter()
+            .filter(|(button, _)| gamepad.is_pressed(*button))
+            .map(|(_, button)| button)
+            .collect(),
+        })
+    }
+}
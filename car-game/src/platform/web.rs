@@ -0,0 +1,209 @@
+use std::{cell::RefCell, sync::Arc};
+
+use utils::MenuOverlay;
+use wasm_bindgen::{JsCast, prelude::*};
+use web_sys::GamepadMappingType;
+use winit::{
+    event_loop::EventLoopProxy,
+    platform::web::WindowAttributesExtWebSys,
+    window::{Window, WindowAttributes},
+};
+
+use crate::{
+    Loaded,
+    gamepad::{GamepadButton, GamepadState},
+    menu::MenuView,
+};
+
+#[wasm_bindgen]
+extern "C" {
+    /// Draws the menu from its view as JSON, or hides it given `null`
+    #[wasm_bindgen(js_name = setMenu)]
+    fn set_menu(json: &str);
+
+    #[wasm_bindgen(js_name = showLoading)]
+    pub fn show_loading(show: bool);
+
+    #[wasm_bindgen(js_name = setLoadingText)]
+    pub fn set_loading_text(string: &str);
+
+    #[wasm_bindgen(js_name = showDebugText)]
+    pub fn show_debug_text(show: bool);
+
+    #[wasm_bindgen(js_name = setDebugText)]
+    pub fn set_debug_text(string: &str);
+
+    /// Offers `contents` to the user as a file download
+    #[wasm_bindgen(js_name = downloadFile)]
+    pub fn save_file(file_name: &str, mime_type: &str, contents: &[u8]);
+
+    #[wasm_bindgen(js_name = showPhotoMode)]
+    pub fn show_photo_mode(show: bool);
+
+    #[wasm_bindgen(js_name = setPhotoModeInfo)]
+    pub fn set_photo_mode_info(string: &str);
+
+    #[wasm_bindgen(js_name = showKeyBindings)]
+    pub fn show_key_bindings(show: bool);
+
+    #[wasm_bindgen(js_name = setKeyBindingsText)]
+    pub fn set_key_bindings_text(string: &str);
+}
+
+thread_local! {
+    /// Menu items clicked on the page since the last frame
+    static MENU_CLICKS: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Called by the page when a menu item is clicked
+#[wasm_bindgen(js_name = menuClick)]
+pub fn menu_click(index: usize) {
+    MENU_CLICKS.with_borrow_mut(|clicks| clicks.push(index));
+}
+
+/// Menu items clicked since this was last called, in order
+pub fn take_menu_clicks() -> Vec<usize> {
+    MENU_CLICKS.take()
+}
+
+pub fn show_menu(view: Option<&MenuView>) {
+    match view.map(serde_json::to_string) {
+        Some(Ok(json)) => set_menu(&json),
+        Some(Err(e)) => log::error!("Failed to serialize menu: {e}"),
+        None => set_menu("null"),
+    }
+}
+
+/// The page draws the menu, so the HUD doesn't need to
+pub fn menu_overlay() -> Option<MenuOverlay> {
+    None
+}
+
+/// `localStorage` key the settings are kept under
+const SETTINGS_KEY: &str = "car-game-settings";
+
+fn local_storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok()?
+}
+
+/// The saved settings, if there are any
+pub fn load_settings() -> Option<String> {
+    local_storage()?.get_item(SETTINGS_KEY).ok()?
+}
+
+pub fn save_settings(contents: &str) {
+    let saved =
+        local_storage().is_some_and(|storage| storage.set_item(SETTINGS_KEY, contents).is_ok());
+    if !saved {
+        log::warn!("Failed to save settings, local storage is unavailable");
+    }
+}
+
+pub fn init_logging() {
+    console_error_panic_hook::set_once();
+    console_log::init_with_level(if cfg!(debug_assertions) {
+        log::Level::Debug
+    } else {
+        log::Level::Info
+    })
+    .expect_throw("Failed to initialize console logging");
+}
+
+/// Window drawing into the canvas element with the given id
+pub fn window_attributes(canvas_id: &str) -> WindowAttributes {
+    let window = wgpu::web_sys::window().expect_throw("Failed to get window");
+    let document = window.document().expect_throw("Failed to get document");
+    let canvas = document
+        .get_element_by_id(canvas_id)
+        .expect_throw("Failed to find canvas in document");
+    let html_canvas_element = canvas.unchecked_into();
+    Window::default_attributes().with_canvas(Some(html_canvas_element))
+}
+
+/// Loads the game asyncronously, sending it into the event loop once it is ready
+pub fn load_game(window: Arc<Window>, proxy: EventLoopProxy<Loaded>) {
+    wasm_bindgen_futures::spawn_local(async move {
+        if let Some(loaded) = crate::load(window).await {
+            assert!(proxy.send_event(loaded).is_ok());
+        }
+    });
+}
+
+/// Indices of the buttons and axes the game uses in the Gamepad API's standard layout
+const GAMEPAD_SOUTH: u32 = 0;
+const GAMEPAD_NORTH: u32 = 3;
+const GAMEPAD_LEFT_TRIGGER: u32 = 6;
+const GAMEPAD_RIGHT_TRIGGER: u32 = 7;
+const GAMEPAD_START: u32 = 9;
+const GAMEPAD_DPAD_UP: u32 = 12;
+const GAMEPAD_DPAD_DOWN: u32 = 13;
+const GAMEPAD_LEFT_STICK_X: u32 = 0;
+const GAMEPAD_LEFT_STICK_Y: u32 = 1;
+
+/// Reads gamepads with the browser's Gamepad API
+///
+/// Browsers only list a gamepad once one of its buttons has been pressed
+pub struct Gamepads {
+    /// Index and id of the gamepad being read from, if any are connected
+    active: Option<(u32, String)>,
+}
+impl Gamepads {
+    pub fn new() -> Gamepads {
+        Gamepads { active: None }
+    }
+
+    /// The state of the active gamepad, switching to another one if it was unplugged
+    pub fn poll(&mut self) -> Option<GamepadState> {
+        let connected: Vec<web_sys::Gamepad> = web_sys::window()?
+            .navigator()
+            .get_gamepads()
+            .ok()?
+            .iter()
+            .filter_map(|gamepad| gamepad.dyn_into::<web_sys::Gamepad>().ok())
+            .filter(web_sys::Gamepad::connected)
+            .collect();
+
+        let still_connected = self.active.as_ref().is_some_and(|(index, id)| {
+            connected
+                .iter()
+                .any(|gamepad| gamepad.index() == *index && gamepad.id() == *id)
+        });
+        if !still_connected {
+            if let Some((_, id)) = self.active.take() {
+                log::info!("Gamepad disconnected: {id}");
+            }
+            if let Some(gamepad) = connected.first() {
+                log::info!("Using gamepad {}", gamepad.id());
+                if gamepad.mapping() != GamepadMappingType::Standard {
+                    log::warn!("Gamepad has no standard mapping, its controls may be mixed up");
+                }
+                self.active = Some((gamepad.index(), gamepad.id()));
+            }
+        }
+
+        let (index, _) = self.active.as_ref()?;
+        let gamepad = connected.iter().find(|gamepad| gamepad.index() == *index)?;
+        let buttons = gamepad.buttons();
+        let button = |index| buttons.get(index).dyn_into::<web_sys::GamepadButton>().ok();
+        let value = |index| button(index).map_or(0.0, |button| button.value() as f32);
+        let axis = |index| gamepad.axes().get(index).as_f64().unwrap_or(0.0) as f32;
+        Some(GamepadState {
+            left_stick_x: axis(GAMEPAD_LEFT_STICK_X),
+            // the standard layout points y down
+            left_stick_y: -axis(GAMEPAD_LEFT_STICK_Y),
+            left_trigger: value(GAMEPAD_LEFT_TRIGGER),
+            right_trigger: value(GAMEPAD_RIGHT_TRIGGER),
+            pressed: [
+                (GAMEPAD_SOUTH, GamepadButton::South),
+                (GAMEPAD_NORTH, GamepadButton::North),
+                (GAMEPAD_START, GamepadButton::Start),
+                (GAMEPAD_DPAD_UP, GamepadButton::DpadUp),
+                (GAMEPAD_DPAD_DOWN, GamepadButton::DpadDown),
+            ]
+            .into_iter()
+            .filter(|(index, _)| button(*index).is_some_and(|button| button.pressed()))
+            .map(|(_, button)| button)
+            .collect(),
+        })
+    }
+}
@@ -0,0 +1,288 @@
+use winit::keyboard::KeyCode;
+
+use crate::input::{Action, KeyBindings, key_name};
+
+/// What the menu wants done after a key press
+pub enum RebindMenuOutcome {
+    Stay,
+    /// The bindings were changed
+    Changed,
+    Close,
+}
+
+/// Actions the menu is driven with, which always keep at least one key so it can't be locked up
+const NAVIGATION: [Action; 4] = [
+    Action::MenuUp,
+    Action::MenuDown,
+    Action::MenuSelect,
+    Action::Pause,
+];
+
+/// What the next key press does
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Browsing,
+    /// The key is bound to the selected action
+    Capturing,
+    /// Selecting again resets every binding
+    ConfirmingReset,
+}
+
+/// Pause menu screen for changing which keys trigger each action
+///
+/// Menu up and down pick an action, menu select waits for a key to add to it, backspace clears it and pause goes
+/// back. Below the actions is an entry to reset every binding to its default, which has to be selected twice
+pub struct RebindMenu {
+    /// Index into [`Action::ALL`], or one past it for the reset entry
+    selected: usize,
+    mode: Mode,
+    /// Feedback on the last change, shown under the list
+    message: Option<String>,
+}
+impl RebindMenu {
+    pub fn new() -> RebindMenu {
+        RebindMenu {
+            selected: 0,
+            mode: Mode::Browsing,
+            message: None,
+        }
+    }
+
+    /// The selected action, `None` when the reset entry is selected
+    fn selected_action(&self) -> Option<Action> {
+        Action::ALL.get(self.selected).copied()
+    }
+
+    /// Handles a key being pressed while the menu is open, given the actions it's bound to
+    pub fn handle_key_press(
+        &mut self,
+        key: KeyCode,
+        actions: &[Action],
+        bindings: &mut KeyBindings,
+    ) -> RebindMenuOutcome {
+        let n_entries = Action::ALL.len() + 1;
+        match self.mode {
+            Mode::Capturing => {
+                self.mode = Mode::Browsing;
+                let Some(action) = self.selected_action() else {
+                    return RebindMenuOutcome::Stay;
+                };
+                if actions.contains(&Action::Pause) {
+                    self.message = None;
+                    return RebindMenuOutcome::Stay;
+                }
+                self.bind(action, key, bindings)
+            }
+            Mode::ConfirmingReset => {
+                self.mode = Mode::Browsing;
+                if actions.contains(&Action::MenuSelect) {
+                    *bindings = KeyBindings::default();
+                    self.message = Some("Reset all controls to their defaults".to_string());
+                    RebindMenuOutcome::Changed
+                } else {
+                    self.message = None;
+                    RebindMenuOutcome::Stay
+                }
+            }
+            Mode::Browsing if actions.contains(&Action::Pause) => RebindMenuOutcome::Close,
+            Mode::Browsing if actions.contains(&Action::MenuUp) => {
+                self.selected = (self.selected + n_entries - 1) % n_entries;
+                RebindMenuOutcome::Stay
+            }
+            Mode::Browsing if actions.contains(&Action::MenuDown) => {
+                self.selected = (self.selected + 1) % n_entries;
+                RebindMenuOutcome::Stay
+            }
+            Mode::Browsing if actions.contains(&Action::MenuSelect) => {
+                if self.selected_action().is_some() {
+                    self.mode = Mode::Capturing;
+                    self.message = None;
+                } else {
+                    self.mode = Mode::ConfirmingReset;
+                    self.message = Some(format!(
+                        "Press {} again to reset every control, or any other key to cancel",
+                        keys_text(bindings.keys(Action::MenuSelect))
+                    ));
+                }
+                RebindMenuOutcome::Stay
+            }
+            Mode::Browsing if matches!(key, KeyCode::Backspace | KeyCode::Delete) => {
+                let Some(action) = self.selected_action() else {
+                    return RebindMenuOutcome::Stay;
+                };
+                if NAVIGATION.contains(&action) {
+                    self.message = Some(format!("{} needs a key", action.name()));
+                    return RebindMenuOutcome::Stay;
+                }
+                bindings.clear(action);
+                self.message = Some(format!("Cleared {}", action.name()));
+                RebindMenuOutcome::Changed
+            }
+            Mode::Browsing => RebindMenuOutcome::Stay,
+        }
+    }
+
+    /// Binds the key, unless that would leave one of the menu's own actions without any keys
+    fn bind(
+        &mut self,
+        action: Action,
+        key: KeyCode,
+        bindings: &mut KeyBindings,
+    ) -> RebindMenuOutcome {
+        let mut changed = bindings.clone();
+        let unbound = changed.bind(action, key);
+        if let Some(stranded) = NAVIGATION
+            .iter()
+            .find(|action| changed.keys(**action).is_empty())
+        {
+            self.message = Some(format!(
+                "Can't bind {}, {} would have no keys left",
+                key_name(key),
+                stranded.name()
+            ));
+            return RebindMenuOutcome::Stay;
+        }
+        *bindings = changed;
+        self.message = Some(if unbound.is_empty() {
+            format!("Bound {} to {}", key_name(key), action.name())
+        } else {
+            format!(
+                "Bound {} to {}, unbinding it from {}",
+                key_name(key),
+                action.name(),
+                unbound
+                    .iter()
+                    .map(Action::name)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        });
+        RebindMenuOutcome::Changed
+    }
+
+    /// The action list with the current bindings and how to use the menu, for showing in the menu
+    pub fn text(&self, bindings: &KeyBindings) -> String {
+        let mut text = String::new();
+        for (i, action) in Action::ALL.iter().enumerate() {
+            let keys = if i == self.selected && self.mode == Mode::Capturing {
+                "press a key...".to_string()
+            } else {
+                keys_text(bindings.keys(*action))
+            };
+            let cursor = if i == self.selected { "> " } else { "  " };
+            text.push_str(&format!("{cursor}{}: {keys}\n", action.name()));
+        }
+        let cursor = if self.selected == Action::ALL.len() {
+            "> "
+        } else {
+            "  "
+        };
+        text.push_str(&format!("{cursor}Reset all controls\n"));
+        if let Some(message) = &self.message {
+            text.push_str(&format!("\n{message}\n"));
+        }
+        for (key, a, b) in bindings.conflicts() {
+            text.push_str(&format!(
+                "\nConflict: {} is bound to both {} and {}",
+                key_name(key),
+                a.name(),
+                b.name()
+            ));
+        }
+        text.push_str(&format!(
+            "\n\n{}/{}: pick an action\n{}: bind a key to it\nBackspace: clear its keys\n{}: back to menu",
+            keys_text(bindings.keys(Action::MenuUp)),
+            keys_text(bindings.keys(Action::MenuDown)),
+            keys_text(bindings.keys(Action::MenuSelect)),
+            keys_text(bindings.keys(Action::Pause)),
+        ));
+        text
+    }
+}
+
+fn keys_text(keys: &[KeyCode]) -> String {
+    if keys.is_empty() {
+        "unbound".to_string()
+    } else {
+        keys.iter()
+            .map(|key| key_name(*key))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Presses a key, the way the game does with the current bindings
+    fn press(menu: &mut RebindMenu, key: KeyCode, bindings: &mut KeyBindings) -> RebindMenuOutcome {
+        let actions: Vec<Action> = bindings.actions(key).collect();
+        menu.handle_key_press(key, &actions, bindings)
+    }
+
+    #[test]
+    fn reset_needs_confirming() {
+        let mut menu = RebindMenu::new();
+        let mut bindings = KeyBindings::default();
+        bindings.clear(Action::Accelerate);
+        // up from the first action wraps around to the reset entry
+        press(&mut menu, KeyCode::ArrowUp, &mut bindings);
+        assert!(matches!(
+            press(&mut menu, KeyCode::Enter, &mut bindings),
+            RebindMenuOutcome::Stay
+        ));
+        assert!(matches!(
+            press(&mut menu, KeyCode::KeyW, &mut bindings),
+            RebindMenuOutcome::Stay
+        ));
+        assert!(bindings.keys(Action::Accelerate).is_empty());
+
+        press(&mut menu, KeyCode::Enter, &mut bindings);
+        assert!(matches!(
+            press(&mut menu, KeyCode::Space, &mut bindings),
+            RebindMenuOutcome::Changed
+        ));
+        assert_eq!(bindings, KeyBindings::default());
+    }
+
+    #[test]
+    fn navigates_with_rebound_keys() {
+        let mut menu = RebindMenu::new();
+        let mut bindings = KeyBindings::default();
+        bindings.bind(Action::MenuDown, KeyCode::KeyJ);
+        bindings.bind(Action::MenuSelect, KeyCode::KeyL);
+        press(&mut menu, KeyCode::KeyJ, &mut bindings);
+        assert_eq!(menu.selected_action(), Some(Action::ALL[1]));
+        press(&mut menu, KeyCode::KeyL, &mut bindings);
+        assert!(matches!(
+            press(&mut menu, KeyCode::KeyB, &mut bindings),
+            RebindMenuOutcome::Changed
+        ));
+        assert_eq!(
+            bindings.keys(Action::ALL[1]),
+            [KeyCode::KeyS, KeyCode::KeyB]
+        );
+        assert!(matches!(
+            press(&mut menu, KeyCode::Escape, &mut bindings),
+            RebindMenuOutcome::Close
+        ));
+    }
+
+    #[test]
+    fn menu_actions_keep_a_key() {
+        let mut menu = RebindMenu::new();
+        let mut bindings = KeyBindings::default();
+        menu.selected = Action::ALL
+            .iter()
+            .position(|a| *a == Action::MenuUp)
+            .unwrap();
+        press(&mut menu, KeyCode::Backspace, &mut bindings);
+        assert_eq!(bindings.keys(Action::MenuUp), [KeyCode::ArrowUp]);
+
+        // taking the only menu down key for menu up
+        press(&mut menu, KeyCode::Enter, &mut bindings);
+        press(&mut menu, KeyCode::ArrowDown, &mut bindings);
+        assert_eq!(bindings, KeyBindings::default());
+    }
+}
@@ -0,0 +1,311 @@
+use audio::Audio;
+use render::RenderState;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sim::{CameraRig, CarKind, DrivingAssists, GameSimulation};
+use utils::SpeedUnit;
+
+use crate::{
+    gamepad::GamepadInput,
+    input::{Input, KeyBindings},
+    menu::GameMode,
+    platform,
+};
+
+/// Most the camera's field of view can be widened or narrowed by, in degrees
+const MAX_FOV_OFFSET: f32 = 30.0;
+/// Largest the gamepad stick's deadzone can be, any more and steering becomes all or nothing
+const MAX_DEADZONE: f32 = 0.9;
+/// Range the gamepad steering curve's exponent is kept in
+const STEERING_CURVE_RANGE: (f32, f32) = (0.2, 5.0);
+
+/// Everything the player can change, saved between sessions
+///
+/// Settings are stored as JSON, in `localStorage` on the web and in the config directory natively. Missing fields
+/// fall back to their defaults and unknown ones are ignored, so adding a setting doesn't need a new schema version;
+/// only renaming or reinterpreting one does, along with a migration
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Schema version these settings were saved with
+    version: u32,
+    pub graphics: GraphicsSettings,
+    pub camera: CameraSettings,
+    pub speed_unit: SpeedUnit,
+    pub key_bindings: KeyBindings,
+    pub gamepad: GamepadSettings,
+    pub assists: DrivingAssists,
+    pub audio: AudioSettings,
+    /// Car and mode picked on the main menu, used for the next run
+    pub car: CarKind,
+    pub mode: GameMode,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GraphicsSettings {
+    pub vsync: bool,
+    pub shadows: bool,
+    pub minimap: bool,
+    /// Whether the minimap turns with the car, instead of keeping north up
+    pub minimap_heading_up: bool,
+}
+impl Default for GraphicsSettings {
+    fn default() -> Self {
+        GraphicsSettings {
+            vsync: true,
+            shadows: true,
+            minimap: true,
+            minimap_heading_up: true,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraSettings {
+    /// Rig the camera starts on, the last one picked
+    pub rig: CameraRig,
+    /// Widens (or narrows when negative) the field of view of every rig, in degrees
+    pub fov_offset: f32,
+}
+impl Default for CameraSettings {
+    fn default() -> Self {
+        CameraSettings {
+            rig: CameraRig::ChaseNear,
+            fov_offset: 0.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GamepadSettings {
+    /// Stick travel from the center that is ignored, from 0 to 1
+    pub deadzone: f32,
+    /// Exponent of the steering response, above 1 makes small stick movements steer less
+    pub steering_curve: f32,
+}
+impl Default for GamepadSettings {
+    fn default() -> Self {
+        GamepadSettings {
+            deadzone: 0.15,
+            steering_curve: 1.5,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioSettings {
+    /// Master volume, from 0 (muted) to 1
+    pub volume: f32,
+}
+impl Default for AudioSettings {
+    fn default() -> Self {
+        AudioSettings { volume: 0.8 }
+    }
+}
+
+impl Settings {
+    /// Current schema version
+    const VERSION: u32 = 2;
+    /// Upgrades saved settings a schema version at a time, `MIGRATIONS[i]` taking version `i + 1` to `i + 2`
+    const MIGRATIONS: &[fn(&mut Value)] = &[migrate_car_key_bindings];
+
+    /// The saved settings, or the defaults if there aren't any or they can't be read
+    pub fn load() -> Settings {
+        let Some(json) = platform::load_settings() else {
+            log::info!("No saved settings, using defaults");
+            return Settings::default();
+        };
+        match Self::from_json(&json) {
+            Ok(settings) => {
+                log::info!("Loaded settings");
+                settings
+            }
+            Err(e) => {
+                log::warn!("Failed to read saved settings, using defaults: {e}");
+                Settings::default()
+            }
+        }
+    }
+
+    /// Saves the settings, unless they came from a newer version of the game, which would lose whatever it added
+    pub fn save(&self) {
+        if self.version > Self::VERSION {
+            log::warn!(
+                "Not saving settings, they were saved by a newer version (schema {})",
+                self.version
+            );
+            return;
+        }
+        match serde_json::to_string_pretty(self) {
+            Ok(json) => platform::save_settings(&json),
+            Err(e) => log::error!("Failed to serialize settings: {e}"),
+        }
+    }
+
+    /// Reads settings saved with any schema version, migrating them to the current one
+    fn from_json(json: &str) -> serde_json::Result<Settings> {
+        let mut value: Value = serde_json::from_str(json)?;
+        let version = value
+            .get("version")
+            .and_then(Value::as_u64)
+            .map_or(1, |version| version as u32);
+        if version > Self::VERSION {
+            log::warn!(
+                "Settings were saved by a newer version (schema {version}), some may be ignored"
+            );
+        } else {
+            for migrate in Self::MIGRATIONS.iter().skip(version.max(1) as usize - 1) {
+                migrate(&mut value);
+            }
+        }
+
+        let mut settings: Settings = serde_json::from_value(value)?;
+        // newer versions are kept, so that they aren't saved over
+        settings.version = version.max(Self::VERSION);
+        Ok(settings)
+    }
+
+    /// Puts the graphics and HUD settings into effect
+    pub fn apply_to_renderer(&self, render_state: &mut RenderState) {
+        render_state.set_vsync(self.graphics.vsync);
+        render_state.scene.shadow_mapper.enabled = self.graphics.shadows;
+        render_state.scene.minimap.visible = self.graphics.minimap;
+        render_state.scene.minimap.heading_up = self.graphics.minimap_heading_up;
+        render_state.hud.speed_unit = self.speed_unit;
+    }
+
+    /// Puts the camera and driving settings into effect
+    pub fn apply_to_sim(&self, sim: &mut GameSimulation) {
+        sim.camera_rigs.set_rig(self.camera.rig);
+        sim.camera_rigs.fov_offset = self
+            .camera
+            .fov_offset
+            .clamp(-MAX_FOV_OFFSET, MAX_FOV_OFFSET)
+            .to_radians();
+        sim.assists = self.assists;
+    }
+
+    pub fn apply_to_input(&self, input: &mut Input) {
+        input.bindings = self.key_bindings.clone();
+        for (key, a, b) in self.key_bindings.conflicts() {
+            log::warn!("{key:?} is bound to both {a:?} and {b:?}");
+        }
+    }
+
+    pub fn apply_to_audio(&self, audio: &mut Audio) {
+        audio.set_volume(self.audio.volume);
+    }
+
+    pub fn apply_to_gamepad(&self, gamepad: &mut GamepadInput) {
+        gamepad.deadzone = self.gamepad.deadzone.clamp(0.0, MAX_DEADZONE);
+        let (min_curve, max_curve) = STEERING_CURVE_RANGE;
+        gamepad.steering_curve = self.gamepad.steering_curve.clamp(min_curve, max_curve);
+    }
+}
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            version: Self::VERSION,
+            graphics: GraphicsSettings::default(),
+            camera: CameraSettings::default(),
+            speed_unit: SpeedUnit::default(),
+            key_bindings: KeyBindings::default(),
+            gamepad: GamepadSettings::default(),
+            assists: DrivingAssists::default(),
+            audio: AudioSettings::default(),
+            car: CarKind::default(),
+            mode: GameMode::default(),
+        }
+    }
+}
+
+/// Version 1 only bound the driving controls, as fields named after them. They are now one map for every action
+fn migrate_car_key_bindings(settings: &mut Value) {
+    const RENAMES: [(&str, &str); 5] = [
+        ("accelerate", "Accelerate"),
+        ("brake", "Brake"),
+        ("steer_left", "SteerLeft"),
+        ("steer_right", "SteerRight"),
+        ("handbrake", "Handbrake"),
+    ];
+    if let Some(Value::Object(bindings)) = settings.get_mut("key_bindings") {
+        for (old, new) in RENAMES {
+            if let Some(keys) = bindings.remove(old) {
+                bindings.insert(new.to_string(), keys);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use winit::keyboard::KeyCode;
+
+    use super::*;
+    use crate::input::Action;
+
+    #[test]
+    fn migrates_version_1() {
+        let json = r#"{
+            "graphics": { "vsync": false },
+            "key_bindings": {
+                "accelerate": ["ArrowUp"],
+                "steer_left": ["KeyQ", "ArrowLeft"],
+                "handbrake": []
+            }
+        }"#;
+        let settings = Settings::from_json(json).unwrap();
+        assert_eq!(settings.version, Settings::VERSION);
+        let bindings = &settings.key_bindings;
+        assert_eq!(bindings.keys(Action::Accelerate), [KeyCode::ArrowUp]);
+        assert_eq!(
+            bindings.keys(Action::SteerLeft),
+            [KeyCode::KeyQ, KeyCode::ArrowLeft]
+        );
+        assert!(bindings.keys(Action::Handbrake).is_empty());
+        // not in version 1
+        assert_eq!(bindings.keys(Action::Brake), [KeyCode::KeyS]);
+        assert_eq!(bindings.keys(Action::Pause), [KeyCode::Escape]);
+        assert!(!settings.graphics.vsync);
+        assert!(settings.graphics.shadows);
+    }
+
+    #[test]
+    fn current_version_round_trips() {
+        let mut settings = Settings::default();
+        settings.audio.volume = 0.25;
+        settings.key_bindings.bind(Action::Pause, KeyCode::KeyP);
+        let json = serde_json::to_string(&settings).unwrap();
+        assert_eq!(Settings::from_json(&json).unwrap(), settings);
+    }
+
+    #[test]
+    fn missing_fields_get_defaults() {
+        let settings = Settings::from_json(r#"{ "version": 2, "audio": {} }"#).unwrap();
+        assert_eq!(settings, Settings::default());
+
+        let settings = Settings::from_json(r#"{ "camera": { "fov_offset": 10.0 } }"#).unwrap();
+        assert_eq!(settings.camera.fov_offset, 10.0);
+        assert_eq!(settings.camera.rig, CameraSettings::default().rig);
+        assert_eq!(settings.gamepad, GamepadSettings::default());
+    }
+
+    #[test]
+    fn malformed_json_fails() {
+        assert!(Settings::from_json("{ \"version\": 2,").is_err());
+        assert!(Settings::from_json(r#"{ "audio": { "volume": "loud" } }"#).is_err());
+        assert!(Settings::from_json(r#"{ "key_bindings": { "Fly": ["KeyW"] } }"#).is_err());
+    }
+
+    #[test]
+    fn newer_versions_are_kept() {
+        let json = r#"{ "version": 99, "audio": { "volume": 0.5 }, "jetpack": true }"#;
+        let settings = Settings::from_json(json).unwrap();
+        assert_eq!(settings.version, 99);
+        assert_eq!(settings.audio.volume, 0.5);
+    }
+}
@@ -0,0 +1,237 @@
+use nalgebra::Vector2;
+use utils::{TouchElement, TouchElementKind, TouchOverlay};
+use winit::event::{Touch, TouchPhase};
+
+/// Logical screen height the controls are sized for, they shrink on shorter screens
+const MIN_LOGICAL_HEIGHT: f32 = 400.0;
+/// Sizes in logical pixels
+const MARGIN: f32 = 16.0;
+const BUTTON_SIZE: f32 = 48.0;
+const PEDAL_SIZE: Vector2<f32> = Vector2::new(64.0, 140.0);
+const SLIDER_SIZE: Vector2<f32> = Vector2::new(300.0, 56.0);
+/// Height the slider and pedals sit on, as a fraction of the screen height, clear of the speedometer and minimap
+const CONTROLS_BOTTOM: f32 = 0.55;
+/// Lightest a pedal can be pressed, at its very bottom
+const MIN_PEDAL: f32 = 0.25;
+
+/// The touch controls
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Control {
+    Steering,
+    Throttle,
+    Brake,
+    Pause,
+    Camera,
+}
+
+/// Buttons that do something once when tapped
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TouchButton {
+    Pause,
+    Camera,
+}
+
+/// Where each control is on screen, in physical pixels
+struct Layout {
+    steering: (Vector2<f32>, Vector2<f32>),
+    throttle: (Vector2<f32>, Vector2<f32>),
+    brake: (Vector2<f32>, Vector2<f32>),
+    pause: (Vector2<f32>, Vector2<f32>),
+    camera: (Vector2<f32>, Vector2<f32>),
+}
+impl Layout {
+    fn new(width: f32, height: f32, scale_factor: f32) -> Layout {
+        // physical pixels per logical pixel, shrunk to fit short screens
+        let unit = scale_factor.min(height / MIN_LOGICAL_HEIGHT);
+        let margin = MARGIN * unit;
+        let bottom = height * CONTROLS_BOTTOM;
+
+        let button = Vector2::repeat(BUTTON_SIZE * unit);
+        let pause_pos = Vector2::new(width - margin - button.x, margin);
+        let camera_pos = Vector2::new(pause_pos.x - margin - button.x, margin);
+
+        let pedal = Vector2::new(PEDAL_SIZE.x * unit, (PEDAL_SIZE.y * unit).min(height * 0.3));
+        let throttle_pos = Vector2::new(width - margin - pedal.x, bottom - pedal.y);
+        let brake_pos = Vector2::new(throttle_pos.x - margin - pedal.x, bottom - pedal.y);
+
+        let slider = Vector2::new(
+            (SLIDER_SIZE.x * unit).min(width * 0.35),
+            SLIDER_SIZE.y * unit,
+        );
+        let slider_pos = Vector2::new(margin, bottom - slider.y);
+
+        Layout {
+            steering: (slider_pos, slider),
+            throttle: (throttle_pos, pedal),
+            brake: (brake_pos, pedal),
+            pause: (pause_pos, button),
+            camera: (camera_pos, button),
+        }
+    }
+
+    fn rect(&self, control: Control) -> (Vector2<f32>, Vector2<f32>) {
+        match control {
+            Control::Steering => self.steering,
+            Control::Throttle => self.throttle,
+            Control::Brake => self.brake,
+            Control::Pause => self.pause,
+            Control::Camera => self.camera,
+        }
+    }
+
+    /// The control under a point, with some slack around each so near misses still count
+    fn hit(&self, point: Vector2<f32>) -> Option<Control> {
+        [
+            Control::Steering,
+            Control::Throttle,
+            Control::Brake,
+            Control::Pause,
+            Control::Camera,
+        ]
+        .into_iter()
+        .find(|control| {
+            let (pos, size) = self.rect(*control);
+            let slack = size.min() * 0.1;
+            point.x >= pos.x - slack
+                && point.x <= pos.x + size.x + slack
+                && point.y >= pos.y - slack
+                && point.y <= pos.y + size.y + slack
+        })
+    }
+}
+
+/// On-screen driving controls for touch screens
+///
+/// Each finger sticks to the control it first touched, so sliding off the steering slider keeps steering. The
+/// controls only show up once the screen has been touched
+pub struct TouchControls {
+    /// Whether the controls are shown and used
+    pub active: bool,
+    layout: Layout,
+    /// Fingers on the screen, with the control each one is holding and where it is
+    fingers: Vec<(u64, Control, Vector2<f32>)>,
+}
+impl TouchControls {
+    pub fn new() -> TouchControls {
+        TouchControls {
+            active: false,
+            layout: Layout::new(1.0, 1.0, 1.0),
+            fingers: Vec::new(),
+        }
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32, scale_factor: f32) {
+        self.layout = Layout::new(width as f32, height as f32, scale_factor);
+    }
+
+    /// Tracks a finger, returning the button it tapped if any
+    pub fn handle_touch(&mut self, touch: &Touch) -> Option<TouchButton> {
+        self.active = true;
+        let point = Vector2::new(touch.location.x as f32, touch.location.y as f32);
+        match touch.phase {
+            TouchPhase::Started => {
+                let control = self.layout.hit(point)?;
+                self.fingers.push((touch.id, control, point));
+                match control {
+                    Control::Pause => Some(TouchButton::Pause),
+                    Control::Camera => Some(TouchButton::Camera),
+                    _ => None,
+                }
+            }
+            TouchPhase::Moved => {
+                if let Some(finger) = self.fingers.iter_mut().find(|f| f.0 == touch.id) {
+                    finger.2 = point;
+                }
+                None
+            }
+            TouchPhase::Ended | TouchPhase::Cancelled => {
+                self.fingers.retain(|f| f.0 != touch.id);
+                None
+            }
+        }
+    }
+
+    /// Lets go of every control, for when touches may have ended unseen
+    pub fn release_all(&mut self) {
+        self.fingers.clear();
+    }
+
+    /// Steering from the slider, from -1 (full left) to 1 (full right)
+    pub fn steer(&self) -> f32 {
+        let (pos, size) = self.layout.steering;
+        self.fingers
+            .iter()
+            .find(|f| f.1 == Control::Steering)
+            .map_or(0.0, |f| {
+                let half_travel = (size.x - size.y) * 0.5;
+                ((f.2.x - (pos.x + size.x * 0.5)) / half_travel).clamp(-1.0, 1.0)
+            })
+    }
+
+    pub fn throttle(&self) -> f32 {
+        self.pedal(Control::Throttle)
+    }
+
+    pub fn brake(&self) -> f32 {
+        self.pedal(Control::Brake)
+    }
+
+    /// How far a pedal is pressed, more the higher up it is touched
+    fn pedal(&self, control: Control) -> f32 {
+        let (pos, size) = self.layout.rect(control);
+        self.fingers
+            .iter()
+            .filter(|f| f.1 == control)
+            .map(|f| ((pos.y + size.y - f.2.y) / size.y).clamp(MIN_PEDAL, 1.0))
+            .fold(0.0, f32::max)
+    }
+
+    fn is_held(&self, control: Control) -> bool {
+        self.fingers.iter().any(|f| f.1 == control)
+    }
+
+    /// The controls for the HUD to draw, or nothing while they're hidden
+    pub fn overlay(&self) -> Option<TouchOverlay> {
+        if !self.active {
+            return None;
+        }
+        let element = |control: Control, label: &'static str, kind: TouchElementKind| {
+            let (pos, size) = self.layout.rect(control);
+            TouchElement {
+                pos,
+                size,
+                label,
+                kind,
+            }
+        };
+        Some(TouchOverlay {
+            elements: vec![
+                element(
+                    Control::Steering,
+                    "steer",
+                    TouchElementKind::Slider(self.steer()),
+                ),
+                element(
+                    Control::Brake,
+                    "brake",
+                    TouchElementKind::Pedal(self.brake()),
+                ),
+                element(
+                    Control::Throttle,
+                    "gas",
+                    TouchElementKind::Pedal(self.throttle()),
+                ),
+                element(
+                    Control::Camera,
+                    "cam",
+                    TouchElementKind::Button(self.is_held(Control::Camera)),
+                ),
+                element(
+                    Control::Pause,
+                    "pause",
+                    TouchElementKind::Button(self.is_held(Control::Pause)),
+                ),
+            ],
+        })
+    }
+}
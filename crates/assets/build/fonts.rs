@@ -0,0 +1,270 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use ab_glyph::{Font, FontRef, GlyphId, PxScale, ScaleFont};
+use ttf_parser::{
+    Tag,
+    gpos::{PairAdjustment, PositioningSubtable},
+};
+
+/// Pixel size glyphs are rasterized at
+const EM_SIZE: f32 = 48.0;
+/// How far the signed distance field reaches past glyph edges, in pixels
+const SDF_RANGE: f32 = 6.0;
+/// Empty pixels around each glyph in the atlas, so neighbouring glyphs don't bleed into each other
+const PADDING: u32 = SDF_RANGE as u32 + 1;
+const ATLAS_WIDTH: u32 = 512;
+/// Characters baked into the atlas
+const CHARSET: std::ops::RangeInclusive<char> = ' '..='~';
+
+struct BakedGlyph {
+    c: char,
+    advance: f32,
+    /// Top left of the bitmap, relative to the pen position on the baseline (y down)
+    offset: [f32; 2],
+    width: u32,
+    height: u32,
+    sdf: Vec<u8>,
+    atlas_pos: [u32; 2],
+}
+
+/// Rasterizes a TTF/OTF font into a signed distance field atlas
+///
+/// Produces an `xyz.ttf.atlas` file of raw single channel pixels, and an `xyz.ttf.rs` file containing a `RawFont`
+/// which includes the atlas
+pub fn bake_font(font_path: &Path, out_dir: &Path) -> Result<(), String> {
+    let data = std::fs::read(font_path).map_err(|e| e.to_string())?;
+    let font = FontRef::try_from_slice(&data).map_err(|e| e.to_string())?;
+    let scaled = font.as_scaled(PxScale::from(EM_SIZE));
+
+    let mut glyphs: Vec<BakedGlyph> = CHARSET
+        .map(|c| {
+            let id = font.glyph_id(c);
+            let mut glyph = BakedGlyph {
+                c,
+                advance: scaled.h_advance(id),
+                offset: [0.0, 0.0],
+                width: 0,
+                height: 0,
+                sdf: Vec::new(),
+                atlas_pos: [0, 0],
+            };
+            if let Some(outline) = scaled.outline_glyph(id.with_scale(EM_SIZE)) {
+                let bounds = outline.px_bounds();
+                let (w, h) = (bounds.width() as u32, bounds.height() as u32);
+                let mut coverage = vec![0.0; (w * h) as usize];
+                outline.draw(|x, y, c| coverage[(y * w + x) as usize] = c);
+
+                glyph.width = w + PADDING * 2;
+                glyph.height = h + PADDING * 2;
+                glyph.offset = [bounds.min.x - PADDING as f32, bounds.min.y - PADDING as f32];
+                glyph.sdf = signed_distance_field(&coverage, w, h);
+            }
+            glyph
+        })
+        .collect();
+
+    // shelf packing, tallest glyphs first
+    let mut order: Vec<usize> = (0..glyphs.len()).collect();
+    order.sort_by_key(|i| std::cmp::Reverse(glyphs[*i].height));
+    let (mut x, mut y, mut shelf_height) = (0, 0, 0);
+    for i in order {
+        let glyph = &mut glyphs[i];
+        if glyph.width == 0 {
+            continue;
+        }
+        if x + glyph.width > ATLAS_WIDTH {
+            x = 0;
+            y += shelf_height;
+            shelf_height = 0;
+        }
+        glyph.atlas_pos = [x, y];
+        x += glyph.width;
+        shelf_height = shelf_height.max(glyph.height);
+    }
+    let atlas_height = (y + shelf_height).next_power_of_two();
+
+    let mut atlas = vec![0u8; (ATLAS_WIDTH * atlas_height) as usize];
+    for glyph in glyphs.iter() {
+        for row in 0..glyph.height {
+            let src = (row * glyph.width) as usize;
+            let dst = ((glyph.atlas_pos[1] + row) * ATLAS_WIDTH + glyph.atlas_pos[0]) as usize;
+            atlas[dst..dst + glyph.width as usize]
+                .copy_from_slice(&glyph.sdf[src..src + glyph.width as usize]);
+        }
+    }
+
+    let file_name = font_path.file_name().unwrap().to_string_lossy();
+    let atlas_path = out_dir.join(format!("{file_name}.atlas"));
+    std::fs::write(&atlas_path, &atlas).map_err(|e| e.to_string())?;
+
+    let kerning = kerning_pairs(&data, &font);
+    emit_font(
+        &scaled,
+        &glyphs,
+        &kerning,
+        atlas_height,
+        &atlas_path,
+        &out_dir.join(format!("{file_name}.rs")),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Turns glyph coverage into a padded signed distance field, where 0.5 (127) lies on the glyph edge and values
+/// increase inwards
+fn signed_distance_field(coverage: &[f32], w: u32, h: u32) -> Vec<u8> {
+    let (pw, ph) = (w + PADDING * 2, h + PADDING * 2);
+    let inside = |x: i32, y: i32| {
+        let (x, y) = (x - PADDING as i32, y - PADDING as i32);
+        x >= 0
+            && y >= 0
+            && x < w as i32
+            && y < h as i32
+            && coverage[(y as u32 * w + x as u32) as usize] > 0.5
+    };
+    let range = SDF_RANGE.ceil() as i32;
+
+    let mut sdf = Vec::with_capacity((pw * ph) as usize);
+    for y in 0..ph as i32 {
+        for x in 0..pw as i32 {
+            let is_inside = inside(x, y);
+            // nearest pixel on the other side of the edge
+            let mut nearest_sq = (range * range) as f32;
+            for dy in -range..=range {
+                for dx in -range..=range {
+                    if inside(x + dx, y + dy) != is_inside {
+                        nearest_sq = nearest_sq.min((dx * dx + dy * dy) as f32);
+                    }
+                }
+            }
+            // the edge lies halfway between the two pixels
+            let dist = nearest_sq.sqrt() - 0.5;
+            let signed = if is_inside { dist } else { -dist };
+            let value = 0.5 + signed / (SDF_RANGE * 2.0);
+            sdf.push((value.clamp(0.0, 1.0) * 255.0).round() as u8);
+        }
+    }
+    sdf
+}
+
+/// Finds the horizontal kerning between every pair of characters in the charset, in pixels at `EM_SIZE`
+///
+/// Kerning is read from the GPOS `kern` feature if there is one, falling back to the legacy `kern` table
+fn kerning_pairs(data: &[u8], font: &FontRef) -> Vec<(char, char, f32)> {
+    let Ok(face) = ttf_parser::Face::parse(data, 0) else {
+        return Vec::new();
+    };
+    let px_per_unit = EM_SIZE / face.units_per_em() as f32;
+
+    let mut pair_subtables: Vec<PairAdjustment> = Vec::new();
+    if let Some(gpos) = face.tables().gpos {
+        for feature in gpos.features {
+            if feature.tag != Tag::from_bytes(b"kern") {
+                continue;
+            }
+            for lookup_index in feature.lookup_indices {
+                let Some(lookup) = gpos.lookups.get(lookup_index) else {
+                    continue;
+                };
+                for subtable in lookup.subtables.into_iter::<PositioningSubtable>() {
+                    if let PositioningSubtable::Pair(pair) = subtable {
+                        pair_subtables.push(pair);
+                    }
+                }
+            }
+        }
+    }
+
+    let gpos_kerning = |left: GlyphId, right: GlyphId| -> Option<i16> {
+        let (left, right) = (ttf_parser::GlyphId(left.0), ttf_parser::GlyphId(right.0));
+        pair_subtables.iter().find_map(|pair| {
+            let values = match pair {
+                PairAdjustment::Format1 { coverage, sets } => {
+                    sets.get(coverage.get(left)?)?.get(right)
+                }
+                PairAdjustment::Format2 {
+                    coverage,
+                    classes,
+                    matrix,
+                } => {
+                    if !coverage.contains(left) {
+                        return None;
+                    }
+                    matrix.get((classes.0.get(left), classes.1.get(right)))
+                }
+            };
+            values.map(|(first, _)| first.x_advance)
+        })
+    };
+
+    let mut pairs = Vec::new();
+    for left in CHARSET {
+        for right in CHARSET {
+            let (left_id, right_id) = (font.glyph_id(left), font.glyph_id(right));
+            let units = gpos_kerning(left_id, right_id)
+                .map(f32::from)
+                .unwrap_or_else(|| font.kern_unscaled(left_id, right_id));
+            if units != 0.0 {
+                pairs.push((left, right, units * px_per_unit));
+            }
+        }
+    }
+    pairs
+}
+
+fn emit_font<F: Font>(
+    scaled: &ab_glyph::PxScaleFont<&F>,
+    glyphs: &[BakedGlyph],
+    kerning: &[(char, char, f32)],
+    atlas_height: u32,
+    atlas_path: &Path,
+    file_path: &Path,
+) -> std::io::Result<()> {
+    let file = File::create(file_path)?;
+    let mut output = BufWriter::new(file);
+
+    output.write_all(b"// Baked font, generated via build script\n")?;
+    output.write_all(b"RawFont {\n")?;
+    writeln!(output, "\tem_size: {EM_SIZE}f32,")?;
+    writeln!(output, "\tsdf_range: {SDF_RANGE}f32,")?;
+    writeln!(output, "\tascent: {}f32,", scaled.ascent())?;
+    writeln!(output, "\tdescent: {}f32,", scaled.descent())?;
+    writeln!(
+        output,
+        "\tline_height: {}f32,",
+        scaled.height() + scaled.line_gap()
+    )?;
+    writeln!(output, "\tatlas_width: {ATLAS_WIDTH}u32,")?;
+    writeln!(output, "\tatlas_height: {atlas_height}u32,")?;
+    writeln!(
+        output,
+        "\tatlas: include_bytes!({:?}),",
+        atlas_path.display().to_string()
+    )?;
+    output.write_all(b"\tglyphs: &[\n")?;
+    for glyph in glyphs {
+        writeln!(
+            output,
+            "\t\tRawGlyph {{ c: {:?}, advance: {}f32, offset: [{}f32, {}f32], size: [{}f32, {}f32], atlas_pos: [{}u32, {}u32] }},",
+            glyph.c,
+            glyph.advance,
+            glyph.offset[0],
+            glyph.offset[1],
+            glyph.width,
+            glyph.height,
+            glyph.atlas_pos[0],
+            glyph.atlas_pos[1],
+        )?;
+    }
+    output.write_all(b"\t],\n")?;
+    output.write_all(b"\tkerning: &[\n")?;
+    for (left, right, amount) in kerning {
+        writeln!(output, "\t\t({left:?}, {right:?}, {amount}f32),")?;
+    }
+    output.write_all(b"\t],\n")?;
+    output.write_all(b"}")?;
+    Ok(())
+}
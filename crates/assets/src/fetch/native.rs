@@ -0,0 +1,19 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+
+/// Where the asset files are, `assets/` in the working directory if there is one, else the one in the workspace
+fn assets_dir() -> PathBuf {
+    let local = PathBuf::from("assets");
+    if local.is_dir() {
+        local
+    } else {
+        PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/../../assets"))
+    }
+}
+
+/// Reads an asset file
+pub async fn read(file: &str) -> anyhow::Result<Vec<u8>> {
+    let path = assets_dir().join(file);
+    std::fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))
+}
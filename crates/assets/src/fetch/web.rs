@@ -0,0 +1,28 @@
+use anyhow::anyhow;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{Response, js_sys::Uint8Array};
+
+/// Fetches an asset file, from the `assets/` directory next to the page
+pub async fn read(file: &str) -> anyhow::Result<Vec<u8>> {
+    let url = format!("assets/{file}");
+    let window = web_sys::window().ok_or_else(|| anyhow!("No window to fetch from"))?;
+    let response: Response = JsFuture::from(window.fetch_with_str(&url))
+        .await
+        .map_err(|e| anyhow!("Failed to fetch {url}: {e:?}"))?
+        .unchecked_into();
+    if !response.ok() {
+        return Err(anyhow!(
+            "Failed to fetch {url}: {} {}",
+            response.status(),
+            response.status_text()
+        ));
+    }
+    let buffer = response
+        .array_buffer()
+        .map_err(|e| anyhow!("Failed to read {url}: {e:?}"))?;
+    let buffer = JsFuture::from(buffer)
+        .await
+        .map_err(|e| anyhow!("Failed to read {url}: {e:?}"))?;
+    Ok(Uint8Array::new(&buffer).to_vec())
+}
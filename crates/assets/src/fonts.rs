@@ -0,0 +1,52 @@
+use crate::load_font;
+
+/// SpaceGrotesk, the font used by the web UI
+pub const SPACE_GROTESK: RawFont = load_font!("SpaceGrotesk.ttf");
+
+/// A font baked into a signed distance field atlas
+///
+/// All metrics are in pixels at `em_size`, with y pointing down
+pub struct RawFont {
+    /// Font size the atlas was rasterized at
+    pub em_size: f32,
+    /// How far the distance field reaches past glyph edges, in atlas pixels
+    pub sdf_range: f32,
+    /// Height of the tallest glyphs above the baseline
+    pub ascent: f32,
+    /// Depth of the lowest glyphs below the baseline (negative)
+    pub descent: f32,
+    /// Distance between consecutive baselines
+    pub line_height: f32,
+    pub atlas_width: u32,
+    pub atlas_height: u32,
+    /// Single channel distance field, where 0.5 lies on glyph edges and values increase inwards
+    pub atlas: &'static [u8],
+    pub glyphs: &'static [RawGlyph],
+    /// Horizontal adjustments between pairs of characters, as `(left, right, adjustment)`
+    pub kerning: &'static [(char, char, f32)],
+}
+impl RawFont {
+    pub fn glyph(&self, c: char) -> Option<&RawGlyph> {
+        self.glyphs.iter().find(|g| g.c == c)
+    }
+
+    pub fn kerning(&self, left: char, right: char) -> f32 {
+        self.kerning
+            .iter()
+            .find(|(l, r, _)| *l == left && *r == right)
+            .map_or(0.0, |(_, _, adjustment)| *adjustment)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct RawGlyph {
+    pub c: char,
+    /// How far to move the pen after this glyph
+    pub advance: f32,
+    /// Top left corner of the glyph's bitmap, relative to the pen position on the baseline
+    pub offset: [f32; 2],
+    /// Size of the glyph's bitmap, zero for glyphs with no outline
+    pub size: [f32; 2],
+    /// Top left corner of the glyph's bitmap in the atlas, in pixels
+    pub atlas_pos: [u32; 2],
+}
@@ -0,0 +1,448 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+use anyhow::{Context, anyhow, bail};
+use base64::Engine;
+use gltf::{Gltf, Node, Primitive, buffer, image, mesh::Mode};
+use nalgebra::{Isometry3, Matrix3, Matrix4, Point3, Translation3, UnitQuaternion, Vector3};
+
+use super::texture::decode_texture;
+use crate::{RawMaterial, RawMesh, RawModel, RawPart, RawTexture, RawVertex, fetch};
+
+/// Fetches a .gltf or .glb file and any buffers and images it refers to, and flattens it into a model, see
+/// [`parse_gltf`]
+pub async fn load_gltf(file: &str) -> anyhow::Result<RawModel> {
+    let data = fetch::read(file).await?;
+    let mut files = HashMap::new();
+    for uri in external_uris(&Gltf::from_slice(&data)?) {
+        let path = match file.rsplit_once('/') {
+            Some((dir, _)) => format!("{dir}/{uri}"),
+            None => uri.clone(),
+        };
+        let contents = fetch::read(&path)
+            .await
+            .with_context(|| format!("Failed to load '{path}'"))?;
+        files.insert(uri, contents);
+    }
+    parse_gltf(&data, |uri| {
+        // every file was fetched above
+        files
+            .get(uri)
+            .cloned()
+            .ok_or_else(|| anyhow!("'{uri}' wasn't fetched"))
+    })
+}
+
+/// Flattens the scene of a .gltf or .glb file into a model. Buffers and images in other files are read with
+/// `read_uri`, given their URI as written in the file
+///
+/// Every node's transform is applied to its meshes, so the whole model is in the scene's space. Nodes with meshes become
+/// parts, named after the node or else its mesh, nested like the nodes are. Their primitives are merged into one mesh
+/// per material, colored by its PBR base color and texture. Other textures, skins and animations are ignored
+pub fn parse_gltf(
+    data: &[u8],
+    mut read_uri: impl FnMut(&str) -> anyhow::Result<Vec<u8>>,
+) -> anyhow::Result<RawModel> {
+    let gltf = Gltf::from_slice(data)?;
+    let mut read_uri = |uri: &str| match decode_data_uri(uri) {
+        Some(data) => data,
+        None => read_uri(uri),
+    };
+
+    let mut buffers = Vec::new();
+    for buffer in gltf.buffers() {
+        let data = match buffer.source() {
+            buffer::Source::Bin => gltf
+                .blob
+                .clone()
+                .ok_or_else(|| anyhow!("Missing the binary chunk"))?,
+            buffer::Source::Uri(uri) => read_uri(uri)
+                .with_context(|| format!("Failed to load buffer {}", buffer.index()))?,
+        };
+        if data.len() < buffer.length() {
+            bail!(
+                "Buffer {} is {} bytes, but should be {}",
+                buffer.index(),
+                data.len(),
+                buffer.length()
+            );
+        }
+        buffers.push(data);
+    }
+
+    // only the images used as base color textures are loaded
+    let mut textures = HashMap::new();
+    for material in gltf.materials() {
+        let Some(info) = material.pbr_metallic_roughness().base_color_texture() else {
+            continue;
+        };
+        let image = info.texture().source();
+        if textures.contains_key(&image.index()) {
+            continue;
+        }
+        let data = match image.source() {
+            image::Source::View { view, .. } => {
+                let start = view.offset();
+                buffers[view.buffer().index()]
+                    .get(start..start + view.length())
+                    .ok_or_else(|| anyhow!("Image {} is out of its buffer", image.index()))?
+                    .to_vec()
+            }
+            image::Source::Uri { uri, .. } => read_uri(uri)?,
+        };
+        let texture = decode_texture(&data)
+            .with_context(|| format!("Failed to load image {}", image.index()))?;
+        textures.insert(image.index(), Arc::new(texture));
+    }
+
+    let scene = gltf
+        .default_scene()
+        .or_else(|| gltf.scenes().next())
+        .ok_or_else(|| anyhow!("No scenes"))?;
+    let mut flattener = Flattener {
+        buffers: &buffers,
+        textures: &textures,
+        markers: HashMap::new(),
+    };
+    let mut parts = Vec::new();
+    for node in scene.nodes() {
+        parts.extend(flattener.add_node(&node, &Matrix4::identity())?);
+    }
+    Ok(RawModel::from_parts(parts, flattener.markers))
+}
+
+/// URIs of the buffers and base color images that are in other files, rather than embedded
+fn external_uris(gltf: &Gltf) -> Vec<String> {
+    let buffers = gltf.buffers().filter_map(|buffer| match buffer.source() {
+        buffer::Source::Uri(uri) => Some(uri),
+        buffer::Source::Bin => None,
+    });
+    let images = gltf.materials().filter_map(|material| {
+        let info = material.pbr_metallic_roughness().base_color_texture()?;
+        match info.texture().source().source() {
+            image::Source::Uri { uri, .. } => Some(uri),
+            image::Source::View { .. } => None,
+        }
+    });
+    let mut uris: Vec<String> = buffers
+        .chain(images)
+        .filter(|uri| !uri.starts_with("data:"))
+        .map(str::to_string)
+        .collect();
+    uris.sort();
+    uris.dedup();
+    uris
+}
+
+/// The contents of a data URI, or `None` if the URI is for a file
+fn decode_data_uri(uri: &str) -> Option<anyhow::Result<Vec<u8>>> {
+    let data = uri.strip_prefix("data:")?;
+    Some(match data.split_once(";base64,") {
+        Some((_, encoded)) => base64::engine::general_purpose::STANDARD
+            .decode(encoded)
+            .map_err(Into::into),
+        None => Err(anyhow!("Only base64 data URIs are supported")),
+    })
+}
+
+/// Collects the parts and markers of a node hierarchy
+struct Flattener<'a> {
+    buffers: &'a [Vec<u8>],
+    /// Decoded images by index
+    textures: &'a HashMap<usize, Arc<RawTexture>>,
+    markers: HashMap<String, Isometry3<f32>>,
+}
+impl Flattener<'_> {
+    /// The parts of a node and its descendants. Nodes without meshes pass their children's parts up
+    fn add_node(
+        &mut self,
+        node: &Node,
+        parent_transform: &Matrix4<f32>,
+    ) -> anyhow::Result<Vec<RawPart>> {
+        let transform = parent_transform * Matrix4::from(node.transform().matrix());
+        if let Some(name) = node.name() {
+            self.markers.insert(name.to_string(), isometry(&transform));
+        }
+        let mut children = Vec::new();
+        for child in node.children() {
+            children.extend(self.add_node(&child, &transform)?);
+        }
+        let Some(mesh) = node.mesh() else {
+            return Ok(children);
+        };
+
+        // meshes by material index, `None` for primitives without one
+        let mut meshes = BTreeMap::new();
+        for primitive in mesh.primitives() {
+            self.add_primitive(&primitive, &transform, &mut meshes)
+                .with_context(|| {
+                    format!(
+                        "Failed to read primitive {} of mesh {}",
+                        primitive.index(),
+                        mesh.name().unwrap_or(&mesh.index().to_string())
+                    )
+                })?;
+        }
+        Ok(vec![RawPart {
+            name: node.name().or(mesh.name()).unwrap_or_default().to_string(),
+            meshes: meshes.into_values().collect(),
+            children,
+        }])
+    }
+
+    fn add_primitive(
+        &self,
+        primitive: &Primitive,
+        transform: &Matrix4<f32>,
+        meshes: &mut BTreeMap<Option<usize>, RawMesh>,
+    ) -> anyhow::Result<()> {
+        if primitive.mode() != Mode::Triangles {
+            log::warn!(
+                "Skipping primitive drawn as {:?}, only triangles are supported",
+                primitive.mode()
+            );
+            return Ok(());
+        }
+        let buffers = self.buffers;
+        let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(Vec::as_slice));
+
+        let positions: Vec<Point3<f32>> = reader
+            .read_positions()
+            .ok_or_else(|| anyhow!("No vertex positions"))?
+            .map(|pos| transform.transform_point(&Point3::from(pos)))
+            .collect();
+        let mut indices: Vec<u32> = match reader.read_indices() {
+            Some(indices) => indices.into_u32().collect(),
+            None => (0..positions.len() as u32).collect(),
+        };
+        if let Some(index) = indices.iter().find(|i| **i as usize >= positions.len()) {
+            bail!(
+                "Index {index} is out of range of the {} vertices",
+                positions.len()
+            );
+        }
+
+        // a trailing partial face can't be drawn
+        indices.truncate(indices.len() / 3 * 3);
+
+        let linear: Matrix3<f32> = transform.fixed_view::<3, 3>(0, 0).into_owned();
+        if linear.determinant() < 0.0 {
+            // mirroring turns the faces inside out, so their winding is flipped back
+            for face in indices.chunks_exact_mut(3) {
+                face.swap(1, 2);
+            }
+        }
+        let normals: Vec<Vector3<f32>> = match reader.read_normals() {
+            Some(normals) => {
+                // normals stay perpendicular to their faces under non-uniform scaling with the inverse transpose
+                let normal_transform = linear
+                    .try_inverse()
+                    .map_or(Matrix3::identity(), |inverse| inverse.transpose());
+                normals
+                    .map(|normal| (normal_transform * Vector3::from(normal)).normalize())
+                    .collect()
+            }
+            None => smooth_normals(&positions, &indices),
+        };
+        if normals.len() != positions.len() {
+            bail!("{} normals for {} vertices", normals.len(), positions.len());
+        }
+
+        let material = primitive.material();
+        let pbr = material.pbr_metallic_roughness();
+        let texture = pbr.base_color_texture();
+        let texcoords: Vec<[f32; 2]> = match texture
+            .as_ref()
+            .and_then(|info| reader.read_tex_coords(info.tex_coord()))
+        {
+            Some(texcoords) => texcoords.into_f32().collect(),
+            None => vec![[0.0, 0.0]; positions.len()],
+        };
+        if texcoords.len() != positions.len() {
+            bail!(
+                "{} texture coordinates for {} vertices",
+                texcoords.len(),
+                positions.len()
+            );
+        }
+
+        let [r, g, b, _] = pbr.base_color_factor();
+        let mesh = meshes.entry(material.index()).or_insert_with(|| {
+            RawMesh::new(RawMaterial {
+                color: [r, g, b],
+                texture: texture
+                    .and_then(|info| self.textures.get(&info.texture().source().index()))
+                    .cloned(),
+            })
+        });
+        let offset = mesh.verts.len() as u32;
+        mesh.verts
+            .extend(positions.iter().zip(normals).zip(texcoords).map(
+                |((pos, normal), texcoord)| RawVertex {
+                    pos: pos.coords.into(),
+                    normal: normal.into(),
+                    texcoord,
+                },
+            ));
+        mesh.indices.extend(indices.iter().map(|i| i + offset));
+        Ok(())
+    }
+}
+
+/// Vertex normals averaged from the faces around each vertex, weighted by their area
+fn smooth_normals(positions: &[Point3<f32>], indices: &[u32]) -> Vec<Vector3<f32>> {
+    let mut normals = vec![Vector3::zeros(); positions.len()];
+    for face in indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| positions[face[i] as usize]);
+        // the cross product's length is twice the face's area
+        let normal = (b - a).cross(&(c - a));
+        for i in face {
+            normals[*i as usize] += normal;
+        }
+    }
+    normals
+        .into_iter()
+        .map(|normal| normal.try_normalize(f32::EPSILON).unwrap_or(Vector3::y()))
+        .collect()
+}
+
+/// The position and rotation of a transform, leaving out any scale
+fn isometry(transform: &Matrix4<f32>) -> Isometry3<f32> {
+    let linear = transform.fixed_view::<3, 3>(0, 0);
+    let unscaled = Matrix3::from_columns(&[0, 1, 2].map(|i| {
+        linear
+            .column(i)
+            .try_normalize(f32::EPSILON)
+            .unwrap_or(Vector3::ith(i, 1.0))
+    }));
+    Isometry3::from_parts(
+        Translation3::from(transform.fixed_view::<3, 1>(0, 3).into_owned()),
+        UnitQuaternion::from_matrix(&unscaled),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A triangle at (0, 0, 0), (1, 0, 0) and (0, 1, 0), facing +z
+    fn triangle() -> Vec<u8> {
+        [0.0f32, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0]
+            .iter()
+            .flat_map(|x| x.to_le_bytes())
+            .collect()
+    }
+
+    /// A body with three primitives in two materials, and a mirrored wheel and a marker under it. The wheel's
+    /// triangle is in a separate file
+    fn model() -> String {
+        let embedded = base64::engine::general_purpose::STANDARD.encode(triangle());
+        let accessor = |buffer: usize| {
+            format!(
+                r#"{{"bufferView": {buffer}, "componentType": 5126, "count": 3, "type": "VEC3",
+                    "min": [0, 0, 0], "max": [1, 1, 0]}}"#
+            )
+        };
+        format!(
+            r#"{{
+                "asset": {{"version": "2.0"}},
+                "scene": 0,
+                "scenes": [{{"nodes": [0]}}],
+                "nodes": [
+                    {{"name": "Body", "mesh": 0, "translation": [1, 0, 0], "children": [1, 2]}},
+                    {{"name": "Wheel", "mesh": 1, "translation": [0, 2, 0], "scale": [-1, 1, 1]}},
+                    {{"name": "marker", "translation": [0, 0, 3]}}
+                ],
+                "meshes": [
+                    {{"primitives": [
+                        {{"attributes": {{"POSITION": 0}}, "material": 0}},
+                        {{"attributes": {{"POSITION": 0}}, "material": 1}},
+                        {{"attributes": {{"POSITION": 0}}, "material": 0}}
+                    ]}},
+                    {{"primitives": [{{"attributes": {{"POSITION": 1}}, "material": 1}}]}}
+                ],
+                "materials": [
+                    {{"pbrMetallicRoughness": {{"baseColorFactor": [1, 0, 0, 1]}}}},
+                    {{"pbrMetallicRoughness": {{"baseColorFactor": [0, 0, 1, 1]}}}}
+                ],
+                "accessors": [{}, {}],
+                "bufferViews": [
+                    {{"buffer": 0, "byteLength": 36}},
+                    {{"buffer": 1, "byteLength": 36}}
+                ],
+                "buffers": [
+                    {{"byteLength": 36, "uri": "data:application/octet-stream;base64,{embedded}"}},
+                    {{"byteLength": 36, "uri": "wheel.bin"}}
+                ]
+            }}"#,
+            accessor(0),
+            accessor(1),
+        )
+    }
+
+    fn parse(gltf: &str) -> anyhow::Result<RawModel> {
+        parse_gltf(gltf.as_bytes(), |uri| match uri {
+            "wheel.bin" => Ok(triangle()),
+            _ => Err(anyhow!("No file '{uri}'")),
+        })
+    }
+
+    #[test]
+    fn merges_primitives_by_material() {
+        let model = parse(&model()).unwrap();
+        let body = model.part("Body").unwrap();
+        assert_eq!(body.meshes.len(), 2);
+        let red = &body.meshes[0];
+        assert_eq!(red.material.color, [1.0, 0.0, 0.0]);
+        assert_eq!(red.verts.len(), 6);
+        assert_eq!(red.indices, [0, 1, 2, 3, 4, 5]);
+        assert_eq!(red.verts[1].pos, [2.0, 0.0, 0.0]);
+        assert_eq!(body.meshes[1].material.color, [0.0, 0.0, 1.0]);
+        // the wheel's blue mesh is merged with the body's for the whole model
+        assert_eq!(model.meshes.len(), 2);
+        assert_eq!(model.meshes[1].verts.len(), 6);
+    }
+
+    #[test]
+    fn children_are_transformed_by_their_parents() {
+        let model = parse(&model()).unwrap();
+        assert_eq!(model.part("Body").unwrap().children.len(), 1);
+        let wheel = &model.part("Body/Wheel").unwrap().meshes[0];
+        let positions: Vec<[f32; 3]> = wheel.verts.iter().map(|vert| vert.pos).collect();
+        assert_eq!(
+            positions,
+            [[1.0, 2.0, 0.0], [0.0, 2.0, 0.0], [1.0, 3.0, 0.0]]
+        );
+    }
+
+    #[test]
+    fn mirroring_flips_the_winding() {
+        let model = parse(&model()).unwrap();
+        let wheel = &model.part("Wheel").unwrap().meshes[0];
+        assert_eq!(wheel.indices, [0, 2, 1]);
+        // so the face still points the same way
+        for vert in &wheel.verts {
+            assert_eq!(vert.normal, [0.0, 0.0, 1.0]);
+        }
+    }
+
+    #[test]
+    fn markers() {
+        let model = parse(&model()).unwrap();
+        let marker = model.marker("marker").unwrap();
+        assert_eq!(marker.translation.vector, Vector3::new(1.0, 0.0, 3.0));
+        assert_eq!(
+            model.marker("Wheel").unwrap().translation.vector,
+            Vector3::new(1.0, 2.0, 0.0)
+        );
+        assert!(model.marker("Missing").is_none());
+    }
+
+    #[test]
+    fn missing_files_fail() {
+        let gltf = model().replace("wheel.bin", "tyre.bin");
+        assert!(parse(&gltf).is_err());
+    }
+}
@@ -0,0 +1,8 @@
+//! Turning model files into [`RawModel`](crate::RawModel)s, one module per format
+
+mod gltf;
+mod obj;
+mod texture;
+
+pub use self::gltf::load_gltf;
+pub use self::obj::load_obj;
@@ -0,0 +1,110 @@
+use std::{collections::HashMap, io, sync::Arc};
+
+use anyhow::Context;
+use obj::{OBJMaterial, OBJMesh, OBJWarningKind, ParseOptions};
+
+use super::texture::load_texture;
+use crate::{RawMaterial, RawMesh, RawModel, RawPart, RawTexture, RawVertex, fetch};
+
+/// Fetches an .obj file and the material libraries and textures it uses, and parses them into a model with a part for
+/// each object, and sub-parts for its groups
+pub async fn load_obj(file: &str) -> anyhow::Result<RawModel> {
+    let source = String::from_utf8(fetch::read(file).await?)?;
+    let mut mtl_sources = HashMap::new();
+    for mtl_file in obj::mtl_libraries(&source) {
+        let mtl_source = fetch::read(&mtl_file)
+            .await
+            .and_then(|bytes| Ok(String::from_utf8(bytes)?))
+            .with_context(|| format!("Failed to load '{mtl_file}'"))?;
+        mtl_sources.insert(mtl_file, mtl_source);
+    }
+    let obj_model = obj::parse_obj(file, &source, ParseOptions::default(), |mtl_file| {
+        // every library was fetched above
+        mtl_sources
+            .get(mtl_file)
+            .cloned()
+            .ok_or_else(|| io::ErrorKind::NotFound.into())
+    })?;
+    for warning in &obj_model.warnings {
+        match warning.kind {
+            // exporters write plenty of directives that don't matter here
+            OBJWarningKind::UnsupportedDirective(_) => log::debug!("{warning}"),
+            _ => log::warn!("{warning}"),
+        }
+    }
+
+    let mut textures = HashMap::new();
+    let materials = obj_model.objects.iter().flat_map(|object| {
+        let group_meshes = object.groups.iter().flat_map(|group| &group.meshes);
+        object
+            .meshes
+            .iter()
+            .chain(group_meshes)
+            .map(|(material, _)| material)
+    });
+    for texture_file in materials.filter_map(|material| material.diffuse_texture.as_ref()) {
+        if !textures.contains_key(texture_file) {
+            let texture = load_texture(texture_file)
+                .await
+                .with_context(|| format!("Failed to load '{texture_file}'"))?;
+            textures.insert(texture_file.clone(), Arc::new(texture));
+        }
+    }
+
+    let raw_meshes = |meshes: Vec<(OBJMaterial, OBJMesh)>| -> Vec<RawMesh> {
+        meshes
+            .into_iter()
+            .map(|mesh| raw_mesh(mesh, &textures))
+            .collect()
+    };
+    let parts = obj_model
+        .objects
+        .into_iter()
+        .map(|object| RawPart {
+            name: object.name,
+            meshes: raw_meshes(object.meshes),
+            children: object
+                .groups
+                .into_iter()
+                .map(|group| RawPart {
+                    name: group.name,
+                    meshes: raw_meshes(group.meshes),
+                    children: Vec::new(),
+                })
+                .collect(),
+        })
+        .collect();
+    Ok(RawModel::from_parts(parts, HashMap::new()))
+}
+
+fn raw_mesh(
+    (material, mesh): (OBJMaterial, OBJMesh),
+    textures: &HashMap<String, Arc<RawTexture>>,
+) -> RawMesh {
+    RawMesh {
+        material: RawMaterial {
+            color: material.diffuse_color,
+            texture: material
+                .diffuse_texture
+                .and_then(|file| textures.get(&file).cloned()),
+        },
+        verts: mesh
+            .verts
+            .iter()
+            .map(|vert| {
+                let [u, v] = vert.texcoord.map(f32::from);
+                RawVertex {
+                    pos: vert.pos.map(f32::from),
+                    normal: vert.normal.map(f32::from),
+                    // .obj texture coordinates start at the bottom left
+                    texcoord: [u, 1.0 - v],
+                }
+            })
+            .collect(),
+        indices: mesh
+            .faces
+            .iter()
+            .flat_map(|face| face.map(|i| i as u32))
+            .collect(),
+    }
+}
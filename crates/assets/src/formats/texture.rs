@@ -0,0 +1,49 @@
+use std::io::Cursor;
+
+use anyhow::{anyhow, bail};
+use png::{ColorType, Transformations};
+
+use crate::{RawTexture, fetch};
+
+/// Fetches and decodes a texture file
+pub async fn load_texture(file: &str) -> anyhow::Result<RawTexture> {
+    decode_texture(&fetch::read(file).await?)
+}
+
+/// Decodes an image into RGBA pixels. Only PNG is supported
+pub fn decode_texture(data: &[u8]) -> anyhow::Result<RawTexture> {
+    if !data.starts_with(b"\x89PNG") {
+        bail!("Unsupported image format, textures must be PNG");
+    }
+    let mut decoder = png::Decoder::new(Cursor::new(data));
+    // palettes, low bit depths and 16 bit channels all come out as 8 bits per channel
+    decoder.set_transformations(Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+    let mut buf = vec![
+        0;
+        reader
+            .output_buffer_size()
+            .ok_or_else(|| anyhow!("Image is too large"))?
+    ];
+    let info = reader.next_frame(&mut buf)?;
+    buf.truncate(info.buffer_size());
+
+    let pixels = match info.color_type {
+        ColorType::Rgba => buf,
+        ColorType::Rgb => buf
+            .chunks_exact(3)
+            .flat_map(|p| [p[0], p[1], p[2], 255])
+            .collect(),
+        ColorType::GrayscaleAlpha => buf
+            .chunks_exact(2)
+            .flat_map(|p| [p[0], p[0], p[0], p[1]])
+            .collect(),
+        ColorType::Grayscale => buf.iter().flat_map(|l| [*l, *l, *l, 255]).collect(),
+        ColorType::Indexed => bail!("Palette wasn't expanded"),
+    };
+    Ok(RawTexture {
+        width: info.width,
+        height: info.height,
+        pixels,
+    })
+}
@@ -1,9 +1,17 @@
+mod fetch;
+pub mod fonts;
+mod formats;
+mod loader;
 mod macros;
 pub mod objects;
 
-use nalgebra::Point3;
+use std::{collections::HashMap, sync::Arc};
+
+use nalgebra::{Isometry3, Point3};
 use rapier3d::prelude::ColliderBuilder;
 
+pub use loader::{Assets, LoadProgress, MODEL_FILES};
+
 type Color = [f32; 3];
 
 const BLACK: Color = [0.0, 0.0, 0.0];
@@ -15,43 +23,153 @@ const BLUE: Color = [0.0, 0.0, 1.0];
 /// Describes a game object, provides a default implementation of a collision box which creates it from the render mesh
 #[allow(non_upper_case_globals)]
 pub trait GameObject {
-    const render_meshes: &'static [RawMesh];
+    /// Model the object is drawn with, one of [`MODEL_FILES`]
+    const model_file: &'static str;
+    /// Model the collision box is made from, when it's simpler than the one drawn
+    const collision_model_file: &'static str = Self::model_file;
     const debug_lines: &'static [RawDebugLine] = &[];
-    fn get_collision_box() -> ColliderBuilder {
-        let mut verts: Vec<Point3<f32>> = Vec::new();
-        let mut indices: Vec<[u32; 3]> = Vec::new();
+    fn render_meshes(assets: &Assets) -> &[RawMesh] {
+        &assets.model(Self::model_file).meshes
+    }
+    fn get_collision_box(assets: &Assets) -> ColliderBuilder {
+        trimesh_collision_box(&assets.model(Self::collision_model_file).meshes)
+    }
+}
 
-        for mesh in Self::render_meshes {
-            for v in mesh.verts {
-                verts.push(Point3::from(v.pos));
-            }
-            let mut count = 0;
-            let mut cur_face: [u32; 3] = [0; 3];
-            for index in mesh.indices {
-                cur_face[count] = *index as u32;
-                count += 1;
-                if count == 3 {
-                    indices.push(cur_face);
-                    count = 0;
-                }
+/// A collision box with the exact shape of the given meshes
+fn trimesh_collision_box<'a>(meshes: impl IntoIterator<Item = &'a RawMesh>) -> ColliderBuilder {
+    let mut verts: Vec<Point3<f32>> = Vec::new();
+    let mut indices: Vec<[u32; 3]> = Vec::new();
+
+    for mesh in meshes {
+        // indices are per mesh, so they're offset past the verts of the meshes before
+        let offset = verts.len() as u32;
+        for v in mesh.verts.iter() {
+            verts.push(Point3::from(v.pos));
+        }
+        for face in mesh.indices.chunks_exact(3) {
+            indices.push([face[0] + offset, face[1] + offset, face[2] + offset]);
+        }
+    }
+    ColliderBuilder::trimesh(verts, indices).expect("Failed to create trimesh collision box")
+}
+
+/// A loaded model file
+pub struct RawModel {
+    /// The whole model, one mesh per material
+    pub meshes: Vec<RawMesh>,
+    /// Named pieces of the model, which can be drawn or collided with on their own. These are the objects in .obj
+    /// files with their groups as sub-parts, and the nodes with meshes in glTF models
+    pub parts: Vec<RawPart>,
+    /// Transforms of the named nodes in the model, such as empties marking wheel positions or spawn points. Only glTF
+    /// models have any
+    pub markers: HashMap<String, Isometry3<f32>>,
+}
+impl RawModel {
+    /// A model made of the given parts, with their meshes merged by material for the whole model
+    pub(crate) fn from_parts(
+        parts: Vec<RawPart>,
+        markers: HashMap<String, Isometry3<f32>>,
+    ) -> RawModel {
+        let mut meshes: Vec<RawMesh> = Vec::new();
+        for part_mesh in parts.iter().flat_map(RawPart::all_meshes) {
+            match meshes
+                .iter_mut()
+                .find(|mesh| mesh.material.looks_like(&part_mesh.material))
+            {
+                Some(mesh) => mesh.append(part_mesh),
+                None => meshes.push(part_mesh.clone()),
             }
         }
-        ColliderBuilder::trimesh(verts, indices).expect("Failed to create trimesh collision box")
+        RawModel {
+            meshes,
+            parts,
+            markers,
+        }
+    }
+
+    pub fn marker(&self, name: &str) -> Option<Isometry3<f32>> {
+        self.markers.get(name).copied()
+    }
+
+    /// Finds a part by name, looking through sub-parts too and preferring the least nested one. A path like
+    /// `Car/Door` picks out a sub-part when names are ambiguous
+    pub fn part(&self, name: &str) -> Option<&RawPart> {
+        let mut path = name.split('/');
+        let first = path.next()?;
+        let mut part = find_part(&self.parts, first)?;
+        for name in path {
+            part = part.children.iter().find(|child| child.name == name)?;
+        }
+        Some(part)
     }
 }
 
-#[derive(Clone, Copy)]
+/// Breadth first search for a part
+fn find_part<'a>(parts: &'a [RawPart], name: &str) -> Option<&'a RawPart> {
+    let mut level: Vec<&RawPart> = parts.iter().collect();
+    while !level.is_empty() {
+        if let Some(part) = level.iter().find(|part| part.name == name) {
+            return Some(part);
+        }
+        level = level.iter().flat_map(|part| &part.children).collect();
+    }
+    None
+}
+
+/// A named piece of a model
+pub struct RawPart {
+    pub name: String,
+    /// Faces of this part that aren't in a sub-part, one mesh per material
+    pub meshes: Vec<RawMesh>,
+    pub children: Vec<RawPart>,
+}
+impl RawPart {
+    /// Meshes of this part and all of its sub-parts
+    pub fn all_meshes(&self) -> Vec<&RawMesh> {
+        let mut meshes: Vec<&RawMesh> = self.meshes.iter().collect();
+        for child in &self.children {
+            meshes.extend(child.all_meshes());
+        }
+        meshes
+    }
+    /// A collision box with the exact shape of this part and its sub-parts
+    pub fn collision_box(&self) -> ColliderBuilder {
+        trimesh_collision_box(self.all_meshes())
+    }
+}
+
+#[derive(Clone)]
 /// Represents the raw data for a mesh
 pub struct RawMesh {
     pub material: RawMaterial,
-    pub verts: &'static [RawVertex],
-    pub indices: &'static [u32],
+    pub verts: Vec<RawVertex>,
+    pub indices: Vec<u32>,
+}
+impl RawMesh {
+    pub(crate) fn new(material: RawMaterial) -> RawMesh {
+        RawMesh {
+            material,
+            verts: Vec::new(),
+            indices: Vec::new(),
+        }
+    }
+
+    /// Adds another mesh's faces to this one, keeping this one's material
+    pub(crate) fn append(&mut self, other: &RawMesh) {
+        let offset = self.verts.len() as u32;
+        self.verts.extend_from_slice(&other.verts);
+        self.indices
+            .extend(other.indices.iter().map(|i| i + offset));
+    }
 }
 
 #[derive(Clone, Copy)]
 pub struct RawVertex {
     pub pos: [f32; 3],
     pub normal: [f32; 3],
+    /// (0, 0) is the top left of the texture
+    pub texcoord: [f32; 2],
 }
 
 #[derive(Clone, Copy)]
@@ -61,8 +179,73 @@ pub struct RawDebugLine {
     pub pos2: [f32; 3],
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 pub struct RawMaterial {
-    /// RGB
+    /// RGB, multiplied with the texture if there is one
     pub color: Color,
+    /// Diffuse texture, shared between the meshes that use it
+    pub texture: Option<Arc<RawTexture>>,
+}
+
+impl RawMaterial {
+    /// Whether meshes with these materials are drawn the same, so they can be merged
+    fn looks_like(&self, other: &RawMaterial) -> bool {
+        let same_texture = match (&self.texture, &other.texture) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            (a, b) => a.is_none() && b.is_none(),
+        };
+        self.color == other.color && same_texture
+    }
+}
+
+/// An image with 8 bit sRGB RGBA pixels, row by row from the top left
+pub struct RawTexture {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn part(name: &str, children: Vec<RawPart>) -> RawPart {
+        RawPart {
+            name: name.to_string(),
+            meshes: Vec::new(),
+            children,
+        }
+    }
+
+    /// A house with a door, and a car whose door is also called `Door`
+    fn model() -> RawModel {
+        RawModel::from_parts(
+            vec![
+                part("House", vec![part("Door", Vec::new())]),
+                part("Car", vec![part("Door", vec![part("Handle", Vec::new())])]),
+                part("Door", Vec::new()),
+            ],
+            HashMap::new(),
+        )
+    }
+
+    #[test]
+    fn parts_by_path() {
+        let model = model();
+        assert_eq!(model.part("Car").unwrap().name, "Car");
+        assert_eq!(model.part("Car/Door").unwrap().children.len(), 1);
+        assert_eq!(model.part("Car/Door/Handle").unwrap().name, "Handle");
+        assert!(model.part("House/Door").unwrap().children.is_empty());
+        assert!(model.part("House/Handle").is_none());
+        assert!(model.part("Boat").is_none());
+    }
+
+    #[test]
+    fn least_nested_part_wins() {
+        let model = model();
+        // the top level door, rather than the house's
+        assert!(std::ptr::eq(model.part("Door").unwrap(), &model.parts[2]));
+        // the only handle is nested in the car's door
+        assert_eq!(model.part("Handle").unwrap().name, "Handle");
+    }
 }
@@ -0,0 +1,100 @@
+use std::{collections::HashMap, fmt};
+
+use anyhow::{Context, bail};
+
+use crate::{RawModel, formats};
+
+/// Model files the game needs, in the assets directory. They can be Wavefront .obj files or glTF 2.0, either .gltf
+/// or .glb
+pub const MODEL_FILES: [&str; 8] = [
+    "car.obj",
+    "wheel.obj",
+    "ground.obj",
+    "ground_hitbox.obj",
+    "roads.obj",
+    "decor.obj",
+    "decor_hitbox.obj",
+    "ocean.obj",
+];
+
+/// How far along loading the assets is
+#[derive(Clone, Copy, Debug)]
+pub struct LoadProgress {
+    /// Files finished so far
+    pub loaded: usize,
+    pub total: usize,
+    /// File being loaded now, if any are left
+    pub current: Option<&'static str>,
+}
+impl LoadProgress {
+    /// From 0 (nothing loaded) to 1 (done)
+    pub fn fraction(&self) -> f32 {
+        self.loaded as f32 / self.total as f32
+    }
+}
+impl fmt::Display for LoadProgress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.current {
+            Some(file) => write!(f, "Loading {file} ({}/{})", self.loaded + 1, self.total),
+            None => write!(f, "Loaded {} files", self.total),
+        }
+    }
+}
+
+/// The game's models, loaded at runtime
+///
+/// Files are fetched over HTTP from the page's `assets/` directory on the web, and read from the `assets/` directory
+/// natively
+pub struct Assets {
+    models: HashMap<&'static str, RawModel>,
+}
+impl Assets {
+    /// Loads every model in [`MODEL_FILES`], calling `on_progress` before each file and once when done
+    pub async fn load(mut on_progress: impl FnMut(LoadProgress)) -> anyhow::Result<Assets> {
+        let total = MODEL_FILES.len();
+        let mut models = HashMap::new();
+        for (loaded, file) in MODEL_FILES.into_iter().enumerate() {
+            on_progress(LoadProgress {
+                loaded,
+                total,
+                current: Some(file),
+            });
+            let model = load_model(file)
+                .await
+                .with_context(|| format!("Failed to load '{file}'"))?;
+            log::debug!(
+                "Loaded '{file}' ({} meshes, {} verts, {} parts, {} markers)",
+                model.meshes.len(),
+                model.meshes.iter().map(|m| m.verts.len()).sum::<usize>(),
+                model.parts.len(),
+                model.markers.len()
+            );
+            models.insert(file, model);
+        }
+        on_progress(LoadProgress {
+            loaded: total,
+            total,
+            current: None,
+        });
+        Ok(Assets { models })
+    }
+
+    /// Panics if the model isn't one of [`MODEL_FILES`]
+    pub fn model(&self, file: &str) -> &RawModel {
+        self.models
+            .get(file)
+            .unwrap_or_else(|| panic!("Model '{file}' isn't loaded"))
+    }
+}
+
+/// Loads a model with the importer for its file extension
+async fn load_model(file: &str) -> anyhow::Result<RawModel> {
+    let extension = file
+        .rsplit_once('.')
+        .map(|(_, extension)| extension.to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("obj") => formats::load_obj(file).await,
+        Some("gltf" | "glb") => formats::load_gltf(file).await,
+        _ => bail!("Unsupported model format"),
+    }
+}
@@ -1,7 +1,7 @@
 #[macro_export]
-macro_rules! load_obj_mesh {
+macro_rules! load_font {
     ($file:literal) => {{
-        use crate::{RawMaterial, RawMesh, RawVertex};
+        use $crate::fonts::{RawFont, RawGlyph};
         include!(std::concat!(env!("OUT_DIR"), "/", $file, ".rs"))
     }};
 }
@@ -27,6 +27,13 @@ impl Car {
         Self::WHEEL_OFFSET_REAR_DRIVER,
         Self::WHEEL_OFFSET_REAR_PASSENGER,
     ];
+    /// Markers the model can place its wheels with, in the same order as [`Self::WHEEL_OFFSETS`]
+    const WHEEL_MARKERS: [&'static str; 4] = [
+        "wheel_front_driver",
+        "wheel_front_passenger",
+        "wheel_rear_driver",
+        "wheel_rear_passenger",
+    ];
 
     /// The convex hulls that make up the hitbox of the car
     const HITBOX_PARTS: ([[f32; 3]; 8], [[f32; 3]; 12]) = (
@@ -55,9 +62,21 @@ impl Car {
             [-0.809219, 0.911109, -1.970972],
         ],
     );
+
+    /// Where the wheels attach, from the model's markers if it has them and [`Self::WHEEL_OFFSETS`] otherwise
+    pub fn wheel_offsets(assets: &Assets) -> [[f32; 3]; 4] {
+        let model = assets.model(Self::model_file);
+        std::array::from_fn(|i| {
+            model
+                .marker(Self::WHEEL_MARKERS[i])
+                .map_or(Self::WHEEL_OFFSETS[i], |marker| {
+                    marker.translation.vector.into()
+                })
+        })
+    }
 }
 impl GameObject for Car {
-    const render_meshes: &'static [RawMesh] = load_obj_mesh!("car.obj");
+    const model_file: &'static str = "car.obj";
 
     const debug_lines: &'static [RawDebugLine] = &debug_lines! {
         // top of toppart
@@ -92,7 +111,7 @@ impl GameObject for Car {
 
     };
 
-    fn get_collision_box() -> ColliderBuilder {
+    fn get_collision_box(_assets: &Assets) -> ColliderBuilder {
         let top_points: Vec<Point<f32>> = Self::HITBOX_PARTS
             .0
             .iter()
@@ -120,13 +139,13 @@ impl GameObject for Car {
 
 pub struct Wheel {}
 impl GameObject for Wheel {
-    const render_meshes: &'static [RawMesh] = load_obj_mesh!("wheel.obj");
+    const model_file: &'static str = "wheel.obj";
 
     const debug_lines: &'static [RawDebugLine] = &debug_lines!(
         [0.0, 0.0, 0.0] => [0.0, 0.5, 0.0];
     );
 
-    fn get_collision_box() -> ColliderBuilder {
+    fn get_collision_box(_assets: &Assets) -> ColliderBuilder {
         // wheel collision is handled artificially
         unimplemented!()
     }
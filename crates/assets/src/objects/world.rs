@@ -1,4 +1,4 @@
-use nalgebra::{DMatrix, Point3, Vector3};
+use nalgebra::{DMatrix, Vector3};
 use rapier3d::prelude::*;
 
 use crate::*;
@@ -9,9 +9,9 @@ impl Ocean {
     const WATER_HEIGHT: f32 = -2.96968;
 }
 impl GameObject for Ocean {
-    const render_meshes: &'static [RawMesh] = load_obj_mesh!("ocean.obj");
+    const model_file: &'static str = "ocean.obj";
 
-    fn get_collision_box() -> ColliderBuilder {
+    fn get_collision_box(_assets: &Assets) -> ColliderBuilder {
         ColliderBuilder::heightfield(
             DMatrix::from_element(2, 2, Self::WATER_HEIGHT),
             Vector3::new(Self::HITBOX_SIZE, 1.0, Self::HITBOX_SIZE),
@@ -21,7 +21,8 @@ impl GameObject for Ocean {
 
 pub struct Ground {}
 impl GameObject for Ground {
-    const render_meshes: &'static [RawMesh] = load_obj_mesh!("ground.obj");
+    const model_file: &'static str = "ground.obj";
+    const collision_model_file: &'static str = "ground_hitbox.obj";
 
     #[rustfmt::skip]
     const debug_lines: &'static [RawDebugLine] = &[
@@ -33,58 +34,15 @@ impl GameObject for Ground {
         RawDebugLine { col: GRAY, pos1: [0.0, 0.0, 0.0], pos2: [0.0, -1.0, 0.0], },
         RawDebugLine { col: GRAY, pos1: [0.0, 0.0, 0.0], pos2: [0.0, 0.0, -1.0], },
     ];
-
-    fn get_collision_box() -> ColliderBuilder {
-        let hitbox_mesh: RawMesh = load_obj_mesh!("ground_hitbox.obj")[0];
-        let mut verts: Vec<Point3<f32>> = Vec::new();
-        let mut indices: Vec<[u32; 3]> = Vec::new();
-
-        for v in hitbox_mesh.verts {
-            verts.push(Point3::from(v.pos));
-        }
-
-        let mut count = 0;
-        let mut cur_face: [u32; 3] = [0; 3];
-        for index in hitbox_mesh.indices {
-            cur_face[count] = *index as u32;
-            count += 1;
-            if count == 3 {
-                indices.push(cur_face);
-                count = 0;
-            }
-        }
-        ColliderBuilder::trimesh(verts, indices).expect("Failed to create trimesh collision box")
-    }
 }
 
 pub struct Roads {}
 impl GameObject for Roads {
-    const render_meshes: &'static [RawMesh] = load_obj_mesh!("roads.obj");
+    const model_file: &'static str = "roads.obj";
 }
 
 pub struct WorldDecor {}
 impl GameObject for WorldDecor {
-    const render_meshes: &'static [RawMesh] = load_obj_mesh!("decor.obj");
-
-    fn get_collision_box() -> ColliderBuilder {
-        let hitbox_mesh: RawMesh = load_obj_mesh!("decor_hitbox.obj")[0];
-        let mut verts: Vec<Point3<f32>> = Vec::new();
-        let mut indices: Vec<[u32; 3]> = Vec::new();
-
-        for v in hitbox_mesh.verts {
-            verts.push(Point3::from(v.pos));
-        }
-
-        let mut count = 0;
-        let mut cur_face: [u32; 3] = [0; 3];
-        for index in hitbox_mesh.indices {
-            cur_face[count] = *index as u32;
-            count += 1;
-            if count == 3 {
-                indices.push(cur_face);
-                count = 0;
-            }
-        }
-        ColliderBuilder::trimesh(verts, indices).expect("Failed to create trimesh collision box")
-    }
+    const model_file: &'static str = "decor.obj";
+    const collision_model_file: &'static str = "decor_hitbox.obj";
 }
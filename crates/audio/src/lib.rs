@@ -0,0 +1,66 @@
+mod output;
+mod synth;
+
+use std::sync::{Arc, Mutex};
+
+use utils::AudioInfo;
+
+use output::Output;
+pub use synth::Synth;
+
+/// Sample rate the synth runs at until an output tells it its own
+const DEFAULT_SAMPLE_RATE: f32 = 44100.0;
+
+/// The game's sound, synthesized from the car's state
+///
+/// If no output can be started the game carries on silently
+pub struct Audio {
+    synth: Arc<Mutex<Synth>>,
+    output: Option<Output>,
+}
+impl Audio {
+    pub fn new() -> Audio {
+        let synth = Arc::new(Mutex::new(Synth::new(DEFAULT_SAMPLE_RATE)));
+        let output = match Output::start(synth.clone()) {
+            Ok(output) => Some(output),
+            Err(e) => {
+                log::warn!("Audio is unavailable: {e}");
+                None
+            }
+        };
+        Audio { synth, output }
+    }
+
+    /// Master volume, from 0 (muted) to 1
+    pub fn set_volume(&mut self, volume: f32) {
+        self.synth.lock().unwrap().set_volume(volume);
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.synth.lock().unwrap().set_paused(paused);
+    }
+
+    /// Makes the sounds follow the car's latest state
+    pub fn set_info(&mut self, info: &AudioInfo) {
+        self.synth.lock().unwrap().set_info(info);
+    }
+
+    /// Keeps the output fed, called every frame
+    pub fn pump(&mut self) {
+        if let Some(output) = &mut self.output {
+            output.pump();
+        }
+    }
+
+    /// Starts playback if the platform held it back, call on player input
+    pub fn resume(&self) {
+        if let Some(output) = &self.output {
+            output.resume();
+        }
+    }
+}
+impl Default for Audio {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,11 @@
+//! Getting the synthesized samples to the speakers, with Web Audio in the browser and cpal natively
+
+#[cfg(not(target_arch = "wasm32"))]
+mod native;
+#[cfg(target_arch = "wasm32")]
+mod web;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use native::*;
+#[cfg(target_arch = "wasm32")]
+pub use web::*;
@@ -0,0 +1,69 @@
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context, anyhow};
+use cpal::{
+    Device, FromSample, SampleFormat, SizedSample, Stream, StreamConfig,
+    traits::{DeviceTrait, HostTrait, StreamTrait},
+};
+
+use crate::synth::Synth;
+
+/// Plays the synth on the default output device, which pulls samples from it on its own thread
+pub struct Output {
+    _stream: Stream,
+}
+impl Output {
+    pub fn start(synth: Arc<Mutex<Synth>>) -> anyhow::Result<Output> {
+        let device = cpal::default_host()
+            .default_output_device()
+            .context("No audio output device")?;
+        let config = device.default_output_config()?;
+        synth
+            .lock()
+            .unwrap()
+            .set_sample_rate(config.sample_rate().0 as f32);
+
+        let stream = match config.sample_format() {
+            SampleFormat::F32 => build_stream::<f32>(&device, &config.config(), synth),
+            SampleFormat::I16 => build_stream::<i16>(&device, &config.config(), synth),
+            SampleFormat::U16 => build_stream::<u16>(&device, &config.config(), synth),
+            format => return Err(anyhow!("Unsupported audio sample format {format}")),
+        }?;
+        stream.play()?;
+        log::info!(
+            "Playing audio at {} Hz on {}",
+            config.sample_rate().0,
+            device
+                .name()
+                .unwrap_or_else(|_| "unknown device".to_string())
+        );
+        Ok(Output { _stream: stream })
+    }
+
+    /// The stream pulls samples as it needs them, so there is nothing to do each frame
+    pub fn pump(&mut self) {}
+
+    pub fn resume(&self) {}
+}
+
+fn build_stream<T: SizedSample + FromSample<f32>>(
+    device: &Device,
+    config: &StreamConfig,
+    synth: Arc<Mutex<Synth>>,
+) -> anyhow::Result<Stream> {
+    let channels = config.channels as usize;
+    let mut mono = Vec::new();
+    let stream = device.build_output_stream(
+        config,
+        move |data: &mut [T], _| {
+            mono.resize(data.len() / channels, 0.0);
+            synth.lock().unwrap().render(&mut mono);
+            for (frame, sample) in data.chunks_mut(channels).zip(mono.iter()) {
+                frame.fill(T::from_sample(*sample));
+            }
+        },
+        |e| log::error!("Audio stream error: {e}"),
+        None,
+    )?;
+    Ok(stream)
+}
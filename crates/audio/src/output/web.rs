@@ -0,0 +1,74 @@
+use std::sync::{Arc, Mutex};
+
+use anyhow::anyhow;
+use web_sys::{AudioContext, AudioContextState};
+
+use crate::synth::Synth;
+
+/// Samples in each buffer queued up to play
+const BLOCK_SIZE: usize = 1024;
+/// How far ahead of the playback position buffers are queued, in seconds. Longer survives slower frames, but the
+/// sound lags the car by as much
+const LOOKAHEAD: f64 = 0.1;
+
+/// Plays the synth through Web Audio, rendering it in blocks that are scheduled back to back
+///
+/// Blocks are only rendered when pumped, so this relies on being pumped every frame
+pub struct Output {
+    context: AudioContext,
+    synth: Arc<Mutex<Synth>>,
+    /// Context time the next block starts playing at
+    next_start: f64,
+    block: Vec<f32>,
+}
+impl Output {
+    pub fn start(synth: Arc<Mutex<Synth>>) -> anyhow::Result<Output> {
+        let context =
+            AudioContext::new().map_err(|e| anyhow!("Failed to create audio context: {e:?}"))?;
+        synth.lock().unwrap().set_sample_rate(context.sample_rate());
+        log::info!("Playing audio at {} Hz", context.sample_rate());
+        Ok(Output {
+            context,
+            synth,
+            next_start: 0.0,
+            block: vec![0.0; BLOCK_SIZE],
+        })
+    }
+
+    /// Queues up enough blocks to keep playing until the next frame
+    pub fn pump(&mut self) {
+        let now = self.context.current_time();
+        if self.next_start < now {
+            // fell behind, most likely a stalled frame, so restart just ahead of the playback position
+            self.next_start = now + BLOCK_SIZE as f64 / self.context.sample_rate() as f64;
+        }
+        while self.next_start < now + LOOKAHEAD {
+            if let Err(e) = self.queue_block() {
+                log::error!("Failed to queue audio: {e:?}");
+                return;
+            }
+        }
+    }
+
+    fn queue_block(&mut self) -> Result<(), wasm_bindgen::JsValue> {
+        let sample_rate = self.context.sample_rate();
+        self.synth.lock().unwrap().render(&mut self.block);
+        let buffer = self
+            .context
+            .create_buffer(1, BLOCK_SIZE as u32, sample_rate)?;
+        buffer.copy_to_channel(&self.block, 0)?;
+        let source = self.context.create_buffer_source()?;
+        source.set_buffer(Some(&buffer));
+        source.connect_with_audio_node(&self.context.destination())?;
+        source.start_with_when(self.next_start)?;
+        self.next_start += BLOCK_SIZE as f64 / sample_rate as f64;
+        Ok(())
+    }
+
+    /// Browsers keep audio suspended until the player interacts with the page, so this is called on input
+    pub fn resume(&self) {
+        if self.context.state() == AudioContextState::Suspended {
+            let _ = self.context.resume();
+        }
+    }
+}
@@ -0,0 +1,420 @@
+use std::f32::consts::{PI, TAU};
+
+use utils::AudioInfo;
+
+/// How long the sounds take to glide to new values, in seconds, so they don't click when updated each frame
+const SMOOTHING_TIME: f32 = 0.05;
+
+/// Cylinder firings per engine revolution, for a four stroke four cylinder
+const FIRINGS_PER_REV: f32 = 2.0;
+/// Harmonics of the firing frequency making up the engine's tone
+const ENGINE_HARMONICS: usize = 8;
+const ENGINE_VOLUME: f32 = 0.35;
+
+const SQUEAL_FREQ: f32 = 850.0;
+/// How fast and how far the squeal's pitch wavers
+const SQUEAL_WOBBLE_FREQ: f32 = 7.0;
+const SQUEAL_WOBBLE_DEPTH: f32 = 0.03;
+const SQUEAL_VOLUME: f32 = 0.25;
+
+/// Speed the wind is loudest at, in m/s
+const WIND_FULL_SPEED: f32 = 50.0;
+const WIND_VOLUME: f32 = 0.3;
+
+/// Pitch of the thump at the start of an impact
+const IMPACT_THUMP_FREQ: f32 = 55.0;
+/// How long an impact takes to decay, in seconds, for the lightest and heaviest ones
+const IMPACT_DECAY: (f32, f32) = (0.06, 0.35);
+const IMPACT_VOLUME: f32 = 0.8;
+/// Most impacts ringing at once, the oldest are cut off past this
+const MAX_IMPACTS: usize = 8;
+
+/// Mixes the car's sounds from its state
+///
+/// Everything is synthesized as it's rendered, so the same inputs always give the same samples regardless of how
+/// they end up being played
+pub struct Synth {
+    sample_rate: f32,
+    volume: f32,
+    paused: bool,
+    gain: Smoothed,
+    noise: Noise,
+    engine: Engine,
+    squeal: Squeal,
+    wind: Wind,
+    impacts: Vec<Impact>,
+}
+impl Synth {
+    pub fn new(sample_rate: f32) -> Synth {
+        Synth {
+            sample_rate,
+            volume: 1.0,
+            paused: false,
+            gain: Smoothed::new(1.0),
+            noise: Noise::new(),
+            engine: Engine::new(),
+            squeal: Squeal::new(),
+            wind: Wind::new(),
+            impacts: Vec::new(),
+        }
+    }
+
+    pub fn sample_rate(&self) -> f32 {
+        self.sample_rate
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+    }
+
+    /// Master volume, from 0 (muted) to 1
+    pub fn set_volume(&mut self, volume: f32) {
+        self.volume = volume.clamp(0.0, 1.0);
+        self.gain.target = self.target_gain();
+    }
+
+    /// Fades everything out while paused
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+        self.gain.target = self.target_gain();
+    }
+
+    fn target_gain(&self) -> f32 {
+        if self.paused { 0.0 } else { self.volume }
+    }
+
+    /// Makes the sounds follow the car. They glide to the new state, and any impacts start playing
+    pub fn set_info(&mut self, info: &AudioInfo) {
+        self.engine.rpm.target = info.engine_rpm.max(0.0);
+        self.engine.load.target = info.engine_load.clamp(0.0, 1.0);
+        // the loudest tire sets the squeal, the rest only thicken it
+        let loudest = info.tire_slip.iter().copied().fold(0.0, f32::max);
+        let total: f32 = info.tire_slip.iter().sum();
+        self.squeal.amount.target = (loudest + (total - loudest) * 0.15).min(1.0);
+        self.wind.level.target = (info.speed.abs() / WIND_FULL_SPEED).min(1.0).powi(2);
+
+        for strength in info.impacts.iter() {
+            if self.impacts.len() == MAX_IMPACTS {
+                self.impacts.remove(0);
+            }
+            self.impacts.push(Impact::new(strength.clamp(0.0, 1.0)));
+        }
+    }
+
+    /// Fills the buffer with the next mono samples, from -1 to 1
+    pub fn render(&mut self, out: &mut [f32]) {
+        let dt = 1.0 / self.sample_rate;
+        let smoothing = 1.0 - (-dt / SMOOTHING_TIME).exp();
+        for sample in out.iter_mut() {
+            let noise = self.noise.next();
+            let mut mix = self.engine.next(dt, smoothing, noise)
+                + self.squeal.next(dt, smoothing, noise)
+                + self.wind.next(dt, smoothing, noise);
+            for impact in self.impacts.iter_mut() {
+                mix += impact.next(dt, noise);
+            }
+            // soft clipping keeps pileups of sounds from distorting harshly
+            *sample = (mix * self.gain.next(smoothing)).tanh();
+        }
+        self.impacts.retain(|impact| !impact.finished());
+    }
+}
+
+/// A value that glides towards its target instead of jumping
+struct Smoothed {
+    value: f32,
+    target: f32,
+}
+impl Smoothed {
+    fn new(value: f32) -> Smoothed {
+        Smoothed {
+            value,
+            target: value,
+        }
+    }
+
+    fn next(&mut self, smoothing: f32) -> f32 {
+        self.value += (self.target - self.value) * smoothing;
+        self.value
+    }
+}
+
+/// White noise from a xorshift generator, seeded the same every time so renders are repeatable
+struct Noise {
+    state: u32,
+}
+impl Noise {
+    fn new() -> Noise {
+        Noise { state: 0x9E37_79B9 }
+    }
+
+    /// Next value, from -1 to 1
+    fn next(&mut self) -> f32 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+        self.state as f32 / u32::MAX as f32 * 2.0 - 1.0
+    }
+}
+
+/// State variable filter, giving low and band passed versions of its input
+struct Filter {
+    low: f32,
+    band: f32,
+}
+impl Filter {
+    fn new() -> Filter {
+        Filter {
+            low: 0.0,
+            band: 0.0,
+        }
+    }
+
+    /// Filters the next sample, returning its low and band passed values. Higher `q` gives a narrower band
+    fn next(&mut self, input: f32, cutoff: f32, q: f32, dt: f32) -> (f32, f32) {
+        // unstable with cutoffs past about a sixth of the sample rate
+        let f = 2.0 * (PI * (cutoff * dt).min(1.0 / 6.0)).sin();
+        let high = input - self.low - self.band / q;
+        self.band += f * high;
+        self.low += f * self.band;
+        (self.low, self.band)
+    }
+}
+
+/// Harmonics at the firing frequency with a crackle of noise on each firing, brighter and louder under load
+struct Engine {
+    rpm: Smoothed,
+    load: Smoothed,
+    /// Position through the current firing, from 0 to 1
+    phase: f32,
+    filter: Filter,
+}
+impl Engine {
+    fn new() -> Engine {
+        Engine {
+            rpm: Smoothed::new(0.0),
+            load: Smoothed::new(0.0),
+            phase: 0.0,
+            filter: Filter::new(),
+        }
+    }
+
+    fn next(&mut self, dt: f32, smoothing: f32, noise: f32) -> f32 {
+        let rpm = self.rpm.next(smoothing);
+        let load = self.load.next(smoothing);
+        if rpm <= 0.0 {
+            return 0.0;
+        }
+        let freq = rpm / 60.0 * FIRINGS_PER_REV;
+        self.phase = (self.phase + freq * dt).fract();
+
+        // higher harmonics fall off slower under load
+        let rolloff = 2.0 - load;
+        let mut tone = 0.0;
+        let mut total_amplitude = 0.0;
+        for harmonic in 1..=ENGINE_HARMONICS {
+            let amplitude = (harmonic as f32).powf(-rolloff);
+            tone += (TAU * harmonic as f32 * self.phase).sin() * amplitude;
+            total_amplitude += amplitude;
+        }
+        tone /= total_amplitude;
+
+        // each firing is a sharp burst that dies off before the next
+        let firing = (1.0 - self.phase).powi(8);
+        let crackle = noise * firing * (0.2 + 0.5 * load);
+        let (low, _) = self
+            .filter
+            .next(tone + crackle, 300.0 + 2500.0 * load, 0.7, dt);
+        low * ENGINE_VOLUME * (0.5 + 0.5 * load)
+    }
+}
+
+/// A wavering whine with some hiss around it
+struct Squeal {
+    amount: Smoothed,
+    phase: f32,
+    wobble_phase: f32,
+    filter: Filter,
+}
+impl Squeal {
+    fn new() -> Squeal {
+        Squeal {
+            amount: Smoothed::new(0.0),
+            phase: 0.0,
+            wobble_phase: 0.0,
+            filter: Filter::new(),
+        }
+    }
+
+    fn next(&mut self, dt: f32, smoothing: f32, noise: f32) -> f32 {
+        let amount = self.amount.next(smoothing);
+        self.wobble_phase = (self.wobble_phase + SQUEAL_WOBBLE_FREQ * dt).fract();
+        let wobble = (TAU * self.wobble_phase).sin() * SQUEAL_WOBBLE_DEPTH;
+        // harder slides squeal a little higher
+        let freq = SQUEAL_FREQ * (1.0 + 0.15 * amount + wobble);
+        self.phase = (self.phase + freq * dt).fract();
+        let (_, hiss) = self.filter.next(noise, freq * 1.5, 4.0, dt);
+        let tone = (TAU * self.phase).sin();
+        (tone * 0.6 + hiss * 0.4) * amount * SQUEAL_VOLUME
+    }
+}
+
+/// Low passed noise, getting louder and brighter with speed
+struct Wind {
+    level: Smoothed,
+    filter: Filter,
+}
+impl Wind {
+    fn new() -> Wind {
+        Wind {
+            level: Smoothed::new(0.0),
+            filter: Filter::new(),
+        }
+    }
+
+    fn next(&mut self, dt: f32, smoothing: f32, noise: f32) -> f32 {
+        let level = self.level.next(smoothing);
+        let (low, _) = self.filter.next(noise, 150.0 + 1500.0 * level, 0.8, dt);
+        low * level * WIND_VOLUME
+    }
+}
+
+/// A thump and a burst of crunch that decay away, longer the harder the hit
+struct Impact {
+    strength: f32,
+    /// Time since the impact, in seconds
+    time: f32,
+    decay: f32,
+    filter: Filter,
+}
+impl Impact {
+    fn new(strength: f32) -> Impact {
+        let (min_decay, max_decay) = IMPACT_DECAY;
+        Impact {
+            strength,
+            time: 0.0,
+            decay: min_decay + (max_decay - min_decay) * strength,
+            filter: Filter::new(),
+        }
+    }
+
+    fn next(&mut self, dt: f32, noise: f32) -> f32 {
+        let envelope = (-self.time / self.decay).exp();
+        let thump = (TAU * IMPACT_THUMP_FREQ * self.time).sin();
+        let (crunch, _) = self
+            .filter
+            .next(noise, 800.0 + 2000.0 * self.strength, 0.7, dt);
+        self.time += dt;
+        (thump * 0.6 + crunch * 0.8) * envelope * self.strength.sqrt() * IMPACT_VOLUME
+    }
+
+    fn finished(&self) -> bool {
+        // about -60dB
+        self.time > self.decay * 7.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+
+    fn loud_info() -> AudioInfo {
+        AudioInfo {
+            engine_rpm: 7000.0,
+            engine_load: 1.0,
+            tire_slip: [1.0; 4],
+            speed: 80.0,
+            impacts: vec![1.0; MAX_IMPACTS],
+        }
+    }
+
+    /// Renders the given length of audio
+    fn render_for(synth: &mut Synth, seconds: f32) -> Vec<f32> {
+        let mut out = vec![0.0; (seconds * SAMPLE_RATE) as usize];
+        synth.render(&mut out);
+        out
+    }
+
+    fn peak(samples: &[f32]) -> f32 {
+        samples
+            .iter()
+            .fold(0.0, |peak, sample| peak.max(sample.abs()))
+    }
+
+    #[test]
+    fn silent_when_idle() {
+        let mut synth = Synth::new(SAMPLE_RATE);
+        synth.set_info(&AudioInfo::default());
+        assert!(
+            render_for(&mut synth, 0.5)
+                .iter()
+                .all(|sample| *sample == 0.0)
+        );
+    }
+
+    #[test]
+    fn stays_in_range() {
+        let mut synth = Synth::new(SAMPLE_RATE);
+        let mut samples = Vec::new();
+        for _ in 0..10 {
+            synth.set_info(&loud_info());
+            samples.extend(render_for(&mut synth, 0.05));
+        }
+        assert!(peak(&samples) > 0.1);
+        assert!(samples.iter().all(|sample| (-1.0..=1.0).contains(sample)));
+    }
+
+    #[test]
+    fn pausing_fades_out() {
+        let mut synth = Synth::new(SAMPLE_RATE);
+        synth.set_info(&AudioInfo {
+            impacts: Vec::new(),
+            ..loud_info()
+        });
+        assert!(peak(&render_for(&mut synth, 0.2)) > 0.1);
+
+        synth.set_paused(true);
+        // a handful of smoothing times takes the gain to practically nothing
+        render_for(&mut synth, SMOOTHING_TIME * 10.0);
+        assert!(peak(&render_for(&mut synth, 0.1)) < 1e-3);
+
+        synth.set_paused(false);
+        render_for(&mut synth, SMOOTHING_TIME * 10.0);
+        assert!(peak(&render_for(&mut synth, 0.1)) > 0.1);
+    }
+
+    #[test]
+    fn impacts_decay() {
+        let mut synth = Synth::new(SAMPLE_RATE);
+        synth.set_info(&AudioInfo {
+            impacts: vec![1.0],
+            ..AudioInfo::default()
+        });
+        let decay = synth.impacts[0].decay;
+        let samples = render_for(&mut synth, decay * 7.0 + 0.01);
+        assert!(peak(&samples[..1000]) > 0.1);
+        assert!(peak(&samples[samples.len() - 1000..]) < 1e-3);
+        assert!(synth.impacts.is_empty());
+        assert_eq!(peak(&render_for(&mut synth, 0.1)), 0.0);
+    }
+
+    #[test]
+    fn renders_are_repeatable() {
+        let render = || {
+            let mut synth = Synth::new(SAMPLE_RATE);
+            let mut samples = Vec::new();
+            for i in 0..10 {
+                synth.set_info(&AudioInfo {
+                    engine_rpm: 1000.0 + 500.0 * i as f32,
+                    impacts: if i == 3 { vec![0.5] } else { Vec::new() },
+                    ..loud_info()
+                });
+                samples.extend(render_for(&mut synth, 0.02));
+            }
+            samples
+        };
+        assert_eq!(render(), render());
+    }
+}
@@ -0,0 +1,131 @@
+use std::{fmt, io};
+
+/// Where in a file something was found
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Location {
+    pub file: String,
+    /// Starting from 1, or 0 if it's about the whole file
+    pub line: usize,
+    /// Starting from 1, in characters
+    pub column: usize,
+}
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}", self.file)
+        } else {
+            write!(f, "{}:{}:{}", self.file, self.line, self.column)
+        }
+    }
+}
+
+/// Why a file couldn't be parsed
+#[derive(Debug)]
+pub struct OBJError {
+    pub location: Location,
+    pub kind: OBJErrorKind,
+}
+impl fmt::Display for OBJError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.location, self.kind)
+    }
+}
+impl std::error::Error for OBJError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            OBJErrorKind::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum OBJErrorKind {
+    /// The file, or a material library it uses, couldn't be read
+    Io(io::Error),
+    InvalidNumber(String),
+    /// NaN or infinity
+    NonFiniteNumber(String),
+    /// The w of a vertex is 0, so it can't be divided by
+    ZeroWeight,
+    /// A directive is missing something, such as a coordinate or a name
+    Missing(&'static str),
+    InvalidIndex(String),
+    /// A face refers to a vertex element that hasn't been defined yet
+    IndexOutOfRange {
+        token: String,
+        element: &'static str,
+        count: usize,
+    },
+    /// A face vertex without a normal index, such as `1/2`
+    MissingNormal(String),
+    TooFewVertices(usize),
+    UnknownMaterial(String),
+}
+impl fmt::Display for OBJErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OBJErrorKind::Io(e) => write!(f, "Failed to read file: {e}"),
+            OBJErrorKind::InvalidNumber(token) => write!(f, "Expected a number, found `{token}`"),
+            OBJErrorKind::NonFiniteNumber(token) => write!(f, "`{token}` isn't a finite number"),
+            OBJErrorKind::ZeroWeight => write!(f, "Vertex weight can't be 0"),
+            OBJErrorKind::Missing(what) => write!(f, "Missing {what}"),
+            OBJErrorKind::InvalidIndex(token) => {
+                write!(f, "Expected a face vertex like `1/2/3`, found `{token}`")
+            }
+            OBJErrorKind::IndexOutOfRange {
+                token,
+                element,
+                count,
+            } => write!(
+                f,
+                "`{token}` refers to a {element} that doesn't exist, there are {count} so far"
+            ),
+            OBJErrorKind::MissingNormal(token) => {
+                write!(f, "Face vertex `{token}` has no normal index")
+            }
+            OBJErrorKind::TooFewVertices(n) => {
+                write!(f, "Faces need at least 3 vertices, this one has {n}")
+            }
+            OBJErrorKind::UnknownMaterial(name) => write!(f, "No material named `{name}`"),
+        }
+    }
+}
+
+/// Something in a file that was parsed, but may not come out as intended
+#[derive(Debug)]
+pub struct OBJWarning {
+    pub location: Location,
+    pub kind: OBJWarningKind,
+}
+impl fmt::Display for OBJWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.location, self.kind)
+    }
+}
+
+#[derive(Debug)]
+pub enum OBJWarningKind {
+    /// A directive the parser ignores. Only its first use in each file is reported
+    UnsupportedDirective(String),
+    /// A face with no area, which is left out
+    DegenerateFace,
+    /// A material property before any `newmtl`, which is ignored
+    NoMaterial(String),
+    /// A line that couldn't be parsed in lenient mode, which is left out
+    SkippedLine(OBJErrorKind),
+}
+impl fmt::Display for OBJWarningKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OBJWarningKind::UnsupportedDirective(directive) => {
+                write!(f, "Ignoring unsupported directive `{directive}`")
+            }
+            OBJWarningKind::DegenerateFace => write!(f, "Skipping face with no area"),
+            OBJWarningKind::NoMaterial(directive) => {
+                write!(f, "Ignoring `{directive}` before any `newmtl`")
+            }
+            OBJWarningKind::SkippedLine(e) => write!(f, "Skipping line: {e}"),
+        }
+    }
+}
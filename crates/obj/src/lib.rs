@@ -1,28 +1,27 @@
+mod error;
 mod mtl;
+mod parse;
+mod triangulate;
 
 use std::{
-    collections::HashMap,
-    fs::File,
-    io::{self, BufRead, BufReader},
+    collections::{BTreeMap, HashMap},
+    io,
     path::PathBuf,
 };
 
 use ordered_float::NotNan;
 
-use mtl::parse_mtl_file;
-
-/// How a vertex is identified in a face definition, e.g. `4/4/3`
-#[derive(PartialEq, Eq, Hash, Clone, Copy)]
-struct VertIndexes {
-    pos_index: usize,
-    normal_index: usize,
-    // texcoord_index: usize,
-}
+pub use error::{Location, OBJError, OBJErrorKind, OBJWarning, OBJWarningKind};
+use mtl::parse_mtl;
+use parse::{Diagnostics, LineResult, Words, not_nan, parse_number};
+use triangulate::triangulate;
 
 #[derive(PartialEq, Eq, Hash, Clone, Copy)]
 pub struct Vert {
     pub pos: [NotNan<f32>; 3],
     pub normal: [NotNan<f32>; 3],
+    /// (0, 0) is the bottom left of the texture, and is used for verts without one
+    pub texcoord: [NotNan<f32>; 2],
 }
 type Face = [usize; 3];
 
@@ -39,18 +38,31 @@ impl OBJMesh {
             existing_verts: HashMap::new(),
         }
     }
+
+    /// Index of the vert, emitting it if it's new
+    fn add_vert(&mut self, vert: Vert) -> usize {
+        if let Some(found_index) = self.existing_verts.get(&vert) {
+            return *found_index;
+        }
+        self.verts.push(vert);
+        self.existing_verts.insert(vert, self.verts.len() - 1);
+        self.verts.len() - 1
+    }
 }
 
 #[derive(Clone)]
 pub struct OBJMaterial {
     pub name: String,
     pub diffuse_color: [f32; 3],
+    /// File name of the diffuse texture, as written in the .mtl file
+    pub diffuse_texture: Option<String>,
 }
 impl OBJMaterial {
     pub(self) fn new(name: String) -> Self {
         Self {
             name,
             diffuse_color: [1.0, 1.0, 1.0],
+            diffuse_texture: None,
         }
     }
 }
@@ -59,153 +71,587 @@ impl Default for OBJMaterial {
         Self {
             name: String::new(),
             diffuse_color: [1.0, 0.0, 1.0],
+            diffuse_texture: None,
         }
     }
 }
 
+/// How forgiving parsing is of malformed files
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ParseOptions {
+    /// Skip lines that can't be parsed, reporting them as warnings, instead of failing at the first one
+    pub lenient: bool,
+}
+
+/// A parsed .obj file
+pub struct OBJModel {
+    pub objects: Vec<OBJObject>,
+    /// Problems in the .obj file and its material libraries that didn't stop them from being parsed
+    pub warnings: Vec<OBJWarning>,
+}
+
+/// A named object, from an `o` directive. Faces before the first one are in an object with an empty name
+pub struct OBJObject {
+    pub name: String,
+    /// Faces that aren't in a group, one mesh per material
+    pub meshes: Vec<(OBJMaterial, OBJMesh)>,
+    pub groups: Vec<OBJGroup>,
+}
+
+/// A named group of faces within an object, from a `g` directive. Faces in several groups at once (`g a b`) are in a
+/// group named after all of them
+pub struct OBJGroup {
+    pub name: String,
+    /// One mesh per material
+    pub meshes: Vec<(OBJMaterial, OBJMesh)>,
+}
+
 /// Reads in a Wavefront .obj file.
 ///
 /// This parser only supports the following:
-/// - Geometric vertices~~, texture coordinates,~~ and normals
-/// - Diffuse color in mtllib materials
-/// - Triangular faces (poly faces can be read, but will just be parsed as a tri fan, which may produce incorrect results)
+/// - Geometric vertices, texture coordinates, and normals
+/// - Diffuse color and texture in mtllib materials
+/// - Polygon faces, which are split into triangles by ear clipping, so concave polygons come out right
 ///
-/// This parser ignores named objects and groups, instead treating the whole file as a model and grouping faces into
-/// meshes by material
-pub fn parse_obj_file(input_filepath: PathBuf) -> io::Result<Vec<(OBJMaterial, OBJMesh)>> {
-    let input_file = File::open(input_filepath.clone())?;
-    let input = BufReader::new(input_file);
-
-    let mut raw_vert_positions: Vec<[f32; 3]> = Vec::new();
-    let mut raw_vert_normals: Vec<[f32; 3]> = Vec::new();
-    //let mut raw_vert_texcoords: Vec<[f32; 2]> = Vec::new();
-
-    let mut material_table: HashMap<String, usize> = HashMap::new();
-    let mut material_list: Vec<OBJMaterial> = Vec::new();
-
-    // hashmap of each material (indexed) and its mesh
-    let mut meshes: HashMap<Option<usize>, OBJMesh> = HashMap::new();
-    meshes.insert(None, OBJMesh::empty());
-    let mut current_material_index: Option<usize> = None;
-    let mut current_mesh: &mut OBJMesh = meshes.get_mut(&current_material_index).unwrap();
-
-    for line in input.lines() {
-        let line = line?;
-        let mut words = line.split_ascii_whitespace().map(String::from);
-        let first_word = if let Some(word) = words.next() {
-            word
-        } else {
+/// Faces are kept in the objects and groups they're in, which are split into meshes by material. Objects and groups
+/// without any faces are left out. Unsupported directives and faces with no area are skipped with a warning
+pub fn parse_obj_file(
+    input_filepath: PathBuf,
+    options: ParseOptions,
+) -> Result<OBJModel, OBJError> {
+    let file = input_filepath.display().to_string();
+    let source = std::fs::read_to_string(&input_filepath).map_err(|e| OBJError {
+        location: Location {
+            file: file.clone(),
+            line: 0,
+            column: 0,
+        },
+        kind: OBJErrorKind::Io(e),
+    })?;
+    parse_obj(&file, &source, options, |mtl_file| {
+        std::fs::read_to_string(input_filepath.with_file_name(mtl_file))
+    })
+}
+
+/// Reads in the contents of a Wavefront .obj file, like [`parse_obj_file`]. `file` is its name, for error locations
+///
+/// Material libraries are read with `read_mtl`, given their file name as written in the .obj file. See
+/// [`mtl_libraries`] to find which ones will be asked for ahead of time
+pub fn parse_obj(
+    file: &str,
+    source: &str,
+    options: ParseOptions,
+    mut read_mtl: impl FnMut(&str) -> io::Result<String>,
+) -> Result<OBJModel, OBJError> {
+    let mut warnings = Vec::new();
+    let mut parser = OBJParser {
+        diagnostics: Diagnostics::new(file, options, &mut warnings),
+        options,
+        raw_vert_positions: Vec::new(),
+        raw_vert_normals: Vec::new(),
+        raw_vert_texcoords: Vec::new(),
+        material_table: HashMap::new(),
+        material_list: Vec::new(),
+        objects: vec![ParsedObject::new(String::new())],
+        current_object: 0,
+        current_group: None,
+        current_material_index: None,
+    };
+
+    for (line_index, line) in source.lines().enumerate() {
+        let line_number = line_index + 1;
+        let mut words = Words::new(line);
+        let Some((column, directive)) = words.next() else {
             continue;
         };
-        match first_word.as_str() {
-            "v" => raw_vert_positions.push(parse_float_triplet(words)),
-            "vn" => raw_vert_normals.push(parse_float_triplet(words)),
-            "f" => current_mesh.faces.append(
-                &mut parse_faces(words)
+        let result = if directive == "mtllib" {
+            parser.parse_mtllib(words, &mut read_mtl)?
+        } else {
+            parser.parse_line(line_number, column, directive, words)
+        };
+        if let Err((column, kind)) = result {
+            parser.diagnostics.error(line_number, column, kind)?;
+        }
+    }
+
+    let OBJParser {
+        material_list,
+        objects,
+        ..
+    } = parser;
+    // gives each mesh its material, leaving out empty ones
+    let with_materials = |meshes: MeshesByMaterial| -> Vec<(OBJMaterial, OBJMesh)> {
+        meshes
+            .into_iter()
+            .filter(|(_, mesh)| !mesh.faces.is_empty())
+            .map(|(mat_index, mesh)| {
+                (
+                    mat_index
+                        .map(|index| material_list[index].clone())
+                        .unwrap_or(OBJMaterial::default()),
+                    mesh,
+                )
+            })
+            .collect()
+    };
+    Ok(OBJModel {
+        objects: objects
+            .into_iter()
+            .map(|object| OBJObject {
+                name: object.name,
+                meshes: with_materials(object.meshes),
+                groups: object
+                    .groups
                     .into_iter()
-                    .map(|verts| {
-                        verts.map(|v: VertIndexes| {
-                            let new_vert = Vert {
-                                pos: raw_vert_positions[v.pos_index]
-                                    .map(|f| NotNan::new(f).unwrap()),
-                                normal: raw_vert_normals[v.normal_index]
-                                    .map(|f| NotNan::new(f).unwrap()),
-                            };
-                            // checking if this vert already exists
-                            if let Some(found_index) = current_mesh.existing_verts.get(&new_vert) {
-                                return *found_index; // vert already exists
-                            }
-                            // this is a new vert, emit it
-                            current_mesh.verts.push(new_vert);
-                            current_mesh
-                                .existing_verts
-                                .insert(new_vert, current_mesh.verts.len() - 1);
-                            return current_mesh.verts.len() - 1;
-                        })
+                    .map(|(name, meshes)| OBJGroup {
+                        name,
+                        meshes: with_materials(meshes),
                     })
+                    .filter(|group| !group.meshes.is_empty())
                     .collect(),
-            ),
-            "mtllib" => parse_mtl_file(
-                input_filepath.parent().unwrap().join(words.next().unwrap()),
-                &mut material_list,
-                &mut material_table,
-            )?,
+            })
+            .filter(|object| !object.meshes.is_empty() || !object.groups.is_empty())
+            .collect(),
+        warnings,
+    })
+}
+
+/// File names of the material libraries an .obj file uses
+pub fn mtl_libraries(source: &str) -> Vec<String> {
+    source
+        .lines()
+        .filter_map(|line| {
+            let mut words = line.split_ascii_whitespace();
+            match (words.next(), words.next()) {
+                (Some("mtllib"), Some(file)) => Some(file.to_string()),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Meshes by the index of their material
+type MeshesByMaterial = BTreeMap<Option<usize>, OBJMesh>;
+
+/// An object whose faces are still being read
+struct ParsedObject {
+    name: String,
+    meshes: MeshesByMaterial,
+    groups: Vec<(String, MeshesByMaterial)>,
+}
+impl ParsedObject {
+    fn new(name: String) -> Self {
+        Self {
+            name,
+            meshes: BTreeMap::new(),
+            groups: Vec::new(),
+        }
+    }
+}
+
+/// State of an .obj file being parsed
+struct OBJParser<'a> {
+    diagnostics: Diagnostics<'a>,
+    options: ParseOptions,
+    raw_vert_positions: Vec<[NotNan<f32>; 3]>,
+    raw_vert_normals: Vec<[NotNan<f32>; 3]>,
+    raw_vert_texcoords: Vec<[NotNan<f32>; 2]>,
+    material_table: HashMap<String, usize>,
+    material_list: Vec<OBJMaterial>,
+    objects: Vec<ParsedObject>,
+    current_object: usize,
+    /// Index into the current object's groups, `None` when faces aren't in a group
+    current_group: Option<usize>,
+    current_material_index: Option<usize>,
+}
+impl OBJParser<'_> {
+    fn parse_line(
+        &mut self,
+        line_number: usize,
+        column: usize,
+        directive: &str,
+        mut words: Words,
+    ) -> LineResult<()> {
+        match directive {
+            "v" => {
+                let pos = parse_float_triplet(&mut words)?;
+                self.raw_vert_positions.push(pos);
+            }
+            "vn" => {
+                let normal = parse_float_triplet(&mut words)?;
+                self.raw_vert_normals.push(normal);
+            }
+            "vt" => {
+                let texcoord = parse_texcoord(&mut words)?;
+                self.raw_vert_texcoords.push(texcoord);
+            }
+            "f" => self.parse_face(line_number, column, words)?,
             "usemtl" => {
-                let mtl_name = words.next().unwrap();
-                current_material_index = Some(*material_table.get(&mtl_name).unwrap());
-                if !meshes.contains_key(&current_material_index) {
-                    meshes.insert(current_material_index, OBJMesh::empty());
-                }
-                current_mesh = meshes.get_mut(&current_material_index).unwrap()
+                let (column, mtl_name) = words.expect("a material name")?;
+                let index = *self
+                    .material_table
+                    .get(mtl_name)
+                    .ok_or_else(|| (column, OBJErrorKind::UnknownMaterial(mtl_name.to_string())))?;
+                self.current_material_index = Some(index);
             }
-            _ => {}
+            "o" => {
+                let (_, name) = words.expect("an object name")?;
+                self.current_object = match self.objects.iter().position(|o| o.name == name) {
+                    Some(index) => index,
+                    None => {
+                        self.objects.push(ParsedObject::new(name.to_string()));
+                        self.objects.len() - 1
+                    }
+                };
+                self.current_group = None;
+            }
+            "g" => {
+                // no names puts faces back in the object itself
+                let name = words.map(|(_, name)| name).collect::<Vec<_>>().join(" ");
+                let groups = &mut self.objects[self.current_object].groups;
+                self.current_group = if name.is_empty() {
+                    None
+                } else {
+                    Some(match groups.iter().position(|(n, _)| *n == name) {
+                        Some(index) => index,
+                        None => {
+                            groups.push((name, BTreeMap::new()));
+                            groups.len() - 1
+                        }
+                    })
+                };
+            }
+            _ if directive.starts_with('#') => {}
+            _ => self.diagnostics.unsupported(line_number, column, directive),
         }
+        Ok(())
+    }
+
+    /// Reads and parses a material library. Errors in the library itself fail straight away, since they're already
+    /// skipped in lenient mode
+    fn parse_mtllib(
+        &mut self,
+        mut words: Words,
+        read_mtl: &mut impl FnMut(&str) -> io::Result<String>,
+    ) -> Result<LineResult<()>, OBJError> {
+        let (column, mtl_file) = match words.expect("a material library file name") {
+            Ok(word) => word,
+            Err(e) => return Ok(Err(e)),
+        };
+        let mtl_source = match read_mtl(mtl_file) {
+            Ok(source) => source,
+            Err(e) => return Ok(Err((column, OBJErrorKind::Io(e)))),
+        };
+        parse_mtl(
+            mtl_file,
+            &mtl_source,
+            self.options,
+            &mut self.material_list,
+            &mut self.material_table,
+            &mut *self.diagnostics.warnings,
+        )?;
+        Ok(Ok(()))
     }
 
-    Ok(meshes
-        .into_iter()
-        .filter(|(_, mesh)| !mesh.verts.is_empty())
-        .map(|(mat_index, mesh)| {
-            (
-                mat_index
-                    .map(|index| material_list[index].clone())
-                    .unwrap_or(OBJMaterial::default()),
-                mesh,
-            )
+    /// parses a face, triangulating polygons
+    fn parse_face(&mut self, line_number: usize, column: usize, words: Words) -> LineResult<()> {
+        // every vertex is checked before any of the face is added
+        let verts = words
+            .map(|word| self.parse_face_vertex(word))
+            .collect::<LineResult<Vec<Vert>>>()?;
+        if verts.len() < 3 {
+            return Err((column, OBJErrorKind::TooFewVertices(verts.len())));
+        }
+        let positions: Vec<[f32; 3]> = verts.iter().map(|v| v.pos.map(f32::from)).collect();
+        let triangles = triangulate(&positions);
+        if triangles.is_empty() {
+            self.diagnostics
+                .warn(line_number, column, OBJWarningKind::DegenerateFace);
+            return Ok(());
+        }
+
+        let object = &mut self.objects[self.current_object];
+        let meshes = match self.current_group {
+            Some(group) => &mut object.groups[group].1,
+            None => &mut object.meshes,
+        };
+        let mesh = meshes
+            .entry(self.current_material_index)
+            .or_insert_with(OBJMesh::empty);
+        for triangle in triangles {
+            let face = triangle.map(|i| mesh.add_vert(verts[i]));
+            mesh.faces.push(face);
+        }
+        Ok(())
+    }
+
+    /// Looks up the elements of a vertex in a face definition, e.g. `4/4/3`
+    fn parse_face_vertex(&self, (column, token): (usize, &str)) -> LineResult<Vert> {
+        let mut nums = token.splitn(3, '/');
+        let (vi, vti, vni) = match (nums.next(), nums.next(), nums.next()) {
+            (Some(vi), vti, Some(vni)) if !vni.is_empty() => (vi, vti, vni),
+            _ => return Err((column, OBJErrorKind::MissingNormal(token.to_string()))),
+        };
+        let pos_index = resolve_index(
+            (column, token),
+            vi,
+            "position",
+            self.raw_vert_positions.len(),
+        )?;
+        // empty in `v//vn`
+        let texcoord_index = match vti {
+            Some("") | None => None,
+            Some(vti) => Some(resolve_index(
+                (column, token),
+                vti,
+                "texture coordinate",
+                self.raw_vert_texcoords.len(),
+            )?),
+        };
+        let normal_index =
+            resolve_index((column, token), vni, "normal", self.raw_vert_normals.len())?;
+
+        Ok(Vert {
+            pos: self.raw_vert_positions[pos_index],
+            normal: self.raw_vert_normals[normal_index],
+            texcoord: texcoord_index
+                .map_or([0.0, 0.0].map(not_nan), |i| self.raw_vert_texcoords[i]),
         })
-        .collect())
+    }
 }
 
-fn parse_float_triplet<I: Iterator<Item = String>>(mut words: I) -> [f32; 3] {
-    let x = words.next().unwrap().parse::<f32>().unwrap();
-    let y = words.next().unwrap().parse::<f32>().unwrap();
-    let z = words.next().unwrap().parse::<f32>().unwrap();
-    let w = if let Some(w) = words.next() {
-        w.parse::<f32>().unwrap()
+/// Turns a 1-based index from a face vertex into a 0-based one. Negative indices count back from the latest element
+fn resolve_index(
+    (column, token): (usize, &str),
+    index: &str,
+    element: &'static str,
+    count: usize,
+) -> LineResult<usize> {
+    let index = index
+        .parse::<isize>()
+        .map_err(|_| (column, OBJErrorKind::InvalidIndex(token.to_string())))?;
+    let resolved = if index < 0 {
+        count.checked_sub(index.unsigned_abs())
+    } else {
+        (index as usize).checked_sub(1).filter(|i| *i < count)
+    };
+    resolved.ok_or_else(|| {
+        (
+            column,
+            OBJErrorKind::IndexOutOfRange {
+                token: token.to_string(),
+                element,
+                count,
+            },
+        )
+    })
+}
+
+/// parses `x y z [w]`, dividing by w
+fn parse_float_triplet(words: &mut Words) -> LineResult<[NotNan<f32>; 3]> {
+    let x = parse_number(words.expect("an x coordinate")?)?;
+    let y = parse_number(words.expect("a y coordinate")?)?;
+    let z = parse_number(words.expect("a z coordinate")?)?;
+    let w = if let Some(word) = words.next() {
+        let w = parse_number(word)?;
+        if w == 0.0 {
+            return Err((word.0, OBJErrorKind::ZeroWeight));
+        }
+        w
     } else {
         1.0
     };
-    return [x / w, y / w, z / w];
-}
-
-/// parses a face, treating poly-faces as triangle fans
-fn parse_faces<I: Iterator<Item = String>>(mut words: I) -> Vec<[VertIndexes; 3]> {
-    let parse_vertex = |s: String| {
-        let mut nums = s.splitn(3, '/');
-        let vi = nums.next().unwrap().parse::<usize>().unwrap();
-        let _vti = nums.next(); //.map(|s| s.parse::<usize>().unwrap());
-        let vni = nums.next().unwrap().parse::<usize>().unwrap();
-        return VertIndexes {
-            pos_index: vi - 1,
-            normal_index: vni - 1,
-        };
+    Ok([x / w, y / w, z / w].map(not_nan))
+}
+
+/// parses a texture coordinate, where v and the unused depth are optional
+fn parse_texcoord(words: &mut Words) -> LineResult<[NotNan<f32>; 2]> {
+    let u = parse_number(words.expect("a u coordinate")?)?;
+    let v = if let Some(word) = words.next() {
+        parse_number(word)?
+    } else {
+        0.0
     };
-    let mut first_and_last_vert = None;
-    let mut parse_face = || {
-        if let Some(word) = words.next() {
-            if let Some((first, last)) = first_and_last_vert {
-                let face = [first, last, parse_vertex(word)];
-                first_and_last_vert = Some((first, face[2]));
-                Some(face)
+    if let Some(word) = words.next() {
+        parse_number(word)?;
+    }
+    Ok([u, v].map(not_nan))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A unit square facing up, after the lines in `body`
+    fn square(body: &str) -> String {
+        format!("{body}\nv 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nvn 0 0 1\nf 1//1 2//1 3//1 4//1\n")
+    }
+
+    fn parse(source: &str, lenient: bool) -> Result<OBJModel, OBJError> {
+        parse_obj(
+            "test.obj",
+            source,
+            ParseOptions { lenient },
+            |mtl_file| match mtl_file {
+                "test.mtl" => Ok("newmtl red\nKd 1 0 0\n".to_string()),
+                _ => Err(io::Error::from(io::ErrorKind::NotFound)),
+            },
+        )
+    }
+
+    fn location(line: usize, column: usize) -> Location {
+        Location {
+            file: "test.obj".to_string(),
+            line,
+            column,
+        }
+    }
+
+    #[test]
+    fn parses_a_square() {
+        let model = parse(&square("mtllib test.mtl\nusemtl red"), false).unwrap();
+        assert!(model.warnings.is_empty());
+        assert_eq!(model.objects.len(), 1);
+        let (material, mesh) = &model.objects[0].meshes[0];
+        assert_eq!(material.diffuse_color, [1.0, 0.0, 0.0]);
+        assert_eq!(mesh.faces.len(), 2);
+    }
+
+    #[test]
+    fn malformed_float_points_at_the_token() {
+        let e = parse("v 1 2.0.0 3", false).err().unwrap();
+        assert_eq!(e.location, location(1, 5));
+        assert!(matches!(&e.kind, OBJErrorKind::InvalidNumber(token) if token == "2.0.0"));
+        assert_eq!(
+            e.to_string(),
+            "test.obj:1:5: Expected a number, found `2.0.0`"
+        );
+    }
+
+    #[test]
+    fn face_without_normals() {
+        let e = parse(&square("f 1/2 2/2 3/2"), false).err().unwrap();
+        assert_eq!(e.location, location(1, 3));
+        assert!(matches!(&e.kind, OBJErrorKind::MissingNormal(token) if token == "1/2"));
+    }
+
+    #[test]
+    fn unknown_material() {
+        let e = parse("mtllib test.mtl\nusemtl blue", false).err().unwrap();
+        assert_eq!(e.location, location(2, 8));
+        assert!(matches!(&e.kind, OBJErrorKind::UnknownMaterial(name) if name == "blue"));
+    }
+
+    #[test]
+    fn nan_is_rejected() {
+        let e = parse("v nan 0 0", false).err().unwrap();
+        assert_eq!(e.location, location(1, 3));
+        assert!(matches!(&e.kind, OBJErrorKind::NonFiniteNumber(token) if token == "nan"));
+    }
+
+    #[test]
+    fn lenient_mode_skips_bad_lines() {
+        let source = square("v 1 2.0.0 3\nf 1/2 2/2 3/2\nusemtl blue\nv nan 0 0");
+        let model = parse(&source, true).unwrap();
+        let skipped: Vec<_> = model
+            .warnings
+            .iter()
+            .map(|warning| match &warning.kind {
+                OBJWarningKind::SkippedLine(kind) => (warning.location.line, kind.to_string()),
+                kind => panic!("unexpected warning {kind}"),
+            })
+            .collect();
+        assert_eq!(
+            skipped,
+            [
+                (1, "Expected a number, found `2.0.0`".to_string()),
+                (2, "Face vertex `1/2` has no normal index".to_string()),
+                (3, "No material named `blue`".to_string()),
+                (4, "`nan` isn't a finite number".to_string()),
+            ]
+        );
+        // the good lines after them are still read
+        assert_eq!(model.objects[0].meshes[0].1.faces.len(), 2);
+    }
+
+    #[test]
+    fn unsupported_directive_is_reported_once() {
+        let model = parse(&square("s 1\nl 1 2\ns off"), false).unwrap();
+        let unsupported: Vec<_> = model
+            .warnings
+            .iter()
+            .map(|warning| match &warning.kind {
+                OBJWarningKind::UnsupportedDirective(directive) => {
+                    (warning.location.clone(), directive.as_str())
+                }
+                kind => panic!("unexpected warning {kind}"),
+            })
+            .collect();
+        assert_eq!(unsupported, [(location(1, 1), "s"), (location(2, 1), "l")]);
+    }
+
+    /// The lines in `body` with each bare `f` line made into a triangle face
+    fn faces(body: &str) -> String {
+        let mut source = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvn 0 0 1\n".to_string();
+        for line in body.lines() {
+            source.push_str(if line == "f" {
+                "f 1//1 2//1 3//1"
             } else {
-                let face = [
-                    parse_vertex(word),
-                    parse_vertex(words.next().unwrap()),
-                    parse_vertex(words.next().unwrap()),
-                ];
-                first_and_last_vert = Some((face[0], face[2]));
-                Some(face)
-            }
-        } else {
-            None
+                line
+            });
+            source.push('\n');
         }
-    };
+        source
+    }
 
-    let mut faces = Vec::new();
-    while let Some(new) = parse_face() {
-        faces.push(new);
+    fn names(model: &OBJModel) -> Vec<(&str, Vec<&str>)> {
+        model
+            .objects
+            .iter()
+            .map(|object| {
+                let groups = object.groups.iter().map(|group| group.name.as_str());
+                (object.name.as_str(), groups.collect())
+            })
+            .collect()
+    }
+
+    fn face_count(meshes: &[(OBJMaterial, OBJMesh)]) -> usize {
+        meshes.iter().map(|(_, mesh)| mesh.faces.len()).sum()
+    }
+
+    #[test]
+    fn faces_before_the_first_object() {
+        let model = parse(&faces("f\no Car\nf"), false).unwrap();
+        assert_eq!(names(&model), [("", vec![]), ("Car", vec![])]);
+    }
+
+    #[test]
+    fn objects_can_be_reentered() {
+        let model = parse(&faces("o A\nf\no B\nf\no A\nf"), false).unwrap();
+        assert_eq!(names(&model), [("A", vec![]), ("B", vec![])]);
+        assert_eq!(face_count(&model.objects[0].meshes), 2);
+        assert_eq!(face_count(&model.objects[1].meshes), 1);
+    }
+
+    #[test]
+    fn unnamed_group_goes_back_to_the_object() {
+        let model = parse(&faces("o Car\ng Door\nf\ng\nf\nf"), false).unwrap();
+        assert_eq!(names(&model), [("Car", vec!["Door"])]);
+        let car = &model.objects[0];
+        assert_eq!(face_count(&car.meshes), 2);
+        assert_eq!(face_count(&car.groups[0].meshes), 1);
     }
-    return faces;
-}
 
-//fn parse_mtllib(file)
+    #[test]
+    fn several_group_names() {
+        let model = parse(&faces("g Door Left\nf\ng Door\nf"), false).unwrap();
+        assert_eq!(names(&model), [("", vec!["Door Left", "Door"])]);
+    }
+
+    #[test]
+    fn empty_groups_and_objects_are_dropped() {
+        let source = faces("o Empty\ng Nothing\no Car\ng Unused\ng Door\nf");
+        let model = parse(&source, false).unwrap();
+        assert_eq!(names(&model), [("Car", vec!["Door"])]);
+    }
+}
@@ -1,50 +1,74 @@
-use std::{
-    collections::HashMap,
-    fs::File,
-    io::{self, BufRead, BufReader},
-    path::PathBuf,
-};
+use std::collections::HashMap;
 
-use crate::OBJMaterial;
+use crate::{
+    OBJError, OBJMaterial, OBJWarning, OBJWarningKind, ParseOptions,
+    parse::{Diagnostics, LineResult, Words, parse_number},
+};
 
-/// reads an mtl file, creating new materials and updating the material table and list accordingly
-pub fn parse_mtl_file(
-    input_filepath: PathBuf,
+/// reads the contents of an mtl file, creating new materials and updating the material table and list accordingly.
+/// `file` is its name, for error locations
+pub fn parse_mtl(
+    file: &str,
+    source: &str,
+    options: ParseOptions,
     mat_list: &mut Vec<OBJMaterial>,
     mat_table: &mut HashMap<String, usize>,
-) -> io::Result<()> {
-    let input_file = File::open(input_filepath)?;
-    let input = BufReader::new(input_file);
-
-    let mut current_material: Option<&mut OBJMaterial> = None; //OBJMaterial::default();
+    warnings: &mut Vec<OBJWarning>,
+) -> Result<(), OBJError> {
+    let mut diagnostics = Diagnostics::new(file, options, warnings);
+    let mut current_material: Option<usize> = None;
 
-    for line in input.lines() {
-        let line = line?;
-        let mut words = line.split_ascii_whitespace().map(String::from);
-
-        let first_word = words.next();
-        if first_word.is_none() {
+    for (line_index, line) in source.lines().enumerate() {
+        let line_number = line_index + 1;
+        let mut words = Words::new(line);
+        let Some((column, directive)) = words.next() else {
             continue;
-        }
-        match first_word.unwrap().as_str() {
-            "newmtl" => {
-                let name = words.next().unwrap();
-                mat_list.push(OBJMaterial::new(name.clone()));
-                mat_table.insert(name, mat_list.len() - 1);
-                current_material = mat_list.last_mut();
-            }
-            "Kd" => {
-                // diffuse color
-                if let Some(current_material) = &mut current_material {
-                    let r = words.next().unwrap().parse::<f32>().unwrap();
-                    let g = words.next().unwrap().parse::<f32>().unwrap();
-                    let b = words.next().unwrap().parse::<f32>().unwrap();
-                    current_material.diffuse_color = [r, g, b];
+        };
+        let result = match directive {
+            "newmtl" => words.expect("a material name").map(|(_, name)| {
+                mat_list.push(OBJMaterial::new(name.to_string()));
+                mat_table.insert(name.to_string(), mat_list.len() - 1);
+                current_material = Some(mat_list.len() - 1);
+            }),
+            "Kd" | "map_Kd" => match current_material {
+                Some(index) => parse_property(&mut mat_list[index], directive, words),
+                None => {
+                    diagnostics.warn(
+                        line_number,
+                        column,
+                        OBJWarningKind::NoMaterial(directive.to_string()),
+                    );
+                    Ok(())
                 }
+            },
+            _ if directive.starts_with('#') => Ok(()),
+            _ => {
+                diagnostics.unsupported(line_number, column, directive);
+                Ok(())
             }
-            _ => {}
+        };
+        if let Err((column, kind)) = result {
+            diagnostics.error(line_number, column, kind)?;
         }
     }
+    Ok(())
+}
 
+fn parse_property(material: &mut OBJMaterial, directive: &str, mut words: Words) -> LineResult<()> {
+    match directive {
+        "Kd" => {
+            // diffuse color, where g and b default to r
+            let r = parse_number(words.expect("a red value")?)?;
+            let g = words.next().map(parse_number).transpose()?.unwrap_or(r);
+            let b = words.next().map(parse_number).transpose()?.unwrap_or(r);
+            material.diffuse_color = [r, g, b];
+        }
+        "map_Kd" => {
+            // diffuse texture, the file name comes after any options
+            let (_, file) = words.expect_last("a texture file name")?;
+            material.diffuse_texture = Some(file.to_string());
+        }
+        _ => {}
+    }
     Ok(())
 }
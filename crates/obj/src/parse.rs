@@ -0,0 +1,137 @@
+//! Helpers shared by the .obj and .mtl parsers
+
+use std::collections::HashSet;
+
+use ordered_float::NotNan;
+
+use crate::{Location, OBJError, OBJErrorKind, OBJWarning, OBJWarningKind, ParseOptions};
+
+/// What went wrong on a line, and at which column
+pub type LineResult<T> = Result<T, (usize, OBJErrorKind)>;
+
+/// The whitespace separated words of a line, with the column each starts at
+pub struct Words<'a> {
+    rest: &'a str,
+    column: usize,
+}
+impl<'a> Words<'a> {
+    pub fn new(line: &'a str) -> Self {
+        Self {
+            rest: line,
+            column: 1,
+        }
+    }
+
+    /// The next word, or an error pointing past the end of the line if there isn't one
+    pub fn expect(&mut self, what: &'static str) -> LineResult<(usize, &'a str)> {
+        self.next()
+            .ok_or((self.column, OBJErrorKind::Missing(what)))
+    }
+
+    /// The last word, skipping everything before it
+    pub fn expect_last(&mut self, what: &'static str) -> LineResult<(usize, &'a str)> {
+        self.by_ref()
+            .last()
+            .ok_or((self.column, OBJErrorKind::Missing(what)))
+    }
+}
+impl<'a> Iterator for Words<'a> {
+    type Item = (usize, &'a str);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let trimmed = self
+            .rest
+            .trim_start_matches(|c: char| c.is_ascii_whitespace());
+        self.column += self.rest[..self.rest.len() - trimmed.len()].chars().count();
+        let end = trimmed
+            .find(|c: char| c.is_ascii_whitespace())
+            .unwrap_or(trimmed.len());
+        let (word, rest) = trimmed.split_at(end);
+        self.rest = rest;
+        if word.is_empty() {
+            return None;
+        }
+        let column = self.column;
+        self.column += word.chars().count();
+        Some((column, word))
+    }
+}
+
+/// Parses a word as a finite number
+pub fn parse_number((column, token): (usize, &str)) -> LineResult<f32> {
+    let number = token
+        .parse::<f32>()
+        .map_err(|_| (column, OBJErrorKind::InvalidNumber(token.to_string())))?;
+    if !number.is_finite() {
+        return Err((column, OBJErrorKind::NonFiniteNumber(token.to_string())));
+    }
+    Ok(number)
+}
+
+/// A number that has already been checked to not be NaN
+pub fn not_nan(number: f32) -> NotNan<f32> {
+    NotNan::new(number).expect("number was checked while parsing")
+}
+
+/// Collects the warnings of a file, and decides what happens to its errors
+pub struct Diagnostics<'a> {
+    file: &'a str,
+    options: ParseOptions,
+    pub warnings: &'a mut Vec<OBJWarning>,
+    /// Unsupported directives already warned about
+    reported: HashSet<String>,
+}
+impl<'a> Diagnostics<'a> {
+    pub fn new(file: &'a str, options: ParseOptions, warnings: &'a mut Vec<OBJWarning>) -> Self {
+        Self {
+            file,
+            options,
+            warnings,
+            reported: HashSet::new(),
+        }
+    }
+
+    pub fn location(&self, line: usize, column: usize) -> Location {
+        Location {
+            file: self.file.to_string(),
+            line,
+            column,
+        }
+    }
+
+    pub fn warn(&mut self, line: usize, column: usize, kind: OBJWarningKind) {
+        self.warnings.push(OBJWarning {
+            location: self.location(line, column),
+            kind,
+        });
+    }
+
+    /// Warns about a directive the first time it's used in the file
+    pub fn unsupported(&mut self, line: usize, column: usize, directive: &str) {
+        if self.reported.insert(directive.to_string()) {
+            self.warn(
+                line,
+                column,
+                OBJWarningKind::UnsupportedDirective(directive.to_string()),
+            );
+        }
+    }
+
+    /// Fails with the error, or in lenient mode, skips the line it's on with a warning
+    pub fn error(
+        &mut self,
+        line: usize,
+        column: usize,
+        kind: OBJErrorKind,
+    ) -> Result<(), OBJError> {
+        if self.options.lenient {
+            self.warn(line, column, OBJWarningKind::SkippedLine(kind));
+            Ok(())
+        } else {
+            Err(OBJError {
+                location: self.location(line, column),
+                kind,
+            })
+        }
+    }
+}
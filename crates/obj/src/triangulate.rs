@@ -0,0 +1,387 @@
+//! Splitting polygon faces into triangles
+
+/// Relative tolerance below which corners count as being in a straight line, and polygons as having no area
+const EPSILON: f32 = 1e-6;
+
+/// Splits a polygon into triangles by ear clipping, returning them as indices into `points`, wound the same way as
+/// the polygon
+///
+/// The polygon is projected onto its best-fit plane first, so concave and slightly non-planar polygons work. Repeated
+/// points and corners that double back are left out, and no triangles are made without any area. A polygon with no
+/// area at all gives no triangles
+pub fn triangulate(points: &[[f32; 3]]) -> Vec<[usize; 3]> {
+    if points.len() < 3 {
+        return Vec::new();
+    }
+    let Some((u, v)) = plane_basis(points) else {
+        return Vec::new();
+    };
+    if points.len() == 3 {
+        return vec![[0, 1, 2]];
+    }
+    // relative to the first point, so large coordinates don't lose precision
+    let origin = points[0];
+    let projected: Vec<[f32; 2]> = points
+        .iter()
+        .map(|p| {
+            let p = sub(*p, origin);
+            [dot(p, u), dot(p, v)]
+        })
+        .collect();
+    ear_clip(&projected)
+}
+
+/// Two perpendicular axes in the polygon's best-fit plane, oriented so the polygon winds counterclockwise in them.
+/// `None` if the polygon has no area
+fn plane_basis(points: &[[f32; 3]]) -> Option<([f32; 3], [f32; 3])> {
+    // Newell's method, which sums up the polygon's area in each axis plane, so it suits any polygon
+    let mut normal = [0.0; 3];
+    let mut scale: f32 = 0.0;
+    for (i, a) in points.iter().enumerate() {
+        let b = points[(i + 1) % points.len()];
+        normal[0] += (a[1] - b[1]) * (a[2] + b[2]);
+        normal[1] += (a[2] - b[2]) * (a[0] + b[0]);
+        normal[2] += (a[0] - b[0]) * (a[1] + b[1]);
+        let edge = sub(b, *a);
+        scale = scale.max(dot(edge, edge));
+    }
+    let length = dot(normal, normal).sqrt();
+    if length <= EPSILON * scale {
+        return None;
+    }
+    let normal = normal.map(|n| n / length);
+
+    // any axis that isn't close to the normal
+    let axis = if normal[0].abs() < 0.9 {
+        [1.0, 0.0, 0.0]
+    } else {
+        [0.0, 1.0, 0.0]
+    };
+    let u = normalize(cross(axis, normal));
+    let v = cross(normal, u);
+    Some((u, v))
+}
+
+/// Triangulates a counterclockwise polygon
+fn ear_clip(points: &[[f32; 2]]) -> Vec<[usize; 3]> {
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    let mut triangles = Vec::with_capacity(points.len() - 2);
+    remove_spikes(points, &mut remaining);
+    while remaining.len() > 3 {
+        let corner = (0..remaining.len())
+            .find(|i| is_ear(points, &remaining, *i))
+            // a polygon that crosses itself may not have any ears, so the sharpest corner is clipped instead
+            .unwrap_or_else(|| most_convex_corner(points, &remaining));
+        let triangle = corner_triangle(&remaining, corner);
+        // a corner that doesn't turn can only be left here by the fallback, and has no area to cover
+        if turn(points, triangle) > EPSILON {
+            triangles.push(triangle);
+        }
+        remaining.remove(corner);
+        remove_spikes(points, &mut remaining);
+    }
+    if remaining.len() == 3 && turn(points, corner_triangle(&remaining, 1)) > EPSILON {
+        triangles.push([remaining[0], remaining[1], remaining[2]]);
+    }
+    triangles
+}
+
+/// The corner and its neighbors in the remaining polygon
+fn corner_triangle(remaining: &[usize], corner: usize) -> [usize; 3] {
+    let n = remaining.len();
+    [
+        remaining[(corner + n - 1) % n],
+        remaining[corner],
+        remaining[(corner + 1) % n],
+    ]
+}
+
+/// How sharply the polygon turns left at a corner, as the sine of the angle. Negative for right turns
+fn turn(points: &[[f32; 2]], [a, b, c]: [usize; 3]) -> f32 {
+    let ab = sub2(points[b], points[a]);
+    let bc = sub2(points[c], points[b]);
+    let lengths = (dot2(ab, ab) * dot2(bc, bc)).sqrt();
+    if lengths == 0.0 {
+        return 0.0;
+    }
+    cross2(ab, bc) / lengths
+}
+
+/// Takes out repeated points, and corners where the polygon turns straight back on itself, which can never be ears.
+/// Corners partway along a straight edge are kept, since the faces next to this one may use them
+fn remove_spikes(points: &[[f32; 2]], remaining: &mut Vec<usize>) {
+    let mut i = 0;
+    let mut since_removal = 0;
+    // goes round until a whole lap finds nothing to remove
+    while remaining.len() >= 3 && since_removal < remaining.len() {
+        let corner = i % remaining.len();
+        if is_spike(points, corner_triangle(remaining, corner)) {
+            remaining.remove(corner);
+            since_removal = 0;
+        } else {
+            i = corner + 1;
+            since_removal += 1;
+        }
+    }
+}
+
+fn is_spike(points: &[[f32; 2]], [a, b, c]: [usize; 3]) -> bool {
+    let ab = sub2(points[b], points[a]);
+    let bc = sub2(points[c], points[b]);
+    if ab == [0.0; 2] || bc == [0.0; 2] {
+        return true;
+    }
+    turn(points, [a, b, c]).abs() <= EPSILON && dot2(ab, bc) < 0.0
+}
+
+/// Whether a corner can be cut off, which is when it turns left and no other corner is in the triangle it makes
+fn is_ear(points: &[[f32; 2]], remaining: &[usize], corner: usize) -> bool {
+    let triangle = corner_triangle(remaining, corner);
+    if turn(points, triangle) <= EPSILON {
+        return false;
+    }
+    let [a, b, c] = triangle.map(|i| points[i]);
+    !remaining.iter().any(|i| {
+        let p = points[*i];
+        // a polygon can pass through the same point twice
+        let is_corner = p == a || p == b || p == c;
+        !is_corner && in_triangle(p, a, b, c)
+    })
+}
+
+fn most_convex_corner(points: &[[f32; 2]], remaining: &[usize]) -> usize {
+    (0..remaining.len())
+        .max_by(|i, j| {
+            let turn_i = turn(points, corner_triangle(remaining, *i));
+            let turn_j = turn(points, corner_triangle(remaining, *j));
+            turn_i.total_cmp(&turn_j)
+        })
+        .unwrap_or(0)
+}
+
+/// Whether a point is inside or on the edge of a counterclockwise triangle
+fn in_triangle(p: [f32; 2], a: [f32; 2], b: [f32; 2], c: [f32; 2]) -> bool {
+    cross2(sub2(b, a), sub2(p, a)) >= 0.0
+        && cross2(sub2(c, b), sub2(p, b)) >= 0.0
+        && cross2(sub2(a, c), sub2(p, c)) >= 0.0
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn normalize(a: [f32; 3]) -> [f32; 3] {
+    let length = dot(a, a).sqrt();
+    a.map(|x| x / length)
+}
+
+fn sub2(a: [f32; 2], b: [f32; 2]) -> [f32; 2] {
+    [a[0] - b[0], a[1] - b[1]]
+}
+
+fn dot2(a: [f32; 2], b: [f32; 2]) -> f32 {
+    a[0] * b[0] + a[1] * b[1]
+}
+
+fn cross2(a: [f32; 2], b: [f32; 2]) -> f32 {
+    a[0] * b[1] - a[1] * b[0]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Twice the area of each triangle along `normal`, negative if it's wound the wrong way
+    fn signed_areas(points: &[[f32; 3]], triangles: &[[usize; 3]], normal: [f32; 3]) -> Vec<f32> {
+        triangles
+            .iter()
+            .map(|[a, b, c]| {
+                let ab = sub(points[*b], points[*a]);
+                let ac = sub(points[*c], points[*a]);
+                dot(cross(ab, ac), normal)
+            })
+            .collect()
+    }
+
+    /// Checks the triangles are all wound like the polygon and cover exactly its area
+    fn assert_covers(points: &[[f32; 3]], triangles: &[[usize; 3]], normal: [f32; 3], area: f32) {
+        let areas = signed_areas(points, triangles, normal);
+        assert!(
+            areas.iter().all(|a| *a > 0.0),
+            "triangles {triangles:?} have areas {areas:?}"
+        );
+        let total = areas.iter().sum::<f32>() / 2.0;
+        assert!(
+            (total - area).abs() < 1e-4,
+            "triangles {triangles:?} cover {total}, not {area}"
+        );
+    }
+
+    fn flat(points: &[[f32; 2]]) -> Vec<[f32; 3]> {
+        points.iter().map(|[x, y]| [*x, *y, 0.0]).collect()
+    }
+
+    const UP: [f32; 3] = [0.0, 0.0, 1.0];
+
+    #[test]
+    fn triangle_is_unchanged() {
+        let points = flat(&[[0.0, 0.0], [1.0, 0.0], [0.0, 1.0]]);
+        assert_eq!(triangulate(&points), vec![[0, 1, 2]]);
+    }
+
+    #[test]
+    fn square() {
+        let points = flat(&[[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]]);
+        let triangles = triangulate(&points);
+        assert_eq!(triangles.len(), 2);
+        assert_covers(&points, &triangles, UP, 1.0);
+    }
+
+    #[test]
+    fn clockwise_quad_keeps_its_winding() {
+        let points = flat(&[[0.0, 0.0], [0.0, 1.0], [2.0, 1.0], [2.0, 0.0]]);
+        let triangles = triangulate(&points);
+        assert_eq!(triangles.len(), 2);
+        assert_covers(&points, &triangles, [0.0, 0.0, -1.0], 2.0);
+    }
+
+    #[test]
+    fn concave_quad() {
+        // an arrowhead, which fanning from the first corner gets wrong
+        let points = flat(&[[0.0, -1.0], [3.0, 0.0], [0.0, 1.0], [1.0, 0.0]]);
+        let triangles = triangulate(&points);
+        assert_eq!(triangles.len(), 2);
+        assert_covers(&points, &triangles, UP, 2.0);
+    }
+
+    #[test]
+    fn concave_l_shape() {
+        // starting at the corner next to the notch, so a fan would cover the notch
+        let points = flat(&[
+            [2.0, 1.0],
+            [1.0, 1.0],
+            [1.0, 2.0],
+            [0.0, 2.0],
+            [0.0, 0.0],
+            [2.0, 0.0],
+        ]);
+        let triangles = triangulate(&points);
+        assert_eq!(triangles.len(), 4);
+        assert_covers(&points, &triangles, UP, 3.0);
+    }
+
+    #[test]
+    fn tilted_concave_l_shape() {
+        // the same L, stood up on a slant and moved far from the origin
+        let l = [
+            [2.0, 1.0],
+            [1.0, 1.0],
+            [1.0, 2.0],
+            [0.0, 2.0],
+            [0.0, 0.0],
+            [2.0, 0.0],
+        ];
+        let (s, c) = 0.5f32.sin_cos();
+        let points: Vec<[f32; 3]> = l
+            .iter()
+            .map(|[x, y]| [100.0 + x, 50.0 + y * c, -200.0 + y * s])
+            .collect();
+        let normal = [0.0, -s, c];
+        let triangles = triangulate(&points);
+        assert_eq!(triangles.len(), 4);
+        assert_covers(&points, &triangles, normal, 3.0);
+    }
+
+    #[test]
+    fn straight_corners_are_kept() {
+        // a square with extra points partway along two sides
+        let points = flat(&[
+            [0.0, 0.0],
+            [0.5, 0.0],
+            [1.0, 0.0],
+            [1.0, 1.0],
+            [0.0, 1.0],
+            [0.0, 0.5],
+        ]);
+        let triangles = triangulate(&points);
+        assert_eq!(triangles.len(), 4);
+        assert_covers(&points, &triangles, UP, 1.0);
+        // the faces next to this one would have cracks if the points along the sides weren't used
+        assert!((0..points.len()).all(|i| triangles.iter().flatten().any(|j| *j == i)));
+    }
+
+    #[test]
+    fn nearly_straight_corners() {
+        for offset in [1e-7, -1e-7, 1e-4, -1e-4] {
+            let points = flat(&[
+                [0.0, 0.0],
+                [0.5, offset],
+                [1.0, 0.0],
+                [1.0, 1.0],
+                [0.0, 1.0],
+            ]);
+            let triangles = triangulate(&points);
+            assert_covers(&points, &triangles, UP, 1.0 - offset / 2.0);
+        }
+    }
+
+    #[test]
+    fn repeated_points() {
+        let points = flat(&[
+            [0.0, 0.0],
+            [1.0, 0.0],
+            [1.0, 0.0],
+            [1.0, 1.0],
+            [0.0, 1.0],
+            [0.0, 0.0],
+        ]);
+        let triangles = triangulate(&points);
+        assert_eq!(triangles.len(), 2);
+        assert_covers(&points, &triangles, UP, 1.0);
+    }
+
+    #[test]
+    fn degenerate_polygons_give_nothing() {
+        assert!(triangulate(&[]).is_empty());
+        assert!(triangulate(&flat(&[[0.0, 0.0], [1.0, 0.0]])).is_empty());
+        // all in a line
+        assert!(triangulate(&flat(&[[0.0, 0.0], [1.0, 0.0], [2.0, 0.0]])).is_empty());
+        assert!(triangulate(&flat(&[[0.0, 0.0], [1.0, 0.0], [2.0, 0.0], [3.0, 0.0]])).is_empty());
+        // all the same point
+        assert!(triangulate(&[[1.0, 2.0, 3.0]; 4]).is_empty());
+    }
+
+    #[test]
+    fn spikes_are_left_out() {
+        // a square with a line sticking out of one side
+        let points = flat(&[
+            [0.0, 0.0],
+            [1.0, 0.0],
+            [2.0, 0.0],
+            [1.0, 0.0],
+            [1.0, 1.0],
+            [0.0, 1.0],
+        ]);
+        let triangles = triangulate(&points);
+        assert_covers(&points, &triangles, UP, 1.0);
+    }
+
+    #[test]
+    fn self_intersecting_polygon_still_terminates() {
+        // a bowtie
+        let points = flat(&[[0.0, 0.0], [1.0, 1.0], [1.0, 0.0], [0.0, 1.0]]);
+        assert!(triangulate(&points).len() <= 2);
+    }
+}
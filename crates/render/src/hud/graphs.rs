@@ -0,0 +1,236 @@
+use std::collections::VecDeque;
+
+use nalgebra::Vector2;
+use utils::TelemetrySample;
+
+use super::shapes::HudMesh;
+use super::{DIM_COLOR, MARGIN, PANEL_COLOR, TEXT_COLOR};
+use crate::text::{TextAlign, TextRenderer};
+
+/// How much history the graph shows, in seconds of sim time
+const WINDOW: f32 = 5.0;
+/// Most points plotted per series, older ticks are skipped over when there are more than this in the window
+const MAX_POINTS: usize = 300;
+
+const GRAPH_SIZE: Vector2<f32> = Vector2::new(360.0, 140.0);
+const LABEL_SIZE: f32 = 11.0;
+const LINE_WIDTH: f32 = 1.5;
+
+/// Series colors, per wheel in order front-driver, front-pass, rear-driver, rear-pass
+const SERIES_COLORS: [[f32; 4]; 4] = [
+    [0.15, 0.55, 1.0, 1.0],
+    [1.0, 0.55, 0.1, 1.0],
+    [0.3, 1.0, 0.4, 1.0],
+    [1.0, 0.3, 0.8, 1.0],
+];
+const WHEEL_NAMES: [&str; 4] = ["FL", "FR", "RL", "RR"];
+
+/// A telemetry value that can be plotted
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GraphChannel {
+    Speed,
+    Throttle,
+    Steer,
+    WheelCompression,
+    WheelSlip,
+}
+impl GraphChannel {
+    fn label(&self) -> &'static str {
+        match self {
+            GraphChannel::Speed => "speed (m/s)",
+            GraphChannel::Throttle => "throttle",
+            GraphChannel::Steer => "steer angle (deg)",
+            GraphChannel::WheelCompression => "compression",
+            GraphChannel::WheelSlip => "slip angle (deg)",
+        }
+    }
+
+    /// Number of lines plotted, one per wheel for wheel channels
+    fn n_series(&self) -> usize {
+        match self {
+            GraphChannel::WheelCompression | GraphChannel::WheelSlip => 4,
+            _ => 1,
+        }
+    }
+
+    /// The plotted values of a sample, with only the first `n_series` used
+    fn values(&self, sample: &TelemetrySample) -> [f32; 4] {
+        match self {
+            GraphChannel::Speed => [sample.speed, 0.0, 0.0, 0.0],
+            GraphChannel::Throttle => [sample.throttle, 0.0, 0.0, 0.0],
+            GraphChannel::Steer => [sample.steer.to_degrees(), 0.0, 0.0, 0.0],
+            GraphChannel::WheelCompression => sample.wheel_compression,
+            GraphChannel::WheelSlip => sample.wheel_slip.map(f32::to_degrees),
+        }
+    }
+
+    /// Fixed vertical range of the graph, or none to fit it to the data
+    fn fixed_range(&self) -> Option<(f32, f32)> {
+        match self {
+            GraphChannel::Throttle => Some((-1.0, 1.0)),
+            GraphChannel::WheelCompression => Some((0.0, 1.0)),
+            _ => None,
+        }
+    }
+}
+
+/// Rolling plot of recent sim ticks, for seeing how the car's handling evolves over time
+pub struct TelemetryGraph {
+    samples: VecDeque<TelemetrySample>,
+    /// What is being plotted, or none if the graph is hidden
+    pub channel: Option<GraphChannel>,
+}
+impl TelemetryGraph {
+    pub fn new() -> TelemetryGraph {
+        TelemetryGraph {
+            samples: VecDeque::new(),
+            channel: None,
+        }
+    }
+
+    pub fn push(&mut self, sample: TelemetrySample) {
+        if let Some(last) = self.samples.back() {
+            if last.time == sample.time {
+                // the same tick redrawn, such as while frozen in photo mode
+                return;
+            } else if last.time > sample.time {
+                // sim was restarted
+                self.samples.clear();
+            }
+        }
+        self.samples.push_back(sample);
+        while self
+            .samples
+            .front()
+            .is_some_and(|first| first.time < sample.time - WINDOW)
+        {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Steps through the channels, hiding the graph after the last one
+    pub fn cycle_channel(&mut self) {
+        self.channel = match self.channel {
+            None => Some(GraphChannel::Speed),
+            Some(GraphChannel::Speed) => Some(GraphChannel::Throttle),
+            Some(GraphChannel::Throttle) => Some(GraphChannel::Steer),
+            Some(GraphChannel::Steer) => Some(GraphChannel::WheelCompression),
+            Some(GraphChannel::WheelCompression) => Some(GraphChannel::WheelSlip),
+            Some(GraphChannel::WheelSlip) => None,
+        };
+    }
+
+    /// Graph panel in the top right, with the newest ticks on the right edge
+    pub fn build(&self, mesh: &mut HudMesh, text: &mut TextRenderer, screen: Vector2<f32>) {
+        let (Some(channel), Some(latest)) = (self.channel, self.samples.back()) else {
+            return;
+        };
+
+        let panel_pos = Vector2::new(screen.x - MARGIN - GRAPH_SIZE.x, MARGIN);
+        mesh.rect(panel_pos, GRAPH_SIZE, PANEL_COLOR);
+        let plot_pos = panel_pos + Vector2::new(8.0, LABEL_SIZE + 12.0);
+        let plot_size = GRAPH_SIZE - Vector2::new(16.0, LABEL_SIZE + 20.0);
+
+        let stride = self.samples.len().div_ceil(MAX_POINTS);
+        let points: Vec<(f32, [f32; 4])> = self
+            .samples
+            .iter()
+            .rev()
+            .step_by(stride)
+            .map(|sample| (sample.time, channel.values(sample)))
+            .collect();
+        let n_series = channel.n_series();
+
+        let (min, max) = channel.fixed_range().unwrap_or_else(|| {
+            // fitting the data, keeping zero in view
+            let (min, max) = points
+                .iter()
+                .flat_map(|(_, values)| values[..n_series].iter())
+                .fold((0.0f32, 0.0f32), |(min, max), val| {
+                    (min.min(*val), max.max(*val))
+                });
+            if max - min < 1.0 {
+                (min, min + 1.0)
+            } else {
+                (min, max)
+            }
+        });
+        let to_screen = |time: f32, val: f32| {
+            Vector2::new(
+                plot_pos.x + plot_size.x * (1.0 - (latest.time - time) / WINDOW),
+                plot_pos.y + plot_size.y * (1.0 - (val - min) / (max - min)),
+            )
+        };
+
+        // axes
+        if min < 0.0 && max > 0.0 {
+            mesh.line(
+                to_screen(latest.time - WINDOW, 0.0),
+                to_screen(latest.time, 0.0),
+                1.0,
+                DIM_COLOR,
+            );
+        }
+        for second in 1..WINDOW as u32 {
+            let x = to_screen(latest.time - second as f32, 0.0).x;
+            mesh.line(
+                Vector2::new(x, plot_pos.y),
+                Vector2::new(x, plot_pos.y + plot_size.y),
+                1.0,
+                [1.0, 1.0, 1.0, 0.1],
+            );
+        }
+
+        for (series, col) in SERIES_COLORS.iter().enumerate().take(n_series) {
+            for pair in points.windows(2) {
+                mesh.line(
+                    to_screen(pair[0].0, pair[0].1[series]),
+                    to_screen(pair[1].0, pair[1].1[series]),
+                    LINE_WIDTH,
+                    *col,
+                );
+            }
+        }
+
+        // labels
+        text.queue_text(
+            channel.label(),
+            panel_pos + Vector2::new(8.0, 6.0),
+            LABEL_SIZE,
+            TextAlign::Left,
+            TEXT_COLOR,
+        );
+        // current values, right to left
+        let values = channel.values(latest);
+        let mut x = panel_pos.x + GRAPH_SIZE.x - 8.0;
+        for series in (0..n_series).rev() {
+            let value_text = if n_series == 1 {
+                format!("{:.2}", values[series])
+            } else {
+                format!("{} {:.2}", WHEEL_NAMES[series], values[series])
+            };
+            text.queue_text(
+                &value_text,
+                Vector2::new(x, panel_pos.y + 6.0),
+                LABEL_SIZE,
+                TextAlign::Right,
+                SERIES_COLORS[series],
+            );
+            x -= text.measure_text(&value_text, LABEL_SIZE).x + 8.0;
+        }
+        text.queue_text(
+            &format!("{max:.2}"),
+            plot_pos + Vector2::new(2.0, 2.0),
+            LABEL_SIZE * 0.8,
+            TextAlign::Left,
+            DIM_COLOR,
+        );
+        text.queue_text(
+            &format!("{min:.2}"),
+            plot_pos + Vector2::new(2.0, plot_size.y - LABEL_SIZE),
+            LABEL_SIZE * 0.8,
+            TextAlign::Left,
+            DIM_COLOR,
+        );
+    }
+}
@@ -0,0 +1,605 @@
+#[cfg(debug_assertions)]
+mod graphs;
+mod shapes;
+
+use std::f32::consts::PI;
+
+use nalgebra::Vector2;
+use utils::{Gear, HudInfo, MenuOverlay, SpeedUnit, TouchElementKind, TouchOverlay};
+use wgpu::{
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingType, Buffer, BufferBindingType, BufferDescriptor, BufferUsages,
+    Queue, RenderPass, RenderPipeline, ShaderStages,
+};
+
+use crate::text::{TextAlign, TextRenderer};
+#[cfg(debug_assertions)]
+use graphs::TelemetryGraph;
+use shapes::{HudMesh, HudVertex, seven_segment_width};
+
+/// Most vertices the HUD can draw in a frame
+const MAX_VERTS: u64 = 16384;
+
+/// Screen height, in logical pixels, under which the HUD is shrunk to fit
+const MIN_LOGICAL_HEIGHT: f32 = 640.0;
+/// Gap between HUD elements and the edges of the screen, in logical pixels
+const MARGIN: f32 = 16.0;
+
+const DIAL_RADIUS: f32 = 90.0;
+/// Speed at the end of the speedometer dial, and past which speeds are marked red, in km/h
+const DIAL_RANGE_KMH: (f32, f32) = (160.0, 140.0);
+/// The same in mph
+const DIAL_RANGE_MPH: (f32, f32) = (100.0, 90.0);
+/// Angle of zero speed on the dial, counterclockwise from the right
+const DIAL_START_ANGLE: f32 = 1.25 * PI;
+/// Angle of max speed on the dial
+const DIAL_END_ANGLE: f32 = -0.25 * PI;
+
+const THROTTLE_BAR_WIDTH: f32 = 10.0;
+
+const MENU_MIN_WIDTH: f32 = 280.0;
+const MENU_PADDING: f32 = 24.0;
+const MENU_TITLE_SIZE: f32 = 32.0;
+const MENU_LINE_SIZE: f32 = 14.0;
+const MENU_ITEM_SIZE: f32 = 20.0;
+
+#[cfg(debug_assertions)]
+const DEBUG_LABEL_SIZE: f32 = 12.0;
+
+const PANEL_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.45];
+const TOUCH_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.3];
+const TEXT_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.95];
+const DIM_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.35];
+const ACCENT_COLOR: [f32; 4] = [0.15, 0.55, 1.0, 0.9];
+const RED_COLOR: [f32; 4] = [1.0, 0.2, 0.15, 0.9];
+const BEST_LAP_COLOR: [f32; 4] = [0.3, 1.0, 0.4, 0.95];
+
+/// Screen-space overlay with the speedometer, gear and throttle indicators, and lap timer
+///
+/// The HUD is rebuilt on the CPU every frame and drawn in a single draw call (plus one for its labels), laid out in
+/// logical pixels so it keeps the same size across device pixel ratios
+pub struct Hud {
+    pub text: TextRenderer,
+    pub speed_unit: SpeedUnit,
+    /// On-screen touch controls, drawn over the rest of the HUD when set
+    pub touch_overlay: Option<TouchOverlay>,
+    /// Menu drawn over everything else when set, on platforms without a page to show it on
+    pub menu_overlay: Option<MenuOverlay>,
+    /// Live telemetry plot, only available in debug builds
+    #[cfg(debug_assertions)]
+    pub graph: TelemetryGraph,
+    /// Debug labels for this frame, positioned in physical pixels
+    #[cfg(debug_assertions)]
+    debug_labels: Vec<(String, Vector2<f32>, [f32; 4])>,
+    render_pipeline: RenderPipeline,
+    screen_buffer: Buffer,
+    bind_group: BindGroup,
+    vert_buffer: Buffer,
+    n_verts: u32,
+}
+impl Hud {
+    pub fn new(device: &wgpu::Device, queue: &Queue, config: &wgpu::SurfaceConfiguration) -> Hud {
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("hud bind group layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::VERTEX,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let screen_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("hud screen buffer"),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            size: size_of::<[f32; 4]>() as u64,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("hud bind group"),
+            layout: &bind_group_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: screen_buffer.as_entire_binding(),
+            }],
+        });
+        let vert_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("hud vert buffer"),
+            size: HudVertex::SIZE * MAX_VERTS,
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let render_pipeline = {
+            let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("hud shader"),
+                source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/hud.wgsl").into()),
+            });
+            let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("hud pipeline layout"),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[],
+            });
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("hud render pipeline"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: None,
+                    buffers: &[HudVertex::BUFFER_LAYOUT],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: None,
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: config.format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: None,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    unclipped_depth: false,
+                    conservative: false,
+                },
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState {
+                    count: 1,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview: None,
+                cache: None,
+            })
+        };
+
+        Hud {
+            text: TextRenderer::new(device, queue, config),
+            speed_unit: SpeedUnit::Kmh,
+            touch_overlay: None,
+            menu_overlay: None,
+            #[cfg(debug_assertions)]
+            graph: TelemetryGraph::new(),
+            #[cfg(debug_assertions)]
+            debug_labels: Vec::new(),
+            render_pipeline,
+            screen_buffer,
+            bind_group,
+            vert_buffer,
+            n_verts: 0,
+        }
+    }
+
+    pub fn prepare(
+        &mut self,
+        queue: &Queue,
+        info: &HudInfo,
+        screen_width: u32,
+        screen_height: u32,
+        scale_factor: f32,
+    ) {
+        // shrinking the hud on short screens
+        let logical_height = screen_height as f32 / scale_factor;
+        let ui_scale = scale_factor * (logical_height / MIN_LOGICAL_HEIGHT).min(1.0);
+        let screen = Vector2::new(screen_width as f32, screen_height as f32) / ui_scale;
+        queue.write_buffer(
+            &self.screen_buffer,
+            0,
+            bytemuck::cast_slice(&[screen_width as f32, screen_height as f32, ui_scale, 0.0]),
+        );
+
+        let mut mesh = HudMesh::new();
+        Self::build_speedometer(&mut mesh, &mut self.text, info, self.speed_unit, screen);
+        Self::build_lap_timer(&mut mesh, &mut self.text, info, screen);
+        if let Some(overlay) = &self.touch_overlay {
+            Self::build_touch_controls(&mut mesh, &mut self.text, overlay, ui_scale);
+        }
+        if let Some(menu) = &self.menu_overlay {
+            Self::build_menu(&mut mesh, &mut self.text, menu, screen);
+        }
+        #[cfg(debug_assertions)]
+        {
+            self.graph.build(&mut mesh, &mut self.text, screen);
+            for (label, pos, col) in self.debug_labels.drain(..) {
+                self.text.queue_text(
+                    &label,
+                    pos / ui_scale,
+                    DEBUG_LABEL_SIZE,
+                    TextAlign::Center,
+                    col,
+                );
+            }
+        }
+
+        // whole triangles only
+        mesh.verts.truncate(MAX_VERTS as usize / 3 * 3);
+        queue.write_buffer(&self.vert_buffer, 0, bytemuck::cast_slice(&mesh.verts));
+        self.n_verts = mesh.verts.len() as u32;
+        self.text
+            .prepare(queue, screen_width, screen_height, ui_scale);
+    }
+
+    /// Queues a label for the next frame, centered on a point in physical pixels
+    #[cfg(debug_assertions)]
+    pub fn queue_debug_label(&mut self, label: &str, pos: Vector2<f32>, col: [f32; 3]) {
+        self.debug_labels
+            .push((label.to_string(), pos, [col[0], col[1], col[2], 1.0]));
+    }
+
+    pub fn render(&self, render_pass: &mut RenderPass) {
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vert_buffer.slice(..));
+        render_pass.draw(0..self.n_verts, 0..1);
+        self.text.render(render_pass);
+    }
+
+    /// Touch controls, which are laid out in physical pixels rather than HUD units
+    fn build_touch_controls(
+        mesh: &mut HudMesh,
+        text: &mut TextRenderer,
+        overlay: &TouchOverlay,
+        ui_scale: f32,
+    ) {
+        for element in overlay.elements.iter() {
+            let pos = element.pos / ui_scale;
+            let size = element.size / ui_scale;
+            let held = match element.kind {
+                TouchElementKind::Slider(value) => {
+                    // track with a knob
+                    let track_height = size.y * 0.15;
+                    mesh.rect(pos, size, TOUCH_COLOR);
+                    mesh.rect(
+                        Vector2::new(pos.x + size.y * 0.5, pos.y + (size.y - track_height) * 0.5),
+                        Vector2::new(size.x - size.y, track_height),
+                        DIM_COLOR,
+                    );
+                    let knob_x = pos.x + size.x * 0.5 + value * (size.x - size.y) * 0.5;
+                    mesh.disc(
+                        Vector2::new(knob_x, pos.y + size.y * 0.5),
+                        size.y * 0.4,
+                        if value != 0.0 {
+                            ACCENT_COLOR
+                        } else {
+                            TEXT_COLOR
+                        },
+                    );
+                    false
+                }
+                TouchElementKind::Pedal(value) => {
+                    mesh.rect(pos, size, TOUCH_COLOR);
+                    let fill = size.y * value;
+                    mesh.rect(
+                        Vector2::new(pos.x, pos.y + size.y - fill),
+                        Vector2::new(size.x, fill),
+                        ACCENT_COLOR,
+                    );
+                    value > 0.0
+                }
+                TouchElementKind::Button(pressed) => {
+                    mesh.rect(pos, size, if pressed { ACCENT_COLOR } else { TOUCH_COLOR });
+                    pressed
+                }
+            };
+            let label_size = (size.x.min(size.y) * 0.22).clamp(9.0, 14.0);
+            let label_pos = match element.kind {
+                // under the knob
+                TouchElementKind::Slider(_) => {
+                    Vector2::new(pos.x + size.x * 0.5, pos.y + size.y + 4.0)
+                }
+                _ => Vector2::new(
+                    pos.x + size.x * 0.5,
+                    pos.y + size.y * 0.5 - label_size * 0.6,
+                ),
+            };
+            text.queue_text(
+                element.label,
+                label_pos,
+                label_size,
+                TextAlign::Center,
+                if held { TEXT_COLOR } else { DIM_COLOR },
+            );
+        }
+    }
+
+    /// Menu in the middle of the screen, over a darkened backdrop, with the selected item highlighted
+    fn build_menu(
+        mesh: &mut HudMesh,
+        text: &mut TextRenderer,
+        menu: &MenuOverlay,
+        screen: Vector2<f32>,
+    ) {
+        let title_height = MENU_TITLE_SIZE * 1.5;
+        let line_height = MENU_LINE_SIZE * 1.5;
+        let item_height = MENU_ITEM_SIZE * 1.8;
+        let width = menu
+            .lines
+            .iter()
+            .map(|line| text.measure_text(line, MENU_LINE_SIZE).x)
+            .chain(
+                menu.items
+                    .iter()
+                    .map(|item| text.measure_text(item, MENU_ITEM_SIZE).x),
+            )
+            .fold(text.measure_text(&menu.title, MENU_TITLE_SIZE).x, f32::max)
+            .max(MENU_MIN_WIDTH)
+            + MENU_PADDING * 2.0;
+        let height = MENU_PADDING * 2.0
+            + title_height
+            + line_height * menu.lines.len() as f32
+            + item_height * menu.items.len() as f32;
+        let panel_pos = Vector2::new((screen.x - width) * 0.5, (screen.y - height) * 0.5);
+
+        mesh.rect(Vector2::zeros(), screen, PANEL_COLOR);
+        mesh.rect(panel_pos, Vector2::new(width, height), PANEL_COLOR);
+        let center_x = screen.x * 0.5;
+        let mut y = panel_pos.y + MENU_PADDING;
+        text.queue_text(
+            &menu.title,
+            Vector2::new(center_x, y),
+            MENU_TITLE_SIZE,
+            TextAlign::Center,
+            TEXT_COLOR,
+        );
+        y += title_height;
+        for line in menu.lines.iter() {
+            text.queue_text(
+                line,
+                Vector2::new(center_x, y),
+                MENU_LINE_SIZE,
+                TextAlign::Center,
+                DIM_COLOR,
+            );
+            y += line_height;
+        }
+        for (i, item) in menu.items.iter().enumerate() {
+            if i == menu.selected {
+                mesh.rect(
+                    Vector2::new(panel_pos.x, y),
+                    Vector2::new(width, item_height),
+                    ACCENT_COLOR,
+                );
+            }
+            text.queue_text(
+                item,
+                Vector2::new(center_x, y + item_height * 0.5 - MENU_ITEM_SIZE * 0.6),
+                MENU_ITEM_SIZE,
+                TextAlign::Center,
+                TEXT_COLOR,
+            );
+            y += item_height;
+        }
+    }
+
+    /// Speedometer dial in the bottom left, with the gear and a throttle bar beside it
+    fn build_speedometer(
+        mesh: &mut HudMesh,
+        text: &mut TextRenderer,
+        info: &HudInfo,
+        unit: SpeedUnit,
+        screen: Vector2<f32>,
+    ) {
+        let center = Vector2::new(MARGIN + DIAL_RADIUS, screen.y - MARGIN - DIAL_RADIUS);
+        let speed = unit.from_mps(info.speed.abs());
+        let (dial_max_speed, dial_redline) = match unit {
+            SpeedUnit::Kmh => DIAL_RANGE_KMH,
+            SpeedUnit::Mph => DIAL_RANGE_MPH,
+        };
+        let speed_angle = |speed: f32| {
+            let t = (speed / dial_max_speed).clamp(0.0, 1.0);
+            DIAL_START_ANGLE + (DIAL_END_ANGLE - DIAL_START_ANGLE) * t
+        };
+
+        mesh.disc(center, DIAL_RADIUS, PANEL_COLOR);
+
+        // scale
+        let band_inner = DIAL_RADIUS * 0.82;
+        let band_outer = DIAL_RADIUS * 0.9;
+        mesh.arc(
+            center,
+            band_inner,
+            band_outer,
+            DIAL_START_ANGLE,
+            speed_angle(dial_redline),
+            DIM_COLOR,
+        );
+        mesh.arc(
+            center,
+            band_inner,
+            band_outer,
+            speed_angle(dial_redline),
+            DIAL_END_ANGLE,
+            RED_COLOR,
+        );
+        mesh.arc(
+            center,
+            band_inner,
+            band_outer,
+            DIAL_START_ANGLE,
+            speed_angle(speed),
+            ACCENT_COLOR,
+        );
+        for tick in 0..=(dial_max_speed / 10.0) as u32 {
+            let angle = speed_angle(tick as f32 * 10.0);
+            let dir = Vector2::new(angle.cos(), -angle.sin());
+            let (length, width) = if tick % 2 == 0 {
+                (0.14, 2.5)
+            } else {
+                (0.08, 1.5)
+            };
+            mesh.line(
+                center + dir * (band_inner - 2.0),
+                center + dir * (band_inner - 2.0 - DIAL_RADIUS * length),
+                width,
+                TEXT_COLOR,
+            );
+            if tick % 4 == 0 {
+                let label_size = 11.0;
+                text.queue_text(
+                    &(tick * 10).to_string(),
+                    center + dir * (DIAL_RADIUS * 0.55) - Vector2::new(0.0, label_size * 0.6),
+                    label_size,
+                    TextAlign::Center,
+                    DIM_COLOR,
+                );
+            }
+        }
+
+        // digital readout and gear
+        let speed_text = format!("{:.0}", speed);
+        let speed_height = DIAL_RADIUS * 0.3;
+        mesh.seven_segment(
+            &speed_text,
+            Vector2::new(
+                center.x - seven_segment_width(&speed_text, speed_height) * 0.5,
+                center.y + DIAL_RADIUS * 0.3,
+            ),
+            speed_height,
+            TEXT_COLOR,
+        );
+        text.queue_text(
+            unit.label(),
+            Vector2::new(center.x, center.y + DIAL_RADIUS * 0.64),
+            11.0,
+            TextAlign::Center,
+            DIM_COLOR,
+        );
+        let gear_text = match info.gear {
+            Gear::Drive => "d",
+            Gear::Neutral => "n",
+            Gear::Reverse => "r",
+        };
+        let gear_height = DIAL_RADIUS * 0.25;
+        mesh.seven_segment(
+            gear_text,
+            Vector2::new(
+                center.x - seven_segment_width(gear_text, gear_height) * 0.5,
+                center.y - DIAL_RADIUS * 0.45,
+            ),
+            gear_height,
+            if info.gear == Gear::Reverse {
+                RED_COLOR
+            } else {
+                ACCENT_COLOR
+            },
+        );
+
+        // needle
+        let angle = speed_angle(speed);
+        let dir = Vector2::new(angle.cos(), -angle.sin());
+        mesh.line(
+            center - dir * (DIAL_RADIUS * 0.1),
+            center + dir * (DIAL_RADIUS * 0.85),
+            3.0,
+            RED_COLOR,
+        );
+        mesh.disc(center, 5.0, TEXT_COLOR);
+
+        // throttle bar, filling up from the middle when going forwards and down when reversing
+        let bar_pos = Vector2::new(center.x + DIAL_RADIUS + 10.0, center.y - DIAL_RADIUS * 0.8);
+        let bar_height = DIAL_RADIUS * 1.6;
+        mesh.rect(
+            bar_pos,
+            Vector2::new(THROTTLE_BAR_WIDTH, bar_height),
+            PANEL_COLOR,
+        );
+        let mid_y = bar_pos.y + bar_height * 0.5;
+        let fill = info.throttle.clamp(-1.0, 1.0) * bar_height * 0.5;
+        mesh.rect(
+            Vector2::new(bar_pos.x, mid_y - fill.max(0.0)),
+            Vector2::new(THROTTLE_BAR_WIDTH, fill.abs()),
+            if fill >= 0.0 { ACCENT_COLOR } else { RED_COLOR },
+        );
+        mesh.rect(
+            Vector2::new(bar_pos.x - 2.0, mid_y - 1.0),
+            Vector2::new(THROTTLE_BAR_WIDTH + 4.0, 2.0),
+            TEXT_COLOR,
+        );
+    }
+
+    /// Current lap time at the top of the screen, with the last and best laps under it
+    fn build_lap_timer(
+        mesh: &mut HudMesh,
+        text: &mut TextRenderer,
+        info: &HudInfo,
+        screen: Vector2<f32>,
+    ) {
+        let time_height = 28.0;
+        let small_height = 14.0;
+        let label_size = 10.0;
+        let panel_size = Vector2::new(180.0, time_height + label_size + small_height + 32.0);
+        let panel_pos = Vector2::new((screen.x - panel_size.x) * 0.5, MARGIN);
+        mesh.rect(panel_pos, panel_size, PANEL_COLOR);
+
+        let time_text = format_lap_time(Some(info.lap_time));
+        mesh.seven_segment(
+            &time_text,
+            Vector2::new(
+                (screen.x - seven_segment_width(&time_text, time_height)) * 0.5,
+                panel_pos.y + 8.0,
+            ),
+            time_height,
+            TEXT_COLOR,
+        );
+
+        // last lap on the left, best on the right
+        let label_y = panel_pos.y + time_height + 16.0;
+        let small_y = label_y + label_size + 6.0;
+        text.queue_text(
+            "LAST",
+            Vector2::new(panel_pos.x + 12.0, label_y),
+            label_size,
+            TextAlign::Left,
+            DIM_COLOR,
+        );
+        text.queue_text(
+            "BEST",
+            Vector2::new(panel_pos.x + panel_size.x - 12.0, label_y),
+            label_size,
+            TextAlign::Right,
+            DIM_COLOR,
+        );
+        let last_text = format_lap_time(info.last_lap);
+        mesh.seven_segment(
+            &last_text,
+            Vector2::new(panel_pos.x + 12.0, small_y),
+            small_height,
+            DIM_COLOR,
+        );
+        let best_text = format_lap_time(info.best_lap);
+        mesh.seven_segment(
+            &best_text,
+            Vector2::new(
+                panel_pos.x + panel_size.x - 12.0 - seven_segment_width(&best_text, small_height),
+                small_y,
+            ),
+            small_height,
+            BEST_LAP_COLOR,
+        );
+    }
+}
+
+/// Formats a lap time as `m:ss.cc`, or dashes if there is none
+fn format_lap_time(time: Option<f32>) -> String {
+    match time {
+        Some(time) => {
+            let centis = (time * 100.0) as u32;
+            format!(
+                "{}:{:02}.{:02}",
+                centis / 6000,
+                (centis / 100) % 60,
+                centis % 100
+            )
+        }
+        None => String::from("-:--.--"),
+    }
+}
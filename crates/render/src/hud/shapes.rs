@@ -0,0 +1,219 @@
+use std::f32::consts::PI;
+
+use nalgebra::Vector2;
+
+/// Vertex of a HUD shape, positioned in logical pixels from the top left of the screen
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct HudVertex {
+    pub pos: [f32; 2],
+    pub col: [f32; 4],
+}
+impl HudVertex {
+    pub const SIZE: u64 = size_of::<Self>() as u64;
+    pub const BUFFER_LAYOUT: wgpu::VertexBufferLayout<'static> = wgpu::VertexBufferLayout {
+        array_stride: Self::SIZE,
+        step_mode: wgpu::VertexStepMode::Vertex,
+        attributes: &wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x4],
+    };
+}
+
+/// Number of segments used per full circle of arcs and discs
+const CIRCLE_SEGMENTS: usize = 64;
+
+/// Seven segment display segments, as `(start, end)` in units of digit width and height
+///
+/// Ordered a through g, clockwise from the top, with g in the middle
+const SEGMENTS: [([f32; 2], [f32; 2]); 7] = [
+    ([0.0, 0.0], [1.0, 0.0]),
+    ([1.0, 0.0], [1.0, 0.5]),
+    ([1.0, 0.5], [1.0, 1.0]),
+    ([0.0, 1.0], [1.0, 1.0]),
+    ([0.0, 0.5], [0.0, 1.0]),
+    ([0.0, 0.0], [0.0, 0.5]),
+    ([0.0, 0.5], [1.0, 0.5]),
+];
+/// Width of a seven segment digit, as a fraction of its height
+const DIGIT_WIDTH: f32 = 0.5;
+/// Thickness of a segment, as a fraction of digit height
+const SEGMENT_THICKNESS: f32 = 0.11;
+/// Gap between characters, as a fraction of digit height
+const CHAR_SPACING: f32 = 0.2;
+
+/// Which segments (bit 0 = a ... bit 6 = g) are lit for a character
+fn segment_mask(c: char) -> u8 {
+    match c {
+        '0' => 0b0111111,
+        '1' => 0b0000110,
+        '2' => 0b1011011,
+        '3' => 0b1001111,
+        '4' => 0b1100110,
+        '5' => 0b1101101,
+        '6' => 0b1111101,
+        '7' => 0b0000111,
+        '8' => 0b1111111,
+        '9' => 0b1101111,
+        '-' => 0b1000000,
+        'd' => 0b1011110,
+        'n' => 0b1010100,
+        'r' => 0b1010000,
+        _ => 0,
+    }
+}
+
+/// Builds up the triangles of the HUD each frame
+pub struct HudMesh {
+    pub verts: Vec<HudVertex>,
+}
+impl HudMesh {
+    pub fn new() -> HudMesh {
+        HudMesh { verts: Vec::new() }
+    }
+
+    fn triangle(&mut self, points: [Vector2<f32>; 3], col: [f32; 4]) {
+        for p in points {
+            self.verts.push(HudVertex {
+                pos: [p.x, p.y],
+                col,
+            });
+        }
+    }
+
+    /// Adds a quad, with corners given in order around its edge
+    pub fn quad(&mut self, corners: [Vector2<f32>; 4], col: [f32; 4]) {
+        self.triangle([corners[0], corners[1], corners[2]], col);
+        self.triangle([corners[0], corners[2], corners[3]], col);
+    }
+
+    pub fn rect(&mut self, pos: Vector2<f32>, size: Vector2<f32>, col: [f32; 4]) {
+        self.quad(
+            [
+                pos,
+                pos + Vector2::new(size.x, 0.0),
+                pos + size,
+                pos + Vector2::new(0.0, size.y),
+            ],
+            col,
+        );
+    }
+
+    pub fn line(&mut self, start: Vector2<f32>, end: Vector2<f32>, width: f32, col: [f32; 4]) {
+        let dir = end - start;
+        if dir.magnitude_squared() == 0.0 {
+            return;
+        }
+        let side = Vector2::new(-dir.y, dir.x).normalize() * (width * 0.5);
+        self.quad([start + side, end + side, end - side, start - side], col);
+    }
+
+    /// Adds a ring segment between two angles
+    ///
+    /// Angles are in radians, counterclockwise from the +x axis as seen on screen
+    pub fn arc(
+        &mut self,
+        center: Vector2<f32>,
+        inner_radius: f32,
+        outer_radius: f32,
+        start_angle: f32,
+        end_angle: f32,
+        col: [f32; 4],
+    ) {
+        let sweep = end_angle - start_angle;
+        let n = ((sweep.abs() / (2.0 * PI) * CIRCLE_SEGMENTS as f32).ceil() as usize).max(1);
+        for i in 0..n {
+            let a0 = start_angle + sweep * (i as f32 / n as f32);
+            let a1 = start_angle + sweep * ((i + 1) as f32 / n as f32);
+            self.quad(
+                [
+                    center + screen_dir(a0) * inner_radius,
+                    center + screen_dir(a0) * outer_radius,
+                    center + screen_dir(a1) * outer_radius,
+                    center + screen_dir(a1) * inner_radius,
+                ],
+                col,
+            );
+        }
+    }
+
+    pub fn disc(&mut self, center: Vector2<f32>, radius: f32, col: [f32; 4]) {
+        for i in 0..CIRCLE_SEGMENTS {
+            let a0 = 2.0 * PI * (i as f32 / CIRCLE_SEGMENTS as f32);
+            let a1 = 2.0 * PI * ((i + 1) as f32 / CIRCLE_SEGMENTS as f32);
+            self.triangle(
+                [
+                    center,
+                    center + screen_dir(a0) * radius,
+                    center + screen_dir(a1) * radius,
+                ],
+                col,
+            );
+        }
+    }
+
+    /// Draws a string as a seven segment display, with its top left corner at `pos`
+    ///
+    /// Supports digits, `-`, `:`, `.`, spaces, and the gear letters `d`, `n`, and `r`
+    pub fn seven_segment(&mut self, text: &str, pos: Vector2<f32>, height: f32, col: [f32; 4]) {
+        let width = height * DIGIT_WIDTH;
+        let thickness = height * SEGMENT_THICKNESS;
+        // keeping segments from overlapping at the corners
+        let inset = thickness * 0.6;
+        let mut x = pos.x;
+        for c in text.chars() {
+            match c {
+                ':' => {
+                    for y in [0.3, 0.7] {
+                        let center = Vector2::new(x + thickness * 1.5, pos.y + height * y);
+                        self.rect(
+                            center - Vector2::repeat(thickness * 0.5),
+                            Vector2::repeat(thickness),
+                            col,
+                        );
+                    }
+                }
+                '.' => {
+                    self.rect(
+                        Vector2::new(x + thickness, pos.y + height - thickness),
+                        Vector2::repeat(thickness),
+                        col,
+                    );
+                }
+                _ => {
+                    let mask = segment_mask(c);
+                    let origin = Vector2::new(x, pos.y);
+                    for (i, (start, end)) in SEGMENTS.iter().enumerate() {
+                        if mask & (1 << i) == 0 {
+                            continue;
+                        }
+                        let start = origin + Vector2::new(start[0] * width, start[1] * height);
+                        let end = origin + Vector2::new(end[0] * width, end[1] * height);
+                        let dir = (end - start).normalize();
+                        self.line(start + dir * inset, end - dir * inset, thickness, col);
+                    }
+                }
+            }
+            x += char_advance(c, height);
+        }
+    }
+}
+
+/// How wide a string is when drawn by `HudMesh::seven_segment`
+pub fn seven_segment_width(text: &str, height: f32) -> f32 {
+    let total: f32 = text.chars().map(|c| char_advance(c, height)).sum();
+    // no spacing after the last character
+    (total - height * CHAR_SPACING).max(0.0)
+}
+
+fn char_advance(c: char, height: f32) -> f32 {
+    let width = match c {
+        // dots sit in the middle of a slot three dots wide
+        ':' | '.' => height * SEGMENT_THICKNESS * 3.0,
+        _ => height * DIGIT_WIDTH,
+    };
+    width + height * CHAR_SPACING
+}
+
+/// Unit vector pointing at an angle on screen, where y points down
+fn screen_dir(angle: f32) -> Vector2<f32> {
+    Vector2::new(angle.cos(), -angle.sin())
+}
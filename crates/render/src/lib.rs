@@ -1,13 +1,20 @@
+mod hud;
+mod photo;
 mod scene;
+mod text;
 mod uniforms;
 
 use std::sync::Arc;
 
+use assets::Assets;
 use utils::*;
 use wasm_bindgen::prelude::*;
 use wgpu::RequestAdapterOptions;
 use winit::{event::WindowEvent, window::Window};
 
+use hud::Hud;
+use photo::PhotoRenderer;
+pub use photo::{PhotoSettings, Screenshot};
 use scene::Scene;
 
 /// Main rendering object
@@ -21,18 +28,25 @@ pub struct RenderState {
     depth_texture: DepthTexture,
 
     pub scene: Scene,
+    pub hud: Hud,
+    pub photo: PhotoRenderer,
 
     // needs to be last
     pub window: Arc<Window>,
 }
 
 impl RenderState {
-    pub async fn new(window: Arc<Window>) -> anyhow::Result<Self> {
+    pub async fn new(window: Arc<Window>, assets: &Assets) -> anyhow::Result<Self> {
         let size = window.inner_size();
 
         // choose webgpu if available, else webgl
+        #[cfg(target_arch = "wasm32")]
+        let backends = wgpu::Backends::BROWSER_WEBGPU | wgpu::Backends::GL;
+        // or whatever the platform's native api is
+        #[cfg(not(target_arch = "wasm32"))]
+        let backends = wgpu::Backends::PRIMARY | wgpu::Backends::GL;
         let instance = wgpu::util::new_instance_with_webgpu_detection(&wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::BROWSER_WEBGPU | wgpu::Backends::GL,
+            backends,
             ..Default::default()
         })
         .await;
@@ -54,7 +68,9 @@ impl RenderState {
             .request_device(&wgpu::DeviceDescriptor {
                 label: None,
                 required_features: wgpu::Features::empty(),
-                required_limits: wgpu::Limits::downlevel_webgl2_defaults(),
+                // allowing bigger textures where supported, for high resolution photos
+                required_limits: wgpu::Limits::downlevel_webgl2_defaults()
+                    .using_resolution(adapter.limits()),
                 memory_hints: Default::default(),
                 trace: wgpu::Trace::Off,
             })
@@ -82,7 +98,9 @@ impl RenderState {
 
         let depth_texture = DepthTexture::new(&device, &config);
 
-        let scene = Scene::new(&device, &config);
+        let scene = Scene::new(&device, &queue, &config, assets);
+        let hud = Hud::new(&device, &queue, &config);
+        let photo = PhotoRenderer::new(&device, &config);
 
         Ok(Self {
             surface,
@@ -93,6 +111,8 @@ impl RenderState {
             is_surface_configured: false,
             depth_texture,
             scene,
+            hud,
+            photo,
             window,
         })
     }
@@ -112,17 +132,52 @@ impl RenderState {
         }
     }
 
+    /// Switches between waiting for the display's refresh (no tearing) and presenting frames as soon as they're ready
+    pub fn set_vsync(&mut self, vsync: bool) {
+        self.config.present_mode = if vsync {
+            wgpu::PresentMode::AutoVsync
+        } else {
+            wgpu::PresentMode::AutoNoVsync
+        };
+        if self.is_surface_configured {
+            self.surface.configure(&self.device, &self.config);
+        }
+    }
+
     pub fn handle_window_event(&mut self, _event: &WindowEvent) {}
 
-    pub fn render(&mut self, snapshot: Option<RenderSnapshot>) -> Result<(), wgpu::SurfaceError> {
+    pub fn render(&mut self, snapshot: Option<&RenderSnapshot>) -> Result<(), wgpu::SurfaceError> {
         self.window.request_redraw();
+        self.photo.poll_screenshot(&self.device);
 
-        if !self.is_surface_configured || snapshot.is_none() {
+        let Some(snapshot) = snapshot else {
+            return Ok(());
+        };
+        if !self.is_surface_configured {
             return Ok(());
         }
-        let snapshot = snapshot.unwrap();
 
-        self.scene.prepare(&self.queue, &snapshot);
+        let scale_factor = self.window.scale_factor() as f32;
+        self.scene.prepare(&self.queue, snapshot);
+        #[cfg(debug_assertions)]
+        {
+            self.hud.graph.push(snapshot.telemetry);
+            for label in snapshot.debug_labels.iter() {
+                if let Some(pos) =
+                    self.scene
+                        .project_to_screen(label.pos, self.config.width, self.config.height)
+                {
+                    self.hud.queue_debug_label(&label.text, pos, label.col);
+                }
+            }
+        }
+        self.hud.prepare(
+            &self.queue,
+            &snapshot.hud,
+            self.config.width,
+            self.config.height,
+            scale_factor,
+        );
 
         let output = self.surface.get_current_texture()?;
         let view = output
@@ -152,27 +207,29 @@ impl RenderState {
                     timestamp_writes: None,
                     occlusion_query_set: None,
                 });
-            render_pass.set_pipeline(&self.scene.shadow_mapper.render_pipeline);
-            render_pass.set_bind_group(0, &self.scene.shadow_mapper.bind_group, &[]);
-            self.scene.shadow_map_render(&mut render_pass);
+            if self.scene.shadow_mapper.enabled {
+                render_pass.set_pipeline(&self.scene.shadow_mapper.render_pipeline);
+                render_pass.set_bind_group(0, &self.scene.shadow_mapper.bind_group, &[]);
+                self.scene.shadow_map_render(&mut render_pass);
+            }
         }
 
-        {
-            // 3d render pass
+        if self.scene.minimap.visible {
+            // minimap pass
             let mut render_pass: wgpu::RenderPass =
                 encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                    label: Some("3D render Pass"),
+                    label: Some("minimap render pass"),
                     color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                        view: &view,
+                        view: &self.scene.minimap.texture_view,
                         resolve_target: None,
                         ops: wgpu::Operations {
                             load: wgpu::LoadOp::Clear(wgpu::Color::WHITE),
                             store: wgpu::StoreOp::Store,
                         },
-                        depth_slice: None, // wgpu 26 feature
+                        depth_slice: None,
                     })],
                     depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                        view: &self.depth_texture.view,
+                        view: &self.scene.minimap.depth_texture_view,
                         depth_ops: Some(wgpu::Operations {
                             load: wgpu::LoadOp::Clear(1.0),
                             store: wgpu::StoreOp::Store,
@@ -182,11 +239,61 @@ impl RenderState {
                     timestamp_writes: None,
                     occlusion_query_set: None,
                 });
+            self.scene.minimap_render(&mut render_pass);
+        }
 
-            self.scene.render(&mut render_pass);
+        if let Some((color_view, depth_view)) = self.photo.preview_views(
+            &self.device,
+            &self.queue,
+            self.config.width,
+            self.config.height,
+        ) {
+            // photo mode, post processing the scene and leaving off the hud
+            render_scene_pass(&mut encoder, &self.scene, color_view, depth_view);
+            self.photo.render_preview(&mut encoder, &view);
+            self.photo.capture(
+                &self.device,
+                &self.queue,
+                &mut encoder,
+                self.config.width,
+                self.config.height,
+                |encoder, color_view, depth_view| {
+                    render_scene_pass(encoder, &self.scene, color_view, depth_view)
+                },
+            );
+        } else {
+            render_scene_pass(&mut encoder, &self.scene, &view, &self.depth_texture.view);
+
+            // hud pass, drawn over the 3d scene
+            let mut render_pass: wgpu::RenderPass =
+                encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                    label: Some("HUD render pass"),
+                    color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                        view: &view,
+                        resolve_target: None,
+                        ops: wgpu::Operations {
+                            load: wgpu::LoadOp::Load,
+                            store: wgpu::StoreOp::Store,
+                        },
+                        depth_slice: None,
+                    })],
+                    depth_stencil_attachment: None,
+                    timestamp_writes: None,
+                    occlusion_query_set: None,
+                });
+            if self.scene.minimap.visible {
+                self.scene.minimap.render_composite(
+                    &mut render_pass,
+                    self.config.width,
+                    self.config.height,
+                    scale_factor,
+                );
+            }
+            self.hud.render(&mut render_pass);
         }
 
         self.queue.submit(std::iter::once(encoder.finish()));
+        self.photo.start_readback();
         output.present();
 
         Ok(())
@@ -209,6 +316,39 @@ impl RenderState {
     }
 }
 
+/// Renders the 3d scene into the given color and depth targets
+fn render_scene_pass(
+    encoder: &mut wgpu::CommandEncoder,
+    scene: &Scene,
+    color_view: &wgpu::TextureView,
+    depth_view: &wgpu::TextureView,
+) {
+    let mut render_pass: wgpu::RenderPass =
+        encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("3D render Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: color_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::WHITE),
+                    store: wgpu::StoreOp::Store,
+                },
+                depth_slice: None, // wgpu 26 feature
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth_view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(1.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+    scene.render(&mut render_pass);
+}
+
 struct DepthTexture {
     view: wgpu::TextureView,
 }
@@ -216,9 +356,13 @@ impl DepthTexture {
     const TEXTURE_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
 
     pub fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> DepthTexture {
+        Self::with_size(device, config.width, config.height)
+    }
+
+    pub fn with_size(device: &wgpu::Device, width: u32, height: u32) -> DepthTexture {
         let size = wgpu::Extent3d {
-            width: config.width.max(1),
-            height: config.height.max(1),
+            width: width.max(1),
+            height: height.max(1),
             depth_or_array_layers: 1,
         };
         let descriptor = wgpu::TextureDescriptor {
@@ -0,0 +1,532 @@
+use std::sync::mpsc::{Receiver, TryRecvError, channel};
+
+use wgpu::{
+    BindGroup, BindGroupLayout, BindGroupLayoutEntry, BindingResource, BindingType, Buffer,
+    BufferBindingType, BufferDescriptor, BufferUsages, CommandEncoder, Device, Queue,
+    RenderPipeline, Sampler, ShaderStages, TextureFormat, TextureSampleType, TextureView,
+};
+
+use crate::DepthTexture;
+
+/// Photos are taken at this multiple of the window resolution
+const SCREENSHOT_SCALE: u32 = 2;
+/// Largest width or height of a photo, in pixels
+const MAX_SCREENSHOT_DIM: u32 = 8192;
+/// Blur radii are given for this target height, and scaled to the actual resolution so photos match the preview
+const REFERENCE_HEIGHT: f32 = 1080.0;
+
+/// Adjustments applied to the scene in photo mode
+#[derive(Clone, Copy, Debug)]
+pub struct PhotoSettings {
+    /// Exposure compensation, in stops
+    pub exposure: f32,
+    /// Distance from the camera that is in focus, in meters
+    pub focus_distance: f32,
+    /// Blur radius of the most out of focus parts of the scene, in pixels at 1080p. Zero disables depth of field
+    pub aperture: f32,
+}
+impl PhotoSettings {
+    pub fn new() -> PhotoSettings {
+        PhotoSettings {
+            exposure: 0.0,
+            focus_distance: 8.0,
+            aperture: 0.0,
+        }
+    }
+}
+impl Default for PhotoSettings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A captured photo, encoded as a PNG
+pub struct Screenshot {
+    pub width: u32,
+    pub height: u32,
+    pub png: Vec<u8>,
+}
+impl Screenshot {
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save(&self, path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+        std::fs::write(path, &self.png)
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct PhotoSettingsUniform {
+    exposure_scale: f32,
+    focus_distance: f32,
+    max_blur: f32,
+    _pad: f32,
+}
+
+/// Offscreen textures the scene is rendered into before post processing
+struct PhotoTarget {
+    width: u32,
+    height: u32,
+    color_view: TextureView,
+    depth: DepthTexture,
+    settings_buffer: Buffer,
+    bind_group: BindGroup,
+}
+impl PhotoTarget {
+    fn new(
+        device: &Device,
+        layout: &BindGroupLayout,
+        sampler: &Sampler,
+        format: TextureFormat,
+        width: u32,
+        height: u32,
+    ) -> PhotoTarget {
+        let color_texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("photo color texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let color_view = color_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let depth = DepthTexture::with_size(device, width, height);
+        let settings_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("photo settings buffer"),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            size: size_of::<PhotoSettingsUniform>() as u64,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("photo bind group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&color_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(&depth.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::Sampler(sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: settings_buffer.as_entire_binding(),
+                },
+            ],
+        });
+        PhotoTarget {
+            width,
+            height,
+            color_view,
+            depth,
+            settings_buffer,
+            bind_group,
+        }
+    }
+
+    fn write_settings(&self, queue: &Queue, settings: &PhotoSettings) {
+        let uniform = PhotoSettingsUniform {
+            exposure_scale: settings.exposure.exp2(),
+            focus_distance: settings.focus_distance,
+            max_blur: settings.aperture * self.height as f32 / REFERENCE_HEIGHT,
+            _pad: 0.0,
+        };
+        queue.write_buffer(&self.settings_buffer, 0, bytemuck::cast_slice(&[uniform]));
+    }
+}
+
+/// A photo being copied back from the GPU
+struct PendingScreenshot {
+    buffer: Buffer,
+    width: u32,
+    height: u32,
+    padded_bytes_per_row: u32,
+    /// Receives the result of mapping the buffer, once mapping has been started after the copy is submitted
+    mapped: Option<Receiver<Result<(), wgpu::BufferAsyncError>>>,
+}
+
+/// Renders the scene through photo mode's post processing, and captures high resolution photos
+pub struct PhotoRenderer {
+    /// Photo mode adjustments, or none when not in photo mode
+    pub settings: Option<PhotoSettings>,
+
+    format: TextureFormat,
+    render_pipeline: RenderPipeline,
+    bind_group_layout: BindGroupLayout,
+    sampler: Sampler,
+    /// Window sized target for previewing
+    preview_target: Option<PhotoTarget>,
+    screenshot_requested: bool,
+    pending_screenshot: Option<PendingScreenshot>,
+    finished_screenshot: Option<Screenshot>,
+}
+impl PhotoRenderer {
+    pub fn new(device: &Device, config: &wgpu::SurfaceConfiguration) -> PhotoRenderer {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("photo bind group layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: TextureSampleType::Float { filterable: false },
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("photo sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("photo shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/photo.wgsl").into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("photo render pipeline layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("photo render pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: None,
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: None,
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: config.format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+            cache: None,
+        });
+
+        PhotoRenderer {
+            settings: None,
+            format: config.format,
+            render_pipeline,
+            bind_group_layout,
+            sampler,
+            preview_target: None,
+            screenshot_requested: false,
+            pending_screenshot: None,
+            finished_screenshot: None,
+        }
+    }
+
+    /// Captures a photo on the next frame rendered in photo mode
+    pub fn request_screenshot(&mut self) {
+        self.screenshot_requested = true;
+    }
+
+    /// A photo that has finished being captured, if there is one
+    pub fn take_screenshot(&mut self) -> Option<Screenshot> {
+        self.finished_screenshot.take()
+    }
+
+    /// Color and depth views to render the scene preview into, sized to the window
+    pub(crate) fn preview_views(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        width: u32,
+        height: u32,
+    ) -> Option<(&TextureView, &TextureView)> {
+        let settings = self.settings?;
+        if self
+            .preview_target
+            .as_ref()
+            .is_none_or(|target| target.width != width || target.height != height)
+        {
+            self.preview_target = Some(PhotoTarget::new(
+                device,
+                &self.bind_group_layout,
+                &self.sampler,
+                self.format,
+                width,
+                height,
+            ));
+        }
+        let target = self.preview_target.as_ref().unwrap();
+        target.write_settings(queue, &settings);
+        Some((&target.color_view, &target.depth.view))
+    }
+
+    /// Post processes the preview onto `output`
+    pub(crate) fn render_preview(&self, encoder: &mut CommandEncoder, output: &TextureView) {
+        if let Some(target) = &self.preview_target {
+            self.post_process(encoder, target, output);
+        }
+    }
+
+    fn post_process(
+        &self,
+        encoder: &mut CommandEncoder,
+        target: &PhotoTarget,
+        output: &TextureView,
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("photo render pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: output,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: wgpu::StoreOp::Store,
+                },
+                depth_slice: None,
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(0, &target.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    /// If a photo was requested, renders the scene at high resolution with `render_scene` and starts copying it back
+    pub(crate) fn capture(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        encoder: &mut CommandEncoder,
+        window_width: u32,
+        window_height: u32,
+        render_scene: impl FnOnce(&mut CommandEncoder, &TextureView, &TextureView),
+    ) {
+        let Some(settings) = self.settings else {
+            return;
+        };
+        if !self.screenshot_requested || self.pending_screenshot.is_some() {
+            return;
+        }
+        self.screenshot_requested = false;
+
+        // as big as the device allows, keeping the window's aspect ratio
+        let max_dim = device
+            .limits()
+            .max_texture_dimension_2d
+            .min(MAX_SCREENSHOT_DIM);
+        let scale =
+            (SCREENSHOT_SCALE as f32).min(max_dim as f32 / window_width.max(window_height) as f32);
+        let width = ((window_width as f32 * scale) as u32).max(1);
+        let height = ((window_height as f32 * scale) as u32).max(1);
+        log::info!("Capturing photo ({width}x{height})");
+
+        let target = PhotoTarget::new(
+            device,
+            &self.bind_group_layout,
+            &self.sampler,
+            self.format,
+            width,
+            height,
+        );
+        target.write_settings(queue, &settings);
+        let output_texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("photo output texture"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: self.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let output_view = output_texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        render_scene(encoder, &target.color_view, &target.depth.view);
+        self.post_process(encoder, &target, &output_view);
+
+        // rows have to be copied out with aligned strides
+        let padded_bytes_per_row = (width * 4).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("photo readback buffer"),
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            size: (padded_bytes_per_row * height) as u64,
+            mapped_at_creation: false,
+        });
+        encoder.copy_texture_to_buffer(
+            output_texture.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_bytes_per_row),
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+
+        self.pending_screenshot = Some(PendingScreenshot {
+            buffer,
+            width,
+            height,
+            padded_bytes_per_row,
+            mapped: None,
+        });
+    }
+
+    /// Starts reading back a captured photo, should be called after submitting the commands from [`Self::capture`]
+    pub(crate) fn start_readback(&mut self) {
+        let Some(pending) = &mut self.pending_screenshot else {
+            return;
+        };
+        if pending.mapped.is_some() {
+            return;
+        }
+        let (sender, receiver) = channel();
+        pending
+            .buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let _ = sender.send(result);
+            });
+        pending.mapped = Some(receiver);
+    }
+
+    /// Checks on a photo being copied back, encoding it once it arrives
+    pub(crate) fn poll_screenshot(&mut self, device: &Device) {
+        let Some(mapped) = self
+            .pending_screenshot
+            .as_ref()
+            .and_then(|pending| pending.mapped.as_ref())
+        else {
+            return;
+        };
+        let _ = device.poll(wgpu::PollType::Poll);
+        let result = mapped.try_recv();
+        let pending = self.pending_screenshot.as_ref().unwrap();
+        match result {
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => log::error!("Photo capture was dropped"),
+            Ok(Err(e)) => log::error!("Failed to read back photo: {e}"),
+            Ok(Ok(())) => {
+                let rgba = self.unpad_pixels(pending);
+                match encode_png(pending.width, pending.height, &rgba) {
+                    Ok(png) => {
+                        self.finished_screenshot = Some(Screenshot {
+                            width: pending.width,
+                            height: pending.height,
+                            png,
+                        })
+                    }
+                    Err(e) => log::error!("Failed to encode photo: {e}"),
+                }
+            }
+        }
+        self.pending_screenshot = None;
+    }
+
+    /// Tightly packed RGBA bytes from the mapped readback buffer
+    fn unpad_pixels(&self, pending: &PendingScreenshot) -> Vec<u8> {
+        let bgra = matches!(
+            self.format,
+            TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb
+        );
+        let row_bytes = (pending.width * 4) as usize;
+        let mut rgba = Vec::with_capacity(row_bytes * pending.height as usize);
+        {
+            let mapped = pending.buffer.slice(..).get_mapped_range();
+            for row in mapped.chunks(pending.padded_bytes_per_row as usize) {
+                rgba.extend_from_slice(&row[..row_bytes]);
+            }
+        }
+        pending.buffer.unmap();
+        if bgra {
+            for pixel in rgba.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+        rgba
+    }
+}
+
+fn encode_png(width: u32, height: u32, rgba: &[u8]) -> Result<Vec<u8>, png::EncodingError> {
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(rgba)?;
+    writer.finish()?;
+    Ok(png)
+}
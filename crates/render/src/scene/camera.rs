@@ -1,7 +1,14 @@
 use nalgebra::{Matrix4, Perspective3};
-use utils::Camera;
+use utils::{Camera, OcclusionFade};
 
 pub fn get_view_projection_matrix(camera: &Camera) -> CameraUniformMatrix {
+    CameraUniformMatrix {
+        view_proj: view_projection(camera).into(),
+    }
+}
+
+/// Transforms world space to wgpu clip space
+pub fn view_projection(camera: &Camera) -> Matrix4<f32> {
     let view_matrix = Matrix4::look_at_rh(&camera.eye, &camera.target, &camera.up);
     let proj_matrix = Perspective3::new(
         camera.aspect_ratio,
@@ -10,9 +17,7 @@ pub fn get_view_projection_matrix(camera: &Camera) -> CameraUniformMatrix {
         Camera::CLIP_FAR,
     )
     .to_homogeneous();
-    return CameraUniformMatrix {
-        view_proj: (OPENGL_TO_WGPU_MATRIX * proj_matrix * view_matrix).into(),
-    };
+    OPENGL_TO_WGPU_MATRIX * proj_matrix * view_matrix
 }
 
 /// Needed this format to pass into buffer
@@ -29,3 +34,23 @@ pub const OPENGL_TO_WGPU_MATRIX: Matrix4<f32> = Matrix4::new(
     0.0, 0.0, 0.5, 0.0,
     0.0, 0.0, 0.5, 1.0,
 );
+
+/// Where the scene shader fades out geometry in front of the camera, a radius of zero fades nothing
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct OcclusionFadeUniform {
+    /// xyz is the start point, w is the radius
+    start_radius: [f32; 4],
+    end: [f32; 4],
+}
+impl From<Option<OcclusionFade>> for OcclusionFadeUniform {
+    fn from(fade: Option<OcclusionFade>) -> Self {
+        match fade {
+            Some(fade) => OcclusionFadeUniform {
+                start_radius: [fade.start.x, fade.start.y, fade.start.z, fade.radius],
+                end: [fade.end.x, fade.end.y, fade.end.z, 0.0],
+            },
+            None => bytemuck::Zeroable::zeroed(),
+        }
+    }
+}
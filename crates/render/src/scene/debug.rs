@@ -1,8 +1,17 @@
+use nalgebra::Isometry3;
+use utils::DebugLine;
 use wgpu::{
-    BufferUsages, RenderPass,
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BufferDescriptor, BufferUsages, Queue,
+    RenderPass,
     util::{BufferInitDescriptor, DeviceExt},
 };
 
+use super::model::Model;
+use crate::uniforms::Matrix4Uniform;
+
+/// Most world space debug lines that can be drawn in a frame
+const MAX_DYNAMIC_LINES: u64 = 65536;
+
 pub struct DebugLineGroup {
     vertex_buffer: wgpu::Buffer,
     n_verts: u32,
@@ -41,6 +50,82 @@ impl DebugLineGroup {
     }
 }
 
+/// World space debug lines that are rewritten every frame
+pub struct DynamicDebugLines {
+    vertex_buffer: wgpu::Buffer,
+    n_verts: u32,
+    /// Identity model transform, since the lines are already in world space
+    bind_group: BindGroup,
+}
+impl DynamicDebugLines {
+    pub fn new(device: &wgpu::Device) -> DynamicDebugLines {
+        let vertex_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("dynamic debug line vertex buffer"),
+            size: size_of::<DebugLineVertex>() as u64 * MAX_DYNAMIC_LINES * 2,
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let identity_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("dynamic debug line transform buffer"),
+            contents: bytemuck::cast_slice(
+                &Matrix4Uniform::from(Isometry3::<f32>::identity()).get_slice(),
+            ),
+            usage: BufferUsages::UNIFORM,
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("dynamic debug line bind group"),
+            layout: &Model::get_bind_group_layout(device),
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: identity_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: identity_buffer.as_entire_binding(),
+                },
+            ],
+        });
+        DynamicDebugLines {
+            vertex_buffer,
+            n_verts: 0,
+            bind_group,
+        }
+    }
+
+    pub fn prepare(&mut self, queue: &Queue, lines: &[DebugLine]) {
+        let lines = &lines[..lines.len().min(MAX_DYNAMIC_LINES as usize)];
+        let verts: Vec<DebugLineVertex> = lines
+            .iter()
+            .flat_map(|line| {
+                [
+                    DebugLineVertex {
+                        pos: line.start.into(),
+                        col: line.col,
+                    },
+                    DebugLineVertex {
+                        pos: line.end.into(),
+                        col: line.col,
+                    },
+                ]
+            })
+            .collect();
+        if !verts.is_empty() {
+            queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&verts));
+        }
+        self.n_verts = verts.len() as u32;
+    }
+
+    pub fn render(&self, render_pass: &mut RenderPass) {
+        if self.n_verts == 0 {
+            return;
+        }
+        render_pass.set_bind_group(1, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.draw(0..self.n_verts, 0..1);
+    }
+}
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct DebugLineVertex {
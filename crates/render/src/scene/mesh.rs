@@ -1,7 +1,10 @@
-use assets::{RawMesh, RawVertex};
+use std::{collections::HashMap, sync::Arc};
+
+use assets::{RawMesh, RawTexture, RawVertex};
 use wgpu::{
     BindGroupDescriptor, BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry,
-    BindingType, BufferBindingType, BufferUsages, ShaderStages,
+    BindingResource, BindingType, BufferBindingType, BufferUsages, Queue, SamplerBindingType,
+    ShaderStages, TextureSampleType, TextureViewDimension,
     util::{BufferInitDescriptor, DeviceExt},
 };
 
@@ -15,11 +18,18 @@ pub struct Mesh {
     pub n_indices: u32,
     /// Per-mesh bind group. Bindings:
     /// 0: material color
+    /// 1: material texture view
+    /// 2: material texture sampler
     pub bind_group: wgpu::BindGroup,
 }
 
 impl Mesh {
-    pub fn from_raw(raw: RawMesh, device: &wgpu::Device) -> Mesh {
+    pub fn from_raw(
+        raw: &RawMesh,
+        device: &wgpu::Device,
+        queue: &Queue,
+        textures: &mut MaterialTextures,
+    ) -> Mesh {
         let verts: Vec<Vertex> = raw.verts.iter().map(|raw| (*raw).into()).collect();
         let vertex_buffer = device.create_buffer_init(&BufferInitDescriptor {
             label: Some("mesh vertex buffer"),
@@ -29,7 +39,7 @@ impl Mesh {
 
         let index_buffer = device.create_buffer_init(&BufferInitDescriptor {
             label: Some("mesh index buffer"),
-            contents: bytemuck::cast_slice(raw.indices),
+            contents: bytemuck::cast_slice(&raw.indices),
             usage: BufferUsages::INDEX,
         });
 
@@ -39,14 +49,25 @@ impl Mesh {
             usage: BufferUsages::UNIFORM,
         });
 
+        let texture_view = textures.view(raw.material.texture.as_ref(), device, queue);
         let bind_group_layout = Self::get_bind_group_layout(device);
         let bind_group = device.create_bind_group(&BindGroupDescriptor {
             label: Some("mesh bind group"),
             layout: &bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: material_color_buffer.as_entire_binding(),
-            }],
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: material_color_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(&texture_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::Sampler(&textures.sampler),
+                },
+            ],
         });
 
         Mesh {
@@ -60,31 +81,179 @@ impl Mesh {
     pub fn get_bind_group_layout(device: &wgpu::Device) -> BindGroupLayout {
         device.create_bind_group_layout(&BindGroupLayoutDescriptor {
             label: Some("mesh bind group layout"),
-            entries: &[BindGroupLayoutEntry {
-                binding: 0,
-                visibility: ShaderStages::FRAGMENT,
-                ty: BindingType::Buffer {
-                    ty: BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: TextureViewDimension::D2,
+                        sample_type: TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
                 },
-                count: None,
-            }],
+            ],
         })
     }
 }
 
+/// Material textures on the GPU, so each is only uploaded once however many meshes use it
+pub struct MaterialTextures {
+    /// Sampled by untextured materials, so they're just their color
+    white: wgpu::TextureView,
+    sampler: wgpu::Sampler,
+    /// Keyed by the raw texture's address, which can't be reused while its `Arc` is held here
+    uploaded: HashMap<*const RawTexture, (Arc<RawTexture>, wgpu::TextureView)>,
+}
+impl MaterialTextures {
+    pub fn new(device: &wgpu::Device, queue: &Queue) -> MaterialTextures {
+        let white = RawTexture {
+            width: 1,
+            height: 1,
+            pixels: vec![255; 4],
+        };
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("mesh material sampler"),
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::Repeat,
+            address_mode_w: wgpu::AddressMode::Repeat,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            anisotropy_clamp: 4,
+            ..Default::default()
+        });
+        MaterialTextures {
+            white: create_material_texture(&white, device, queue),
+            sampler,
+            uploaded: HashMap::new(),
+        }
+    }
+
+    /// The view of a material's texture, uploading it the first time it's used
+    fn view(
+        &mut self,
+        texture: Option<&Arc<RawTexture>>,
+        device: &wgpu::Device,
+        queue: &Queue,
+    ) -> wgpu::TextureView {
+        let Some(texture) = texture else {
+            return self.white.clone();
+        };
+        let (_, view) = self
+            .uploaded
+            .entry(Arc::as_ptr(texture))
+            .or_insert_with(|| {
+                (
+                    texture.clone(),
+                    create_material_texture(texture, device, queue),
+                )
+            });
+        view.clone()
+    }
+}
+
+/// Uploads a material texture with a full mip chain, so distant and glancing surfaces like roads don't shimmer
+fn create_material_texture(
+    raw: &RawTexture,
+    device: &wgpu::Device,
+    queue: &Queue,
+) -> wgpu::TextureView {
+    let mip_level_count = raw.width.max(raw.height).ilog2() + 1;
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("mesh material texture"),
+        size: wgpu::Extent3d {
+            width: raw.width,
+            height: raw.height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: wgpu::TextureFormat::Rgba8UnormSrgb,
+        usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        view_formats: &[],
+    });
+
+    let (mut width, mut height) = (raw.width, raw.height);
+    let mut pixels = raw.pixels.clone();
+    for mip_level in 0..mip_level_count {
+        if mip_level > 0 {
+            (pixels, width, height) = downsample(&pixels, width, height);
+        }
+        queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &texture,
+                mip_level,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &pixels,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(width * 4),
+                rows_per_image: Some(height),
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+    texture.create_view(&wgpu::TextureViewDescriptor::default())
+}
+
+/// Halves an RGBA image in each dimension by averaging 2x2 blocks, returning it and its new size
+fn downsample(pixels: &[u8], width: u32, height: u32) -> (Vec<u8>, u32, u32) {
+    let (new_width, new_height) = ((width / 2).max(1), (height / 2).max(1));
+    let mut out = Vec::with_capacity((new_width * new_height * 4) as usize);
+    for y in 0..new_height {
+        for x in 0..new_width {
+            // odd edges are clamped rather than read past
+            let xs = [(x * 2).min(width - 1), (x * 2 + 1).min(width - 1)];
+            let ys = [(y * 2).min(height - 1), (y * 2 + 1).min(height - 1)];
+            for channel in 0..4 {
+                let sum: u32 = ys
+                    .iter()
+                    .flat_map(|y| xs.iter().map(move |x| (y * width + x) as usize))
+                    .map(|i| pixels[i * 4 + channel] as u32)
+                    .sum();
+                out.push(((sum + 2) / 4) as u8);
+            }
+        }
+    }
+    (out, new_width, new_height)
+}
+
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Vertex {
     pub pos: [f32; 3],
     pub normal: [f32; 3],
+    pub texcoord: [f32; 2],
 }
 impl Vertex {
     pub const BUFFER_LAYOUT: wgpu::VertexBufferLayout<'static> = wgpu::VertexBufferLayout {
         array_stride: std::mem::size_of::<Vertex>() as wgpu::BufferAddress,
         step_mode: wgpu::VertexStepMode::Vertex,
-        attributes: &wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3],
+        attributes: &wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3, 2 => Float32x2],
     };
 }
 impl From<RawVertex> for Vertex {
@@ -92,6 +261,7 @@ impl From<RawVertex> for Vertex {
         Vertex {
             pos: raw.pos,
             normal: raw.normal,
+            texcoord: raw.texcoord,
         }
     }
 }
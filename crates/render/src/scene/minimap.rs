@@ -0,0 +1,458 @@
+use nalgebra::{Matrix4, Orthographic3, Point3, Vector3};
+use utils::RenderSnapshot;
+use wgpu::{
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, Buffer, BufferDescriptor, BufferUsages,
+    Queue, RenderPass, RenderPipeline, ShaderStages, TextureView,
+    util::{BufferInitDescriptor, DeviceExt},
+};
+
+use super::{
+    camera::{OPENGL_TO_WGPU_MATRIX, OcclusionFadeUniform},
+    shadows::ShadowMapper,
+};
+use crate::{DepthTexture, uniforms::Matrix4Uniform};
+
+/// Resolution of the minimap texture
+const TEXTURE_DIM: u32 = 512;
+/// How far the minimap reaches from the car to its edge, in meters
+const RANGE: f32 = 90.0;
+/// On-screen size of the minimap, in logical pixels
+const SIZE: f32 = 180.0;
+/// Gap between the minimap and the corner of the screen, in logical pixels
+const MARGIN: f32 = 16.0;
+/// Height the minimap is viewed from, above the car
+const VIEW_HEIGHT: f32 = 200.0;
+
+/// Most overlay vertices that can be drawn in a frame
+const MAX_OVERLAY_VERTS: u64 = 8192;
+
+const ROUTE_WIDTH: f32 = RANGE * 0.035;
+const CAR_MARKER_SIZE: f32 = RANGE * 0.07;
+const CHECKPOINT_MARKER_SIZE: f32 = RANGE * 0.045;
+
+const ROUTE_COLOR: [f32; 3] = [0.15, 0.55, 1.0];
+const CAR_COLOR: [f32; 3] = [1.0, 0.15, 0.1];
+const CHECKPOINT_COLOR: [f32; 3] = [1.0, 0.85, 0.1];
+
+/// Top-down map of the static scene around the car, drawn into a corner of the screen
+///
+/// The map is rendered with an orthographic camera into its own texture, which is then composited onto the screen
+pub struct Minimap {
+    pub visible: bool,
+    /// Whether the map rotates to keep the car's heading pointing up, instead of keeping north (-z) up
+    pub heading_up: bool,
+
+    camera_buffer: Buffer,
+    /// Scene bind group, but viewed through the minimap camera
+    pub scene_bind_group: BindGroup,
+    pub texture_view: TextureView,
+    pub depth_texture_view: TextureView,
+
+    overlay_render_pipeline: RenderPipeline,
+    overlay_vert_buffer: Buffer,
+    n_overlay_verts: u32,
+
+    composite_render_pipeline: RenderPipeline,
+    composite_bind_group: BindGroup,
+
+    last_up: Vector3<f32>,
+}
+impl Minimap {
+    pub fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        scene_bind_group_layout: &BindGroupLayout,
+        sun_dir_buffer: &Buffer,
+        shadow_mapper: &ShadowMapper,
+    ) -> Minimap {
+        let camera_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("minimap camera buffer"),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            size: size_of::<Matrix4Uniform>() as u64,
+            mapped_at_creation: false,
+        });
+        // nothing is faded from above
+        let occlusion_fade_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("minimap occlusion fade buffer"),
+            contents: bytemuck::cast_slice(&[OcclusionFadeUniform::from(None)]),
+            usage: BufferUsages::UNIFORM,
+        });
+        let scene_bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("minimap scene bind group"),
+            layout: scene_bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: camera_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: sun_dir_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: shadow_mapper.view_proj_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 3,
+                    resource: BindingResource::TextureView(&shadow_mapper.texture_view),
+                },
+                BindGroupEntry {
+                    binding: 4,
+                    resource: BindingResource::Sampler(&shadow_mapper.texture_sampler),
+                },
+                BindGroupEntry {
+                    binding: 5,
+                    resource: occlusion_fade_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let texture_size = wgpu::Extent3d {
+            width: TEXTURE_DIM,
+            height: TEXTURE_DIM,
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("minimap texture"),
+            size: texture_size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: config.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let depth_texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("minimap depth texture"),
+            size: texture_size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: DepthTexture::TEXTURE_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let depth_texture_view = depth_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let texture_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("minimap sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let overlay_vert_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("minimap overlay vert buffer"),
+            size: MinimapOverlayVertex::SIZE * MAX_OVERLAY_VERTS,
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let overlay_render_pipeline = {
+            let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("minimap overlay shader"),
+                source: wgpu::ShaderSource::Wgsl(
+                    include_str!("../shaders/minimap_overlay.wgsl").into(),
+                ),
+            });
+            let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("minimap overlay pipeline layout"),
+                bind_group_layouts: &[scene_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("minimap overlay render pipeline"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: None,
+                    buffers: &[MinimapOverlayVertex::BUFFER_LAYOUT],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: None,
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: config.format,
+                        blend: Some(wgpu::BlendState::REPLACE),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: None,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    unclipped_depth: false,
+                    conservative: false,
+                },
+                // overlays are always drawn on top of the map
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: DepthTexture::TEXTURE_FORMAT,
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::Always,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: 1,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview: None,
+                cache: None,
+            })
+        };
+
+        let composite_bind_group_layout =
+            device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+                label: Some("minimap composite bind group layout"),
+                entries: &[
+                    BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        },
+                        count: None,
+                    },
+                    BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                ],
+            });
+        let composite_bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("minimap composite bind group"),
+            layout: &composite_bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&texture_view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Sampler(&texture_sampler),
+                },
+            ],
+        });
+        let composite_render_pipeline = {
+            let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("minimap composite shader"),
+                source: wgpu::ShaderSource::Wgsl(
+                    include_str!("../shaders/minimap_composite.wgsl").into(),
+                ),
+            });
+            let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("minimap composite pipeline layout"),
+                bind_group_layouts: &[&composite_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("minimap composite render pipeline"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: None,
+                    buffers: &[],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: None,
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: config.format,
+                        blend: Some(wgpu::BlendState::REPLACE),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleStrip,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: None,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    unclipped_depth: false,
+                    conservative: false,
+                },
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState {
+                    count: 1,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview: None,
+                cache: None,
+            })
+        };
+
+        Minimap {
+            visible: true,
+            heading_up: true,
+            camera_buffer,
+            scene_bind_group,
+            texture_view,
+            depth_texture_view,
+            overlay_render_pipeline,
+            overlay_vert_buffer,
+            n_overlay_verts: 0,
+            composite_render_pipeline,
+            composite_bind_group,
+            last_up: -Vector3::z(),
+        }
+    }
+
+    pub fn prepare(&mut self, queue: &Queue, snapshot: &RenderSnapshot) {
+        let car_pos: Point3<f32> = snapshot.car_transform.translation.vector.into();
+        let mut car_forward: Vector3<f32> = snapshot.car_transform.rotation * Vector3::z();
+        car_forward.y = 0.0;
+
+        let up = if !self.heading_up {
+            -Vector3::z()
+        } else if car_forward.magnitude_squared() > 0.01 {
+            car_forward.normalize()
+        } else {
+            // car is pointing straight up or down
+            self.last_up
+        };
+        self.last_up = up;
+
+        queue.write_buffer(
+            &self.camera_buffer,
+            0,
+            bytemuck::cast_slice(
+                &Matrix4Uniform::from(Self::get_view_projection_matrix(car_pos, up)).get_slice(),
+            ),
+        );
+
+        // building overlays
+        let mut verts: Vec<MinimapOverlayVertex> = Vec::new();
+        if let Some(route) = &snapshot.route {
+            for segment in route.windows(2) {
+                let mut dir = segment[1] - segment[0];
+                dir.y = 0.0;
+                if dir.magnitude_squared() == 0.0 {
+                    continue;
+                }
+                let right = dir
+                    .normalize()
+                    .cross(&Vector3::y())
+                    .scale(ROUTE_WIDTH * 0.5);
+                let corners = [
+                    segment[0] + right,
+                    segment[0] - right,
+                    segment[1] + right,
+                    segment[1] - right,
+                ];
+                for i in [0, 1, 2, 2, 1, 3] {
+                    verts.push(MinimapOverlayVertex::new(corners[i], ROUTE_COLOR));
+                }
+            }
+        }
+        for checkpoint in snapshot.checkpoints.iter() {
+            let size = CHECKPOINT_MARKER_SIZE;
+            let corners = [
+                checkpoint + Vector3::new(0.0, 0.0, -size),
+                checkpoint + Vector3::new(size, 0.0, 0.0),
+                checkpoint + Vector3::new(0.0, 0.0, size),
+                checkpoint + Vector3::new(-size, 0.0, 0.0),
+            ];
+            for i in [0, 1, 2, 2, 3, 0] {
+                verts.push(MinimapOverlayVertex::new(corners[i], CHECKPOINT_COLOR));
+            }
+        }
+        // car arrow, always kept on top
+        verts.truncate((MAX_OVERLAY_VERTS as usize - 3) / 3 * 3);
+        let forward = if car_forward.magnitude_squared() > 0.01 {
+            car_forward.normalize()
+        } else {
+            up
+        };
+        let right = forward.cross(&Vector3::y());
+        for point in [
+            car_pos + forward.scale(CAR_MARKER_SIZE),
+            car_pos - forward.scale(CAR_MARKER_SIZE * 0.7) + right.scale(CAR_MARKER_SIZE * 0.6),
+            car_pos - forward.scale(CAR_MARKER_SIZE * 0.7) - right.scale(CAR_MARKER_SIZE * 0.6),
+        ] {
+            verts.push(MinimapOverlayVertex::new(point, CAR_COLOR));
+        }
+
+        queue.write_buffer(&self.overlay_vert_buffer, 0, bytemuck::cast_slice(&verts));
+        self.n_overlay_verts = verts.len() as u32;
+    }
+
+    /// Draws overlays onto the minimap texture, after the map itself has been rendered
+    pub fn render_overlays(&self, render_pass: &mut RenderPass) {
+        render_pass.set_pipeline(&self.overlay_render_pipeline);
+        render_pass.set_bind_group(0, &self.scene_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.overlay_vert_buffer.slice(..));
+        render_pass.draw(0..self.n_overlay_verts, 0..1);
+    }
+
+    /// Draws the minimap texture into the bottom right corner of the screen
+    pub fn render_composite(
+        &self,
+        render_pass: &mut RenderPass,
+        screen_width: u32,
+        screen_height: u32,
+        scale_factor: f32,
+    ) {
+        let (screen_width, screen_height) = (screen_width as f32, screen_height as f32);
+        let size = (SIZE * scale_factor).min(screen_width.min(screen_height) * 0.4);
+        let margin = MARGIN * scale_factor;
+        render_pass.set_viewport(
+            screen_width - size - margin,
+            screen_height - size - margin,
+            size,
+            size,
+            0.0,
+            1.0,
+        );
+        render_pass.set_pipeline(&self.composite_render_pipeline);
+        render_pass.set_bind_group(0, &self.composite_bind_group, &[]);
+        render_pass.draw(0..4, 0..1);
+        render_pass.set_viewport(0.0, 0.0, screen_width, screen_height, 0.0, 1.0);
+    }
+
+    fn get_view_projection_matrix(car_pos: Point3<f32>, up: Vector3<f32>) -> Matrix4<f32> {
+        let eye = car_pos + Vector3::y().scale(VIEW_HEIGHT);
+        let view = Matrix4::look_at_rh(&eye, &car_pos, &up);
+        let proj = Orthographic3::new(-RANGE, RANGE, -RANGE, RANGE, 1.0, VIEW_HEIGHT * 2.0)
+            .to_homogeneous();
+        OPENGL_TO_WGPU_MATRIX * proj * view
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct MinimapOverlayVertex {
+    pub pos: [f32; 3],
+    pub col: [f32; 3],
+}
+impl MinimapOverlayVertex {
+    pub const SIZE: u64 = size_of::<Self>() as u64;
+    pub const BUFFER_LAYOUT: wgpu::VertexBufferLayout<'static> = wgpu::VertexBufferLayout {
+        array_stride: Self::SIZE,
+        step_mode: wgpu::VertexStepMode::Vertex,
+        attributes: &wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3],
+    };
+
+    fn new(pos: Point3<f32>, col: [f32; 3]) -> Self {
+        MinimapOverlayVertex {
+            pos: [pos.x, pos.y, pos.z],
+            col,
+        }
+    }
+}
@@ -2,11 +2,14 @@ mod camera;
 #[cfg(debug_assertions)]
 pub mod debug;
 pub mod mesh;
+mod minimap;
 mod model;
+mod route;
 mod shadows;
 mod skidlines;
 
-use nalgebra::{Isometry3, Rotation3, Translation, Vector3};
+use assets::Assets;
+use nalgebra::{Isometry3, Point3, Rotation3, Translation, Vector2, Vector3};
 use utils::*;
 use wgpu::{
     BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor, BindGroupLayoutEntry,
@@ -16,20 +19,27 @@ use wgpu::{
 };
 
 use crate::{DepthTexture, uniforms::Vector3Uniform};
-use camera::{CameraUniformMatrix, get_view_projection_matrix};
+use camera::{
+    CameraUniformMatrix, OcclusionFadeUniform, get_view_projection_matrix, view_projection,
+};
 #[cfg(debug_assertions)]
-use debug::DebugLineVertex;
+use debug::{DebugLineVertex, DynamicDebugLines};
+use mesh::MaterialTextures;
+use minimap::Minimap;
 use model::Model;
+use route::RouteLine;
 use shadows::{SUN_DIR, ShadowMapper};
 use skidlines::{SkidLine, SkidLineVert};
 
 pub struct Scene {
     mesh_render_pipeline: RenderPipeline,
     skidline_render_pipeline: RenderPipeline,
+    route_render_pipeline: RenderPipeline,
     #[cfg(debug_assertions)]
     debug_render_pipeline: RenderPipeline,
 
     camera_buffer: wgpu::Buffer,
+    occlusion_fade_buffer: wgpu::Buffer,
     scene_bind_group: wgpu::BindGroup,
 
     pub shadow_mapper: ShadowMapper,
@@ -38,10 +48,19 @@ pub struct Scene {
     pub car: Model,
     pub wheels: [Model; 4],
     pub skidlines: [SkidLine; 4],
+    pub route_line: RouteLine,
+    pub minimap: Minimap,
+    #[cfg(debug_assertions)]
+    pub dynamic_debug_lines: DynamicDebugLines,
 }
 
 impl Scene {
-    pub fn new(device: &wgpu::Device, config: &wgpu::SurfaceConfiguration) -> Scene {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &Queue,
+        config: &wgpu::SurfaceConfiguration,
+        assets: &Assets,
+    ) -> Scene {
         let scene_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
             label: Some("scene bind group layout"),
             entries: &[
@@ -91,6 +110,16 @@ impl Scene {
                     ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
                     count: None,
                 },
+                BindGroupLayoutEntry {
+                    binding: 5,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
             ],
         });
 
@@ -113,6 +142,12 @@ impl Scene {
             size: size_of::<CameraUniformMatrix>() as u64,
             mapped_at_creation: false,
         });
+        let occlusion_fade_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("scene occlusion fade buffer"),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            size: size_of::<OcclusionFadeUniform>() as u64,
+            mapped_at_creation: false,
+        });
         let shadow_mapper = ShadowMapper::new(device);
 
         let scene_bind_group = device.create_bind_group(&BindGroupDescriptor {
@@ -139,6 +174,10 @@ impl Scene {
                     binding: 4,
                     resource: BindingResource::Sampler(&shadow_mapper.texture_sampler),
                 },
+                BindGroupEntry {
+                    binding: 5,
+                    resource: occlusion_fade_buffer.as_entire_binding(),
+                },
             ],
         });
 
@@ -266,6 +305,62 @@ impl Scene {
             })
         };
 
+        let route_render_pipeline = {
+            let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("route shader"),
+                source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/route.wgsl").into()),
+            });
+            let route_render_pipeline_layout =
+                device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: Some("scene route render pipeline layout"),
+                    bind_group_layouts: &[&scene_bind_group_layout],
+                    push_constant_ranges: &[],
+                });
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("scene route render pipeline"),
+                layout: Some(&route_render_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: None,
+                    buffers: &[SkidLineVert::BUFFER_LAYOUT],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: None,
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: config.format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleStrip,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: None,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    unclipped_depth: false,
+                    conservative: false,
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: DepthTexture::TEXTURE_FORMAT,
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState {
+                    count: 1,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview: None,
+                cache: None,
+            })
+        };
+
         #[cfg(debug_assertions)]
         let debug_render_pipeline = {
             let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
@@ -328,11 +423,23 @@ impl Scene {
             })
         };
 
-        let car = Model::from_object::<assets::objects::Car>("Car", device, None);
+        // shared by all the models, so textures used by more than one are only uploaded once
+        let mut textures = MaterialTextures::new(device, queue);
+        let car = Model::from_object::<assets::objects::Car>(
+            assets,
+            "Car",
+            device,
+            queue,
+            &mut textures,
+            None,
+        );
         let wheels = [0, 1, 2, 3].map(|i| {
             Model::from_object::<assets::objects::Wheel>(
+                assets,
                 format!("Wheel {}", i).as_str(),
                 device,
+                queue,
+                &mut textures,
                 Some(Isometry3::from_parts(
                     Translation::identity(),
                     Rotation3::from_axis_angle(
@@ -348,19 +455,57 @@ impl Scene {
             )
         });
         let skidlines = [0, 1, 2, 3].map(|i| SkidLine::new(device, i));
+        let route_line = RouteLine::new(device);
+        let minimap = Minimap::new(
+            device,
+            config,
+            &scene_bind_group_layout,
+            &sun_dir_buffer,
+            &shadow_mapper,
+        );
         let static_models: Vec<Model> = vec![
-            Model::from_object::<assets::objects::Ground>("Ground", device, None),
-            Model::from_object::<assets::objects::Roads>("Roads", device, None),
-            Model::from_object::<assets::objects::WorldDecor>("Decor", device, None),
-            Model::from_object::<assets::objects::Ocean>("Ocean", device, None),
+            Model::from_object::<assets::objects::Ground>(
+                assets,
+                "Ground",
+                device,
+                queue,
+                &mut textures,
+                None,
+            ),
+            Model::from_object::<assets::objects::Roads>(
+                assets,
+                "Roads",
+                device,
+                queue,
+                &mut textures,
+                None,
+            ),
+            Model::from_object::<assets::objects::WorldDecor>(
+                assets,
+                "Decor",
+                device,
+                queue,
+                &mut textures,
+                None,
+            ),
+            Model::from_object::<assets::objects::Ocean>(
+                assets,
+                "Ocean",
+                device,
+                queue,
+                &mut textures,
+                None,
+            ),
         ];
 
         Scene {
             mesh_render_pipeline,
             skidline_render_pipeline,
+            route_render_pipeline,
             #[cfg(debug_assertions)]
             debug_render_pipeline,
             camera_buffer,
+            occlusion_fade_buffer,
             scene_bind_group,
 
             shadow_mapper,
@@ -368,6 +513,10 @@ impl Scene {
             car,
             wheels,
             skidlines,
+            route_line,
+            minimap,
+            #[cfg(debug_assertions)]
+            dynamic_debug_lines: DynamicDebugLines::new(device),
             camera,
         }
     }
@@ -378,6 +527,11 @@ impl Scene {
             0,
             bytemuck::cast_slice(&[get_view_projection_matrix(&self.camera)]),
         );
+        queue.write_buffer(
+            &self.occlusion_fade_buffer,
+            0,
+            bytemuck::cast_slice(&[OcclusionFadeUniform::from(self.camera.occlusion_fade)]),
+        );
 
         self.shadow_mapper
             .prepare(queue, snapshot.car_transform.translation.vector.into());
@@ -395,6 +549,11 @@ impl Scene {
         for skidline in self.skidlines.iter_mut() {
             skidline.prepare(queue, snapshot);
         }
+        self.route_line.prepare(queue, snapshot);
+        self.minimap.prepare(queue, snapshot);
+        #[cfg(debug_assertions)]
+        self.dynamic_debug_lines
+            .prepare(queue, &snapshot.debug_lines);
     }
 
     pub fn render<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
@@ -414,6 +573,10 @@ impl Scene {
             skidline.render(render_pass);
         }
 
+        // gps route rendering
+        render_pass.set_pipeline(&self.route_render_pipeline);
+        self.route_line.render(render_pass);
+
         // debug line rendering
         #[cfg(debug_assertions)]
         {
@@ -425,9 +588,39 @@ impl Scene {
             self.static_models
                 .iter()
                 .for_each(|m| m.render_debug_lines(render_pass));
+            self.dynamic_debug_lines.render(render_pass);
         }
     }
 
+    /// Where a point in the world appears on screen in physical pixels, or none if it is behind the camera
+    pub fn project_to_screen(
+        &self,
+        point: Point3<f32>,
+        screen_width: u32,
+        screen_height: u32,
+    ) -> Option<Vector2<f32>> {
+        let clip = view_projection(&self.camera) * point.to_homogeneous();
+        if clip.w <= 0.0 {
+            return None;
+        }
+        let ndc = clip.xy() / clip.w;
+        Some(Vector2::new(
+            (ndc.x + 1.0) * 0.5 * screen_width as f32,
+            (1.0 - ndc.y) * 0.5 * screen_height as f32,
+        ))
+    }
+
+    /// Renders the static models and map overlays into the minimap texture
+    pub fn minimap_render<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
+        render_pass.set_bind_group(0, &self.minimap.scene_bind_group, &[]);
+        render_pass.set_pipeline(&self.mesh_render_pipeline);
+        self.static_models
+            .iter()
+            .for_each(|m| m.render(render_pass));
+
+        self.minimap.render_overlays(render_pass);
+    }
+
     pub fn shadow_map_render<'a>(&'a self, render_pass: &mut wgpu::RenderPass<'a>) {
         self.car.shadow_map_render(render_pass);
         self.wheels
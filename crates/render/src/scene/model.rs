@@ -1,6 +1,6 @@
 use std::cell::OnceCell;
 
-use assets::GameObject;
+use assets::{Assets, GameObject, RawDebugLine, RawMesh};
 use nalgebra::Isometry3;
 use wgpu::{
     BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
@@ -8,7 +8,7 @@ use wgpu::{
     Queue, RenderPass, ShaderStages,
 };
 
-use super::mesh::Mesh;
+use super::mesh::{MaterialTextures, Mesh};
 #[cfg(debug_assertions)]
 use crate::scene::debug::DebugLineGroup;
 use crate::uniforms::Matrix4Uniform;
@@ -29,17 +29,41 @@ pub struct Model {
 }
 impl Model {
     pub fn from_object<GO: GameObject>(
+        assets: &Assets,
         name: &str,
         device: &wgpu::Device,
+        queue: &Queue,
+        textures: &mut MaterialTextures,
         static_transform: Option<Isometry3<f32>>,
     ) -> Model {
-        let meshes: Vec<Mesh> = GO::render_meshes
-            .into_iter()
-            .map(|raw| Mesh::from_raw(*raw, device))
+        Self::from_meshes(
+            GO::render_meshes(assets).iter(),
+            GO::debug_lines,
+            name,
+            device,
+            queue,
+            textures,
+            static_transform,
+        )
+    }
+
+    fn from_meshes<'a>(
+        raw_meshes: impl IntoIterator<Item = &'a RawMesh>,
+        #[cfg_attr(not(debug_assertions), allow(unused_variables))] debug_lines: &[RawDebugLine],
+        name: &str,
+        device: &wgpu::Device,
+        queue: &Queue,
+        textures: &mut MaterialTextures,
+        static_transform: Option<Isometry3<f32>>,
+    ) -> Model {
+        let raw_meshes: Vec<&RawMesh> = raw_meshes.into_iter().collect();
+        let meshes: Vec<Mesh> = raw_meshes
+            .iter()
+            .map(|raw| Mesh::from_raw(raw, device, queue, textures))
             .collect();
         #[cfg(debug_assertions)]
-        let debug_lines = if GO::debug_lines.len() > 0 {
-            Some(DebugLineGroup::from_raw(device, GO::debug_lines))
+        let debug_lines = if !debug_lines.is_empty() {
+            Some(DebugLineGroup::from_raw(device, debug_lines))
         } else {
             None
         };
@@ -76,15 +100,8 @@ impl Model {
         log::debug!(
             "Loaded meshes for model \"{}\" ({} verts, {} faces)",
             name,
-            GO::render_meshes
-                .iter()
-                .map(|m| m.verts.len())
-                .sum::<usize>(),
-            GO::render_meshes
-                .iter()
-                .map(|m| m.indices.len())
-                .sum::<usize>()
-                / 3
+            raw_meshes.iter().map(|m| m.verts.len()).sum::<usize>(),
+            raw_meshes.iter().map(|m| m.indices.len()).sum::<usize>() / 3
         );
         Model {
             _name: name.into(),
@@ -0,0 +1,69 @@
+use nalgebra::Vector3;
+use utils::RenderSnapshot;
+use wgpu::{Buffer, BufferDescriptor, BufferUsages, RenderPass};
+
+use super::skidlines::SkidLineVert;
+
+/// Most route points that can be drawn, any further along are cut off
+const MAX_POINTS: u64 = 1024;
+
+const LINE_WIDTH: f32 = 1.2;
+const LINE_OFFSET: f32 = 0.15;
+
+const LINE_WIDTH_HALF: f32 = LINE_WIDTH * 0.5;
+
+/// Draws the GPS route as a ribbon along the ground
+pub struct RouteLine {
+    vert_buffer: Buffer,
+    n_verts: u32,
+}
+impl RouteLine {
+    pub fn new(device: &wgpu::Device) -> RouteLine {
+        let vert_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("route line vert buffer"),
+            size: SkidLineVert::SIZE * MAX_POINTS * 2,
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        RouteLine {
+            vert_buffer,
+            n_verts: 0,
+        }
+    }
+
+    pub fn prepare(&mut self, queue: &wgpu::Queue, snapshot: &RenderSnapshot) {
+        let Some(route) = &snapshot.route else {
+            self.n_verts = 0;
+            return;
+        };
+        let points = &route[..route.len().min(MAX_POINTS as usize)];
+
+        let mut verts: Vec<SkidLineVert> = Vec::with_capacity(points.len() * 2);
+        let mut right_dir = Vector3::x();
+        for (i, point) in points.iter().enumerate() {
+            let prev = points[i.saturating_sub(1)];
+            let next = points[(i + 1).min(points.len() - 1)];
+            let mut forward = next - prev;
+            forward.y = 0.0;
+            if forward.magnitude_squared() > 0.0 {
+                right_dir = forward.normalize().cross(&Vector3::y());
+            }
+            let center = point + Vector3::y().scale(LINE_OFFSET);
+            verts.push(SkidLineVert::from(
+                center + right_dir.scale(LINE_WIDTH_HALF),
+            ));
+            verts.push(SkidLineVert::from(
+                center - right_dir.scale(LINE_WIDTH_HALF),
+            ));
+        }
+        queue.write_buffer(&self.vert_buffer, 0, bytemuck::cast_slice(&verts));
+        self.n_verts = verts.len() as u32;
+    }
+
+    pub fn render(&self, render_pass: &mut RenderPass) {
+        if self.n_verts > 0 {
+            render_pass.set_vertex_buffer(0, self.vert_buffer.slice(..));
+            render_pass.draw(0..self.n_verts, 0..1);
+        }
+    }
+}
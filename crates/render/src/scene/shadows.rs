@@ -11,6 +11,8 @@ use crate::{scene::model::Model, uniforms::Matrix4Uniform};
 pub const SUN_DIR: Vector3<f32> = Vector3::new(-1.0, 2.5, 1.2);
 
 pub struct ShadowMapper {
+    /// Whether anything casts shadows. The map is still cleared when off, leaving everything lit
+    pub enabled: bool,
     pub view_proj_buffer: Buffer,
 
     pub render_pipeline: RenderPipeline,
@@ -137,6 +139,7 @@ impl ShadowMapper {
             })
         };
         ShadowMapper {
+            enabled: true,
             view_proj_buffer,
             bind_group,
             texture_view,
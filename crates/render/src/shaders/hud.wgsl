@@ -0,0 +1,39 @@
+// shader to render the 2d hud over the scene
+// Bind groups:
+// 0: Once per frame
+//   0: screen info (width and height in physical pixels, ui scale)
+
+// vert shader ---------------------------------------
+
+@group(0) @binding(0)
+var<uniform> screen: vec4<f32>;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+}
+
+@vertex
+fn vert_main(
+    @location(0) v_position: vec2<f32>,
+    @location(1) v_color: vec4<f32>,
+) -> VertexOutput {
+    // logical pixels from the top left to clip space
+    let pixel_pos = v_position * screen.z;
+    let clip_pos = vec2<f32>(
+        pixel_pos.x / screen.x * 2.0 - 1.0,
+        1.0 - pixel_pos.y / screen.y * 2.0,
+    );
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(clip_pos, 0.0, 1.0);
+    out.color = v_color;
+    return out;
+}
+
+// frag shader ---------------------------------------
+
+@fragment
+fn frag_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
@@ -0,0 +1,50 @@
+// shader to draw the minimap texture onto the screen, as a circle with a border
+// Bind groups:
+// 0: Once per frame
+//   0: minimap texture view
+//   1: minimap sampler
+
+// vert shader ---------------------------------------
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+// fullscreen (viewport) quad, drawn as a 4 vertex triangle strip
+@vertex
+fn vert_main(
+    @builtin(vertex_index) index: u32,
+) -> VertexOutput {
+    let uv = vec2<f32>(f32(index & 1u), f32(index >> 1u));
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+// frag shader ---------------------------------------
+
+@group(0) @binding(0)
+var minimap_tex: texture_2d<f32>;
+
+@group(0) @binding(1)
+var minimap_sampler: sampler;
+
+const BORDER_WIDTH: f32 = 0.03;
+const BORDER_COLOR: vec4<f32> = vec4(0.1, 0.1, 0.1, 1.0);
+
+@fragment
+fn frag_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // sampling before discarding, to keep control flow uniform
+    let color = textureSample(minimap_tex, minimap_sampler, in.uv);
+    let radius = length(in.uv - vec2(0.5)) * 2.0;
+    if radius > 1.0 {
+        discard;
+    }
+    if radius > 1.0 - BORDER_WIDTH {
+        return BORDER_COLOR;
+    }
+    return color;
+}
@@ -0,0 +1,36 @@
+// shader to render route, checkpoint, and car markers onto the minimap
+// Bind groups:
+// 0: Once per scene render
+//   0: camera matrix (minimap camera)
+//   1: sun direction vector
+//   2: shadow map view proj matrix
+//   3: shadow map texture view
+//   4: shadow map sampler
+
+// vert shader ---------------------------------------
+
+@group(0) @binding(0)
+var<uniform> camera_matrix: mat4x4<f32>;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec3<f32>,
+}
+
+@vertex
+fn vert_main(
+    @location(0) v_position: vec3<f32>,
+    @location(1) v_color: vec3<f32>,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = camera_matrix * vec4<f32>(v_position, 1.0);
+    out.color = v_color;
+    return out;
+}
+
+// frag shader ---------------------------------------
+
+@fragment
+fn frag_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4(in.color, 1.0);
+}
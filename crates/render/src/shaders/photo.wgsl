@@ -0,0 +1,85 @@
+// photo mode post processing, applies exposure and depth of field to the rendered scene
+// Bind groups:
+// 0: Once per photo target
+//   0: scene color texture
+//   1: scene depth texture
+//   2: color sampler
+//   3: photo settings
+
+// must match the camera's clip planes
+const CLIP_NEAR: f32 = 0.1;
+const CLIP_FAR: f32 = 1000.0;
+// samples taken across the blur disc
+const BLUR_TAPS: u32 = 32u;
+const GOLDEN_ANGLE: f32 = 2.39996323;
+
+struct PhotoSettings {
+    // color multiplier from the exposure
+    exposure_scale: f32,
+    // distance in focus, in meters
+    focus_distance: f32,
+    // largest blur radius, in pixels of the target
+    max_blur: f32,
+    _pad: f32,
+}
+
+@group(0) @binding(0)
+var color_tex: texture_2d<f32>;
+
+// bound as a plain float texture, depth textures can't be loaded from on WebGL
+@group(0) @binding(1)
+var depth_tex: texture_2d<f32>;
+
+@group(0) @binding(2)
+var color_sampler: sampler;
+
+@group(0) @binding(3)
+var<uniform> settings: PhotoSettings;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+// one triangle covering the screen
+@vertex
+fn vert_main(@builtin(vertex_index) i: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((i << 1u) & 2u), f32(i & 2u));
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv * vec2(2.0, -2.0) + vec2(-1.0, 1.0), 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+// blur radius in pixels for the scene at a pixel, from how far it is from the focus distance
+fn circle_of_confusion(pixel: vec2<i32>) -> f32 {
+    let size = vec2<i32>(textureDimensions(depth_tex));
+    let depth = textureLoad(depth_tex, clamp(pixel, vec2<i32>(0), size - 1), 0).r;
+    let dist = CLIP_NEAR * CLIP_FAR / (CLIP_FAR - depth * (CLIP_FAR - CLIP_NEAR));
+    return min(abs(1.0 - settings.focus_distance / dist), 1.0) * settings.max_blur;
+}
+
+@fragment
+fn frag_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let size = vec2<f32>(textureDimensions(color_tex));
+    let pixel = vec2<i32>(in.clip_position.xy);
+    let coc = circle_of_confusion(pixel);
+
+    var color = textureSampleLevel(color_tex, color_sampler, in.uv, 0.0).rgb;
+    if (coc >= 0.5) {
+        // spiral of samples over the blur disc, skipping sharper pixels so in focus edges don't bleed outwards
+        var total_weight = 1.0;
+        for (var i = 0u; i < BLUR_TAPS; i++) {
+            let radius = sqrt((f32(i) + 0.5) / f32(BLUR_TAPS)) * coc;
+            let angle = f32(i) * GOLDEN_ANGLE;
+            let offset = vec2<f32>(cos(angle), sin(angle)) * radius;
+            let sample_coc = circle_of_confusion(pixel + vec2<i32>(offset));
+            let weight = clamp(sample_coc - radius + 1.0, 0.0, 1.0);
+            color += textureSampleLevel(color_tex, color_sampler, in.uv + offset / size, 0.0).rgb * weight;
+            total_weight += weight;
+        }
+        color /= total_weight;
+    }
+
+    return vec4<f32>(color * settings.exposure_scale, 1.0);
+}
@@ -0,0 +1,35 @@
+// shader to render the gps route line
+// Bind groups:
+// 0: Once per scene render
+//   0: camera matrix
+//   1: sun direction vector
+//   2: shadow map view proj matrix
+//   3: shadow map texture view
+//   4: shadow map sampler
+
+// vert shader ---------------------------------------
+
+@group(0) @binding(0)
+var<uniform> camera_matrix: mat4x4<f32>;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+}
+
+@vertex
+fn vert_main(
+    @location(0) v_position: vec3<f32>,
+) -> VertexOutput {
+    let world_pos = vec4<f32>(v_position, 1.0);
+
+    var out: VertexOutput;
+    out.clip_position = camera_matrix * world_pos;
+    return out;
+}
+
+// frag shader ---------------------------------------
+
+@fragment
+fn frag_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4(0.15, 0.55, 1.0, 0.75);
+}
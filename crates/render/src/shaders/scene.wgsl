@@ -6,11 +6,14 @@
 //   2: shadow map view proj matrix
 //   3: shadow map texture view
 //   4: shadow map sampler
+//   5: occlusion fade capsule
 // 1: Once per model
 //   0: model transform matrix
 //   1: normal transform matrix
 // 2: Once per mesh/material
 //   0: mesh diffuse color
+//   1: mesh diffuse texture
+//   2: mesh diffuse texture sampler
 
 // vert shader ---------------------------------------
 
@@ -30,12 +33,15 @@ struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) normal: vec3<f32>,
     @location(1) shadow_map_pos: vec4<f32>,
+    @location(2) world_pos: vec3<f32>,
+    @location(3) texcoord: vec2<f32>,
 }
 
 @vertex
 fn vert_main(
     @location(0) v_position: vec3<f32>,
     @location(1) v_normal: vec3<f32>,
+    @location(2) v_texcoord: vec2<f32>,
 ) -> VertexOutput {
     let pos = vec4<f32>(v_position, 1.0);
     let world_pos = model_transform * pos;
@@ -44,6 +50,8 @@ fn vert_main(
     out.clip_position = camera_matrix * world_pos;
     out.normal = (normal_transform * vec4<f32>(v_normal, 0.0)).xyz;
     out.shadow_map_pos = shadow_map_view_proj_matrix * world_pos;
+    out.world_pos = world_pos.xyz;
+    out.texcoord = v_texcoord;
     return out;
 }
 
@@ -59,12 +67,57 @@ var shadow_map_tex: texture_depth_2d;
 @group(0) @binding(4)
 var shadow_map_sampler: sampler_comparison;
 
+struct OcclusionFade {
+    // xyz is the start point, w is the radius, zero to disable
+    start_radius: vec4<f32>,
+    end: vec4<f32>,
+}
+@group(0) @binding(5)
+var<uniform> occlusion_fade: OcclusionFade;
+
 @group(2) @binding(0)
 var<uniform> diffuse_color: vec4<f32>;
 
+@group(2) @binding(1)
+var diffuse_tex: texture_2d<f32>;
+
+@group(2) @binding(2)
+var diffuse_sampler: sampler;
+
+// ordered dither threshold in [0, 1) for a pixel, so faded geometry is discarded in an even screen door pattern
+fn dither_threshold(pixel: vec2<f32>) -> f32 {
+    let bayer = array<f32, 16>(
+        0.0, 8.0, 2.0, 10.0,
+        12.0, 4.0, 14.0, 6.0,
+        3.0, 11.0, 1.0, 9.0,
+        15.0, 7.0, 13.0, 5.0,
+    );
+    let p = vec2<u32>(pixel) % vec2<u32>(4u, 4u);
+    return (bayer[p.y * 4u + p.x] + 0.5) / 16.0;
+}
+
+// how much of a point is faded out, 1 being invisible, for points close to the segment between the camera and the car
+fn occlusion_fade_amount(pos: vec3<f32>) -> f32 {
+    let radius = occlusion_fade.start_radius.w;
+    if (radius <= 0.0) {
+        return 0.0;
+    }
+    let start = occlusion_fade.start_radius.xyz;
+    let segment = occlusion_fade.end.xyz - start;
+    let t = clamp(dot(pos - start, segment) / max(dot(segment, segment), 0.0001), 0.0, 1.0);
+    let dist = distance(pos, start + segment * t);
+    return (1.0 - smoothstep(radius * 0.5, radius, dist)) * 0.85;
+}
+
 
 @fragment
 fn frag_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    // sampled before anything is discarded, since texture sampling needs uniform control flow
+    let albedo = diffuse_color.rgb * textureSample(diffuse_tex, diffuse_sampler, in.texcoord).rgb;
+
+    if (occlusion_fade_amount(in.world_pos) > dither_threshold(in.clip_position.xy)) {
+        discard;
+    }
 
     let shadow_coords = in.shadow_map_pos.xyz / in.shadow_map_pos.w;
     var shadow_uv: vec2<f32> = shadow_coords.xy * vec2(0.5, -0.5) + vec2(0.5, 0.5); // map from [-1, 1] range to [0, 1] texture coordinate range
@@ -98,6 +151,6 @@ fn frag_main(in: VertexOutput) -> @location(0) vec4<f32> {
     }
     
     let light = 1.0 - (0.5 * max(ambient_shade_factor, sun_shade_factor));
-    return vec4<f32>(diffuse_color.rgb * light, 1.0);
+    return vec4<f32>(albedo * light, 1.0);
 
 }
\ No newline at end of file
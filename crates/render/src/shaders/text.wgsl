@@ -0,0 +1,54 @@
+// shader to render screen-space text from a signed distance field atlas
+// Bind groups:
+// 0: Once per frame
+//   0: screen info (width and height in physical pixels, ui scale)
+//   1: font atlas texture view
+//   2: font atlas sampler
+
+// vert shader ---------------------------------------
+
+@group(0) @binding(0)
+var<uniform> screen: vec4<f32>;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+@vertex
+fn vert_main(
+    @location(0) v_position: vec2<f32>,
+    @location(1) v_uv: vec2<f32>,
+    @location(2) v_color: vec4<f32>,
+) -> VertexOutput {
+    // logical pixels from the top left to clip space
+    let pixel_pos = v_position * screen.z;
+    let clip_pos = vec2<f32>(
+        pixel_pos.x / screen.x * 2.0 - 1.0,
+        1.0 - pixel_pos.y / screen.y * 2.0,
+    );
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(clip_pos, 0.0, 1.0);
+    out.uv = v_uv;
+    out.color = v_color;
+    return out;
+}
+
+// frag shader ---------------------------------------
+
+@group(0) @binding(1)
+var atlas_tex: texture_2d<f32>;
+
+@group(0) @binding(2)
+var atlas_sampler: sampler;
+
+@fragment
+fn frag_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let dist = textureSample(atlas_tex, atlas_sampler, in.uv).r;
+    // antialiasing over about a pixel, whatever size the text is drawn at
+    let edge_width = max(fwidth(dist) * 0.5, 0.001);
+    let alpha = smoothstep(0.5 - edge_width, 0.5 + edge_width, dist);
+    return vec4(in.color.rgb, in.color.a * alpha);
+}
@@ -0,0 +1,191 @@
+use assets::fonts::RawFont;
+use nalgebra::Vector2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextAlign {
+    Left,
+    Center,
+    Right,
+}
+
+/// Where a glyph's bitmap goes on screen, and where it comes from in the atlas
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GlyphQuad {
+    pub min: Vector2<f32>,
+    pub max: Vector2<f32>,
+    /// Normalized atlas coordinates
+    pub uv_min: Vector2<f32>,
+    pub uv_max: Vector2<f32>,
+}
+
+/// A laid out block of text
+///
+/// Positions are in pixels, with y pointing down. Horizontally the origin is at the left, middle, or right of each
+/// line depending on alignment, and vertically it is at the top of the first line
+#[derive(Debug)]
+pub struct TextLayout {
+    pub quads: Vec<GlyphQuad>,
+    /// Width of the widest line and height of all lines
+    pub size: Vector2<f32>,
+}
+
+/// Lays out text into glyph quads, applying kerning and breaking lines at `\n`
+///
+/// Characters missing from the font are drawn as `?`
+pub fn layout_text(font: &RawFont, text: &str, font_size: f32, align: TextAlign) -> TextLayout {
+    let scale = font_size / font.em_size;
+    let atlas_size = Vector2::new(font.atlas_width as f32, font.atlas_height as f32);
+
+    let mut quads = Vec::new();
+    let mut size = Vector2::zeros();
+    for (line_i, line) in text.split('\n').enumerate() {
+        let baseline = (font.ascent + font.line_height * line_i as f32) * scale;
+        let line_start = quads.len();
+
+        let mut pen = 0.0;
+        let mut prev: Option<char> = None;
+        for c in line.chars() {
+            let Some(glyph) = font.glyph(c).or_else(|| font.glyph('?')) else {
+                continue;
+            };
+            if let Some(prev) = prev {
+                pen += font.kerning(prev, glyph.c) * scale;
+            }
+            if glyph.size[0] > 0.0 {
+                let min = Vector2::new(
+                    pen + glyph.offset[0] * scale,
+                    baseline + glyph.offset[1] * scale,
+                );
+                let atlas_pos = Vector2::new(glyph.atlas_pos[0] as f32, glyph.atlas_pos[1] as f32);
+                let glyph_size = Vector2::new(glyph.size[0], glyph.size[1]);
+                quads.push(GlyphQuad {
+                    min,
+                    max: min + glyph_size * scale,
+                    uv_min: atlas_pos.component_div(&atlas_size),
+                    uv_max: (atlas_pos + glyph_size).component_div(&atlas_size),
+                });
+            }
+            pen += glyph.advance * scale;
+            prev = Some(glyph.c);
+        }
+
+        let shift = match align {
+            TextAlign::Left => 0.0,
+            TextAlign::Center => -pen * 0.5,
+            TextAlign::Right => -pen,
+        };
+        for quad in quads[line_start..].iter_mut() {
+            quad.min.x += shift;
+            quad.max.x += shift;
+        }
+        size.x = f32::max(size.x, pen);
+        size.y = font.line_height * (line_i + 1) as f32 * scale;
+    }
+
+    TextLayout { quads, size }
+}
+
+#[cfg(test)]
+mod tests {
+    use assets::fonts::SPACE_GROTESK;
+
+    use super::*;
+
+    const FONT: &RawFont = &SPACE_GROTESK;
+
+    /// Lays out at the size the font was baked at, so metrics come out unscaled
+    fn layout(text: &str, align: TextAlign) -> TextLayout {
+        layout_text(FONT, text, FONT.em_size, align)
+    }
+
+    fn advance(c: char) -> f32 {
+        FONT.glyph(c).unwrap().advance
+    }
+
+    fn assert_close(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-3, "{a} != {b}");
+    }
+
+    #[test]
+    fn advances_add_up() {
+        assert_close(layout("H", TextAlign::Left).size.x, advance('H'));
+        let width = "HIH".chars().map(advance).sum();
+        assert_close(layout("HIH", TextAlign::Left).size.x, width);
+    }
+
+    #[test]
+    fn kerned_pairs_are_narrower() {
+        let (left, right, adjustment) = *FONT
+            .kerning
+            .iter()
+            .find(|(l, r, adjustment)| {
+                *adjustment < 0.0 && FONT.glyph(*l).is_some() && FONT.glyph(*r).is_some()
+            })
+            .expect("font has negative kerning");
+        let separate = advance(left) + advance(right);
+        let pair = layout(&format!("{left}{right}"), TextAlign::Left).size.x;
+        assert!(pair < separate);
+        assert_close(pair, separate + adjustment);
+    }
+
+    #[test]
+    fn scales_with_font_size() {
+        let small = layout_text(FONT, "HI", FONT.em_size, TextAlign::Left);
+        let large = layout_text(FONT, "HI", FONT.em_size * 2.0, TextAlign::Left);
+        assert_close(large.size.x, small.size.x * 2.0);
+        assert_close(large.size.y, small.size.y * 2.0);
+    }
+
+    #[test]
+    fn alignment_shifts_lines() {
+        let left = layout("HI", TextAlign::Left);
+        let center = layout("HI", TextAlign::Center);
+        let right = layout("HI", TextAlign::Right);
+        let width = left.size.x;
+        assert_close(left.quads[0].min.x, FONT.glyph('H').unwrap().offset[0]);
+        for ((l, c), r) in left.quads.iter().zip(&center.quads).zip(&right.quads) {
+            assert_close(c.min.x, l.min.x - width * 0.5);
+            assert_close(c.max.x, l.max.x - width * 0.5);
+            assert_close(r.min.x, l.min.x - width);
+            assert_eq!((l.min.y, l.uv_min), (c.min.y, c.uv_min));
+        }
+        assert_eq!(center.size, left.size);
+    }
+
+    #[test]
+    fn lines_are_aligned_separately() {
+        let text = layout("H\nHH", TextAlign::Right);
+        assert_eq!(text.quads.len(), 3);
+        assert_close(text.size.x, advance('H') * 2.0);
+        assert_close(text.size.y, FONT.line_height * 2.0);
+        // both lines end at the origin
+        assert_close(text.quads[0].min.x, text.quads[2].min.x);
+        assert_close(text.quads[2].min.y - text.quads[0].min.y, FONT.line_height);
+    }
+
+    #[test]
+    fn empty_text() {
+        let text = layout("", TextAlign::Center);
+        assert!(text.quads.is_empty());
+        assert_eq!(text.size.x, 0.0);
+        assert_close(text.size.y, FONT.line_height);
+
+        let text = layout("\n", TextAlign::Left);
+        assert!(text.quads.is_empty());
+        assert_close(text.size.y, FONT.line_height * 2.0);
+    }
+
+    #[test]
+    fn spaces_take_room_without_quads() {
+        let text = layout("H H", TextAlign::Left);
+        assert_eq!(text.quads.len(), 2);
+        assert_close(text.size.x, advance('H') * 2.0 + advance(' '));
+    }
+
+    #[test]
+    fn missing_characters_fall_back() {
+        let missing = layout("\u{1F697}", TextAlign::Left);
+        let fallback = layout("?", TextAlign::Left);
+        assert_eq!(missing.quads, fallback.quads);
+    }
+}
@@ -0,0 +1,266 @@
+mod layout;
+
+use assets::fonts::{RawFont, SPACE_GROTESK};
+use nalgebra::Vector2;
+use wgpu::{
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, Buffer, BufferBindingType,
+    BufferDescriptor, BufferUsages, Queue, RenderPass, RenderPipeline, ShaderStages,
+};
+
+pub use layout::{TextAlign, layout_text};
+
+/// Most glyphs that can be drawn in a frame
+const MAX_GLYPHS: u64 = 4096;
+
+/// Draws screen-space text from a signed distance field font atlas
+///
+/// Text is queued up over the course of a frame, then drawn all at once in a single draw call. Positions and sizes
+/// are in logical pixels from the top left of the screen
+pub struct TextRenderer {
+    font: &'static RawFont,
+    render_pipeline: RenderPipeline,
+    screen_buffer: Buffer,
+    bind_group: BindGroup,
+    vert_buffer: Buffer,
+    n_verts: u32,
+    /// Glyph vertices queued for the next frame
+    verts: Vec<TextVertex>,
+}
+impl TextRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &Queue,
+        config: &wgpu::SurfaceConfiguration,
+    ) -> TextRenderer {
+        let font = &SPACE_GROTESK;
+
+        let atlas_size = wgpu::Extent3d {
+            width: font.atlas_width,
+            height: font.atlas_height,
+            depth_or_array_layers: 1,
+        };
+        let atlas_texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("font atlas texture"),
+            size: atlas_size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        queue.write_texture(
+            atlas_texture.as_image_copy(),
+            font.atlas,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(font.atlas_width),
+                rows_per_image: Some(font.atlas_height),
+            },
+            atlas_size,
+        );
+        let atlas_view = atlas_texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let atlas_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("font atlas sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let screen_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("text screen buffer"),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            size: size_of::<[f32; 4]>() as u64,
+            mapped_at_creation: false,
+        });
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("text bind group layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::VERTEX,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("text bind group"),
+            layout: &bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: screen_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(&atlas_view),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::Sampler(&atlas_sampler),
+                },
+            ],
+        });
+        let vert_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("text vert buffer"),
+            size: TextVertex::SIZE * 6 * MAX_GLYPHS,
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let render_pipeline = {
+            let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("text shader"),
+                source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/text.wgsl").into()),
+            });
+            let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("text pipeline layout"),
+                bind_group_layouts: &[&bind_group_layout],
+                push_constant_ranges: &[],
+            });
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("text render pipeline"),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: None,
+                    buffers: &[TextVertex::BUFFER_LAYOUT],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: None,
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: config.format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: wgpu::PipelineCompilationOptions::default(),
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    strip_index_format: None,
+                    front_face: wgpu::FrontFace::Ccw,
+                    cull_mode: None,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    unclipped_depth: false,
+                    conservative: false,
+                },
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState {
+                    count: 1,
+                    mask: !0,
+                    alpha_to_coverage_enabled: false,
+                },
+                multiview: None,
+                cache: None,
+            })
+        };
+
+        TextRenderer {
+            font,
+            render_pipeline,
+            screen_buffer,
+            bind_group,
+            vert_buffer,
+            n_verts: 0,
+            verts: Vec::new(),
+        }
+    }
+
+    /// Queues text to be drawn this frame, with `pos` at the top of the first line (see `TextLayout`)
+    pub fn queue_text(
+        &mut self,
+        text: &str,
+        pos: Vector2<f32>,
+        font_size: f32,
+        align: TextAlign,
+        col: [f32; 4],
+    ) {
+        let layout = layout_text(self.font, text, font_size, align);
+        for quad in layout.quads {
+            let (min, max) = (quad.min + pos, quad.max + pos);
+            let corners = [
+                ([min.x, min.y], [quad.uv_min.x, quad.uv_min.y]),
+                ([max.x, min.y], [quad.uv_max.x, quad.uv_min.y]),
+                ([max.x, max.y], [quad.uv_max.x, quad.uv_max.y]),
+                ([min.x, max.y], [quad.uv_min.x, quad.uv_max.y]),
+            ];
+            for i in [0, 1, 2, 0, 2, 3] {
+                self.verts.push(TextVertex {
+                    pos: corners[i].0,
+                    uv: corners[i].1,
+                    col,
+                });
+            }
+        }
+    }
+
+    /// Size text would take up on screen
+    pub fn measure_text(&self, text: &str, font_size: f32) -> Vector2<f32> {
+        layout_text(self.font, text, font_size, TextAlign::Left).size
+    }
+
+    /// Uploads the text queued this frame
+    ///
+    /// `ui_scale` is the number of physical pixels per logical pixel
+    pub fn prepare(&mut self, queue: &Queue, screen_width: u32, screen_height: u32, ui_scale: f32) {
+        queue.write_buffer(
+            &self.screen_buffer,
+            0,
+            bytemuck::cast_slice(&[screen_width as f32, screen_height as f32, ui_scale, 0.0]),
+        );
+        self.verts.truncate(6 * MAX_GLYPHS as usize);
+        queue.write_buffer(&self.vert_buffer, 0, bytemuck::cast_slice(&self.verts));
+        self.n_verts = self.verts.len() as u32;
+        self.verts.clear();
+    }
+
+    pub fn render(&self, render_pass: &mut RenderPass) {
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vert_buffer.slice(..));
+        render_pass.draw(0..self.n_verts, 0..1);
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct TextVertex {
+    pos: [f32; 2],
+    uv: [f32; 2],
+    col: [f32; 4],
+}
+impl TextVertex {
+    const SIZE: u64 = size_of::<Self>() as u64;
+    const BUFFER_LAYOUT: wgpu::VertexBufferLayout<'static> = wgpu::VertexBufferLayout {
+        array_stride: Self::SIZE,
+        step_mode: wgpu::VertexStepMode::Vertex,
+        attributes: &wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2, 2 => Float32x4],
+    };
+}
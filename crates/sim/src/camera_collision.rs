@@ -0,0 +1,138 @@
+use nalgebra::{Isometry3, Point3, Vector3};
+use rapier3d::{parry::query::ShapeCastOptions, prelude::*};
+use utils::OcclusionFade;
+
+use crate::{camera_rigs::smoothing_factor, car::CarHandler, physics::PhysicsHandler};
+
+/// Radius of the sphere swept to find where the camera fits, big enough to keep the near plane out of walls
+const CAMERA_RADIUS: f32 = 0.35;
+/// Time constant of the camera moving in towards the car when blocked, in seconds
+const PULL_IN_SMOOTHING: f32 = 0.05;
+/// Time constant of the camera moving back out once the way is clear, in seconds
+const RECOVER_SMOOTHING: f32 = 0.6;
+/// Obstructions thinner than this along the line of sight, such as poles and signs, are faded out instead of pulling
+/// the camera in
+const THIN_OCCLUDER_THICKNESS: f32 = 1.5;
+/// Radius of the stretch between the camera and the car where geometry is faded out
+const FADE_RADIUS: f32 = 0.8;
+/// How far short of the pivot the fade stops, so the car itself never fades
+const FADE_CAR_CLEARANCE: f32 = 3.0;
+
+/// Keeps a following camera from clipping into the world or being blocked from seeing the car
+pub struct CameraCollision {
+    /// Current distance of the camera from the pivot, eased towards the distance that is clear
+    distance: Option<f32>,
+}
+impl CameraCollision {
+    pub fn new() -> CameraCollision {
+        CameraCollision { distance: None }
+    }
+
+    /// Forgets the eased distance, so the next update cuts straight to where the camera fits
+    pub fn reset(&mut self) {
+        self.distance = None;
+    }
+
+    /// Moves `desired_eye` in towards `pivot` as far as it needs to be to see the pivot unobstructed, and returns where
+    /// to fade out thin occluders that were left in the way
+    pub fn resolve(
+        &mut self,
+        dt_secs: f32,
+        physics: &mut PhysicsHandler,
+        car: &CarHandler,
+        pivot: Point3<f32>,
+        desired_eye: Point3<f32>,
+    ) -> (Point3<f32>, OcclusionFade) {
+        let offset = desired_eye - pivot;
+        let desired_distance = offset.magnitude();
+        let Some(dir) = offset.try_normalize(1e-4) else {
+            return (desired_eye, Self::fade(pivot, desired_eye));
+        };
+
+        let ball = Ball::new(CAMERA_RADIUS);
+        let query_pipeline =
+            physics.create_query_pipeline(QueryFilter::new().exclude_rigid_body(car.handle));
+        let clear_distance = match query_pipeline.cast_shape(
+            &Isometry3::translation(pivot.x, pivot.y, pivot.z),
+            &dir,
+            &ball,
+            ShapeCastOptions::with_max_time_of_impact(desired_distance),
+        ) {
+            Some((_, hit)) => {
+                let hit_distance = hit.time_of_impact;
+                if Self::is_thin_occluder(
+                    &query_pipeline,
+                    &ball,
+                    desired_eye,
+                    dir,
+                    desired_distance - hit_distance,
+                ) {
+                    desired_distance
+                } else {
+                    hit_distance
+                }
+            }
+            None => desired_distance,
+        };
+
+        // snapping in quickly when blocked and easing back out when clear
+        let current = self.distance.unwrap_or(clear_distance);
+        let time_constant = if clear_distance < current {
+            PULL_IN_SMOOTHING
+        } else {
+            RECOVER_SMOOTHING
+        };
+        let eased = current + (clear_distance - current) * smoothing_factor(dt_secs, time_constant);
+        // never lagging far enough to end up inside what was hit
+        let distance = eased
+            .min(clear_distance + CAMERA_RADIUS * 0.5)
+            .min(desired_distance);
+        self.distance = Some(distance);
+
+        let eye = pivot + dir * distance;
+        (eye, Self::fade(pivot, eye))
+    }
+
+    /// Whether an obstruction between the pivot and the desired camera position is thin enough to see through rather
+    /// than moving the camera in front of it
+    fn is_thin_occluder(
+        query_pipeline: &QueryPipeline,
+        ball: &Ball,
+        desired_eye: Point3<f32>,
+        dir: Vector3<f32>,
+        max_distance: f32,
+    ) -> bool {
+        // sweeping back from the camera to find the far side of the obstruction
+        let Some((_, back_hit)) = query_pipeline.cast_shape(
+            &Isometry3::translation(desired_eye.x, desired_eye.y, desired_eye.z),
+            &-dir,
+            ball,
+            ShapeCastOptions::with_max_time_of_impact(max_distance),
+        ) else {
+            return false;
+        };
+        if max_distance - back_hit.time_of_impact > THIN_OCCLUDER_THICKNESS {
+            return false;
+        }
+
+        // the camera has to be out in the open, not under the ground or an overhang
+        let above_eye = desired_eye + Vector3::y() * 100.0;
+        query_pipeline
+            .cast_ray(&Ray::new(above_eye, -Vector3::y()), 100.0, true)
+            .is_none()
+    }
+
+    fn fade(pivot: Point3<f32>, eye: Point3<f32>) -> OcclusionFade {
+        let to_eye = eye - pivot;
+        let length = to_eye.magnitude();
+        OcclusionFade {
+            start: eye,
+            end: if length > FADE_CAR_CLEARANCE {
+                pivot + to_eye * (FADE_CAR_CLEARANCE / length)
+            } else {
+                eye
+            },
+            radius: FADE_RADIUS,
+        }
+    }
+}
@@ -0,0 +1,393 @@
+use nalgebra::{Isometry3, Point3, Vector3};
+use rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+use utils::Camera;
+
+use crate::{camera_collision::CameraCollision, car::CarHandler, physics::PhysicsHandler};
+
+/// How fast the orbit camera circles the car, in radians per second
+const ORBIT_SPEED: f32 = 0.35;
+const ORBIT_RADIUS: f32 = 8.0;
+const ORBIT_HEIGHT: f32 = 2.5;
+
+/// How far ahead of the car a new trackside camera is placed
+const TRACKSIDE_LEAD: f32 = 35.0;
+/// How far off to the side of the car's path a trackside camera stands
+const TRACKSIDE_SIDE_OFFSET: f32 = 8.0;
+const TRACKSIDE_HEIGHT: f32 = 2.5;
+/// A new trackside camera is picked once the car gets this far from the current one
+const TRACKSIDE_MAX_DIST: f32 = 45.0;
+/// Width of the view around the car the trackside camera zooms to fit, in meters
+const TRACKSIDE_FRAMING: f32 = 9.0;
+
+/// Limits on the field of view after the player's offset is added
+const MIN_FOVY: f32 = 20f32.to_radians();
+const MAX_FOVY: f32 = 120f32.to_radians();
+
+/// A way of placing the camera relative to the car
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CameraRig {
+    ChaseNear,
+    ChaseFar,
+    Hood,
+    Bumper,
+    Cockpit,
+    Orbit,
+    Trackside,
+}
+impl CameraRig {
+    pub const ALL: [CameraRig; 7] = [
+        CameraRig::ChaseNear,
+        CameraRig::ChaseFar,
+        CameraRig::Hood,
+        CameraRig::Bumper,
+        CameraRig::Cockpit,
+        CameraRig::Orbit,
+        CameraRig::Trackside,
+    ];
+
+    pub fn next(&self) -> CameraRig {
+        let i = Self::ALL.iter().position(|rig| rig == self).unwrap();
+        Self::ALL[(i + 1) % Self::ALL.len()]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CameraRig::ChaseNear => "Chase near",
+            CameraRig::ChaseFar => "Chase far",
+            CameraRig::Hood => "Hood",
+            CameraRig::Bumper => "Bumper",
+            CameraRig::Cockpit => "Cockpit",
+            CameraRig::Orbit => "Orbit",
+            CameraRig::Trackside => "Trackside",
+        }
+    }
+
+    fn settings(&self) -> RigSettings {
+        match self {
+            CameraRig::ChaseNear => RigSettings {
+                fovy: 70f32.to_radians(),
+                eye_smoothing: 0.27,
+                target_smoothing: 0.05,
+                up_smoothing: 0.05,
+                collides: true,
+            },
+            CameraRig::ChaseFar => RigSettings {
+                fovy: 60f32.to_radians(),
+                eye_smoothing: 0.4,
+                target_smoothing: 0.08,
+                up_smoothing: 0.05,
+                collides: true,
+            },
+            CameraRig::Hood => RigSettings {
+                fovy: 70f32.to_radians(),
+                eye_smoothing: 0.0,
+                target_smoothing: 0.03,
+                up_smoothing: 0.1,
+                collides: false,
+            },
+            CameraRig::Bumper => RigSettings {
+                fovy: 80f32.to_radians(),
+                eye_smoothing: 0.0,
+                target_smoothing: 0.02,
+                up_smoothing: 0.08,
+                collides: false,
+            },
+            CameraRig::Cockpit => RigSettings {
+                fovy: 75f32.to_radians(),
+                eye_smoothing: 0.0,
+                target_smoothing: 0.04,
+                up_smoothing: 0.12,
+                collides: false,
+            },
+            CameraRig::Orbit => RigSettings {
+                fovy: 60f32.to_radians(),
+                eye_smoothing: 0.1,
+                target_smoothing: 0.05,
+                up_smoothing: 0.2,
+                collides: true,
+            },
+            CameraRig::Trackside => RigSettings {
+                // overridden to zoom on the car
+                fovy: 40f32.to_radians(),
+                eye_smoothing: 0.0,
+                target_smoothing: 0.08,
+                up_smoothing: 0.2,
+                collides: false,
+            },
+        }
+    }
+}
+
+/// How a rig moves the camera
+struct RigSettings {
+    /// Vertical field of view, in radians
+    fovy: f32,
+    /// Time constants of the exponential smoothing towards the rig's placement, in seconds. Zero snaps immediately
+    eye_smoothing: f32,
+    target_smoothing: f32,
+    up_smoothing: f32,
+    /// Whether the camera is kept out of the world and clear of obstructions between it and the car, for rigs that
+    /// follow from a distance
+    collides: bool,
+}
+
+/// Where a rig wants the camera this frame
+struct CameraPlacement {
+    eye: Point3<f32>,
+    target: Point3<f32>,
+    up: Vector3<f32>,
+    fovy: f32,
+}
+
+/// Fraction of the remaining distance to cover this frame for exponential smoothing, so the camera moves the same
+/// regardless of frame rate
+pub(crate) fn smoothing_factor(dt_secs: f32, time_constant: f32) -> f32 {
+    if time_constant <= 0.0 {
+        1.0
+    } else {
+        1.0 - (-dt_secs / time_constant).exp()
+    }
+}
+
+/// Drives the game camera from the current rig
+pub struct CameraRigs {
+    pub current: CameraRig,
+    /// Added to every rig's field of view, in radians
+    pub fov_offset: f32,
+    /// Cut straight to the next placement instead of blending, after switching rigs
+    snap: bool,
+    orbit_angle: f32,
+    trackside_spot: Option<Point3<f32>>,
+    /// Where the smoothing puts the camera before collision moves it in
+    free_eye: Point3<f32>,
+    collision: CameraCollision,
+}
+impl CameraRigs {
+    pub fn new() -> CameraRigs {
+        CameraRigs {
+            current: CameraRig::ChaseNear,
+            fov_offset: 0.0,
+            snap: true,
+            orbit_angle: 0.0,
+            trackside_spot: None,
+            free_eye: Point3::origin(),
+            collision: CameraCollision::new(),
+        }
+    }
+
+    pub fn set_rig(&mut self, rig: CameraRig) {
+        if rig != self.current {
+            self.current = rig;
+            self.snap = true;
+            self.trackside_spot = None;
+        }
+    }
+
+    pub fn cycle(&mut self) {
+        self.set_rig(self.current.next());
+    }
+
+    pub fn update(
+        &mut self,
+        dt_secs: f32,
+        physics: &mut PhysicsHandler,
+        car: &CarHandler,
+        cam: &mut Camera,
+    ) {
+        let settings = self.current.settings();
+        let placement = match self.current {
+            CameraRig::ChaseNear => Self::chase(physics, car, 5.0, 6.25, 2.0, settings.fovy),
+            CameraRig::ChaseFar => Self::chase(physics, car, 6.5, 11.0, 1.5, settings.fovy),
+            CameraRig::Hood => Self::attached(
+                physics,
+                car,
+                Point3::new(0.0, 1.1, 0.75),
+                -0.05,
+                settings.fovy,
+            ),
+            CameraRig::Bumper => Self::attached(
+                physics,
+                car,
+                Point3::new(0.0, 0.45, 2.2),
+                0.0,
+                settings.fovy,
+            ),
+            CameraRig::Cockpit => Self::attached(
+                physics,
+                car,
+                Point3::new(0.33, 1.1, -0.15),
+                -0.08,
+                settings.fovy,
+            ),
+            CameraRig::Orbit => self.orbit(dt_secs, physics, car, settings.fovy),
+            CameraRig::Trackside => self.trackside(physics, car),
+        };
+
+        let snap = self.snap;
+        let blend = |time_constant: f32| {
+            if snap {
+                1.0
+            } else {
+                smoothing_factor(dt_secs, time_constant)
+            }
+        };
+        self.free_eye = self
+            .free_eye
+            .lerp(&placement.eye, blend(settings.eye_smoothing));
+        if snap {
+            self.collision.reset();
+        }
+        if settings.collides {
+            let (eye, fade) =
+                self.collision
+                    .resolve(dt_secs, physics, car, placement.target, self.free_eye);
+            cam.eye = eye;
+            cam.occlusion_fade = Some(fade);
+        } else {
+            cam.eye = self.free_eye;
+            cam.occlusion_fade = None;
+        }
+        cam.target = cam
+            .target
+            .lerp(&placement.target, blend(settings.target_smoothing));
+        cam.up = cam
+            .up
+            .lerp(&placement.up, blend(settings.up_smoothing))
+            .normalize();
+        let fovy = (placement.fovy + self.fov_offset).clamp(MIN_FOVY, MAX_FOVY);
+        cam.fovy += (fovy - cam.fovy) * blend(settings.target_smoothing);
+        self.snap = false;
+    }
+
+    /// Behind and above the car, looking over it
+    fn chase(
+        physics: &PhysicsHandler,
+        car: &CarHandler,
+        eye_height: f32,
+        eye_dist: f32,
+        target_height: f32,
+        fovy: f32,
+    ) -> CameraPlacement {
+        let car_rb = &physics.rigid_bodies[car.handle];
+        let car_transform = *car_rb.position();
+        let car_linear_vel = *car_rb.linvel();
+
+        let forward_dir: Vector3<f32> = {
+            let mut car_forward = car_transform.rotation.transform_vector(&Vector3::z());
+            car_forward.y = 0.0;
+            let mut linvel_forward = car_linear_vel;
+            linvel_forward.y = 0.0;
+
+            // use linear velocity as forward direction if not grounded
+            if car.wheels_grounded > 1 || linvel_forward.magnitude() < 0.5 {
+                car_forward
+            } else {
+                linvel_forward
+            }
+            .normalize()
+        };
+        let eye: Point3<f32> =
+            car_transform.translation * Point3::new(0.0, eye_height, 0.0) - forward_dir * eye_dist;
+
+        CameraPlacement {
+            eye,
+            target: (car_transform.translation.vector + Vector3::new(0.0, target_height, 0.0))
+                .into(),
+            up: Vector3::y(),
+            fovy,
+        }
+    }
+
+    /// Fixed to a point on the car, looking down its heading
+    fn attached(
+        physics: &PhysicsHandler,
+        car: &CarHandler,
+        mount: Point3<f32>,
+        pitch: f32,
+        fovy: f32,
+    ) -> CameraPlacement {
+        let car_transform: Isometry3<f32> = *physics.rigid_bodies[car.handle].position();
+        let eye = car_transform * mount;
+        let look_dir = car_transform.rotation * Vector3::new(0.0, pitch, 1.0).normalize();
+        CameraPlacement {
+            eye,
+            target: eye + look_dir * 10.0,
+            up: car_transform.rotation * Vector3::y(),
+            fovy,
+        }
+    }
+
+    /// Slowly circles the car
+    fn orbit(
+        &mut self,
+        dt_secs: f32,
+        physics: &PhysicsHandler,
+        car: &CarHandler,
+        fovy: f32,
+    ) -> CameraPlacement {
+        self.orbit_angle = (self.orbit_angle + ORBIT_SPEED * dt_secs) % std::f32::consts::TAU;
+        let car_pos = Point3::from(*physics.rigid_bodies[car.handle].translation());
+        CameraPlacement {
+            eye: car_pos
+                + Vector3::new(
+                    self.orbit_angle.sin() * ORBIT_RADIUS,
+                    ORBIT_HEIGHT,
+                    self.orbit_angle.cos() * ORBIT_RADIUS,
+                ),
+            target: car_pos + Vector3::new(0.0, 0.8, 0.0),
+            up: Vector3::y(),
+            fovy,
+        }
+    }
+
+    /// A fixed camera beside the road ahead of the car that pans and zooms to follow it, moving to a new spot once the
+    /// car has passed
+    fn trackside(&mut self, physics: &mut PhysicsHandler, car: &CarHandler) -> CameraPlacement {
+        let car_rb = &physics.rigid_bodies[car.handle];
+        let car_pos = Point3::from(*car_rb.translation());
+        let car_linvel = *car_rb.linvel();
+        let car_forward = car_rb.rotation() * Vector3::z();
+
+        if self
+            .trackside_spot
+            .is_none_or(|spot| nalgebra::distance(&spot, &car_pos) > TRACKSIDE_MAX_DIST)
+        {
+            // place the camera ahead along where the car is heading
+            let mut heading = if car_linvel.magnitude() > 2.0 {
+                car_linvel
+            } else {
+                car_forward
+            };
+            heading.y = 0.0;
+            let heading = heading.try_normalize(1e-4).unwrap_or_else(Vector3::z);
+            let side = heading.cross(&Vector3::y());
+            let ground_spot = car_pos + heading * TRACKSIDE_LEAD + side * TRACKSIDE_SIDE_OFFSET;
+
+            // standing on whatever is below
+            let ray_origin = ground_spot + Vector3::y() * 50.0;
+            let ground_height = physics
+                .create_query_pipeline(QueryFilter::new().exclude_rigid_body(car.handle))
+                .cast_ray(&Ray::new(ray_origin, -Vector3::y()), 100.0, true)
+                .map_or(car_pos.y, |(_, dist)| ray_origin.y - dist);
+            self.trackside_spot = Some(Point3::new(
+                ground_spot.x,
+                ground_height + TRACKSIDE_HEIGHT,
+                ground_spot.z,
+            ));
+            self.snap = true;
+        }
+        let eye = self.trackside_spot.unwrap();
+
+        // zooming to keep the car a constant size in frame
+        let dist = nalgebra::distance(&eye, &car_pos).max(1.0);
+        let fovy = (2.0 * (TRACKSIDE_FRAMING * 0.5 / dist).atan())
+            .clamp(15f32.to_radians(), 70f32.to_radians());
+        CameraPlacement {
+            eye,
+            target: car_pos + Vector3::new(0.0, 0.8, 0.0),
+            up: Vector3::y(),
+            fovy,
+        }
+    }
+}
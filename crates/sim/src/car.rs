@@ -1,12 +1,10 @@
-use assets::{GameObject, objects::Car};
+use assets::{Assets, GameObject, objects::Car};
 use nalgebra::{Isometry3, Point3, Rotation3, UnitQuaternion, Vector2, Vector3};
 use rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+use utils::Gear;
 
-use crate::{controller::CarController, physics::PhysicsHandler};
-
-const MASS: f32 = 2400.0;
-
-const ACCELERATION: f32 = 120.0;
+use crate::{controller::CarController, physics::PhysicsHandler, telemetry::WheelTelemetry};
 
 const SLOW_FAST_THRESH: f32 = 22.0;
 
@@ -18,6 +16,23 @@ const TURN_RESPONSIVENESS_FAST: f32 = 1.2f32.to_radians();
 
 const THROTTLE_RESPONSIVENESS: f32 = 0.1;
 
+/// Speed under which the car is considered stopped, for picking the displayed gear
+const STOPPED_SPEED: f32 = 0.5;
+
+const IDLE_RPM: f32 = 850.0;
+const REDLINE_RPM: f32 = 6800.0;
+/// Speeds the engine sound shifts up at, in m/s. The car has no real gearbox, this just keeps the revs believable
+const SHIFT_SPEEDS: [f32; 5] = [9.0, 17.0, 26.0, 36.0, 48.0];
+/// How far the engine revs above idle with the throttle down while stopped
+const FREE_REV_RPM: f32 = 1500.0;
+
+/// Contact force the car's body has to take before it counts as an impact, in newtons
+const IMPACT_FORCE_THRESHOLD: f32 = 60_000.0;
+/// Contact force of the hardest sounding impact, in newtons
+const IMPACT_FORCE_FULL: f32 = 1_500_000.0;
+/// Slip angle at which a sliding tire squeals the loudest, in radians
+const FULL_SQUEAL_SLIP_ANGLE: f32 = 0.5;
+
 /// max extension of the suspension
 const SUSPENSION_MAX: f32 = 0.3;
 const SUSPENSION_STIFFNESS: f32 = 1800.0;
@@ -31,43 +46,125 @@ fn suspension_compression_curve(val: f32) -> f32 {
 const DRAG_COEFFICIENT: f32 = 0.004;
 const DOWNFORCE_COEFFICIENT: f32 = 17.0;
 
-const MAX_FRICTION: f32 = 180.0;
-
 const WHEEL_DIAMETER: f32 = 0.636653;
 const WHEEL_RADIUS: f32 = WHEEL_DIAMETER / 2.0;
-/// Tire grip coefficient
-const WHEEL_GRIP: f32 = 800.0;
+/// How far down the suspension raycasts reach from the wheel mounts
+pub const SUSPENSION_RAY_LENGTH: f32 = SUSPENSION_MAX + WHEEL_RADIUS;
+
+/// Cars to choose from. They share a body but are tuned to handle differently
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CarKind {
+    #[default]
+    Sedan,
+    Sport,
+    Van,
+}
+impl CarKind {
+    pub const ALL: [CarKind; 3] = [CarKind::Sedan, CarKind::Sport, CarKind::Van];
+
+    pub fn next(&self) -> CarKind {
+        let i = Self::ALL.iter().position(|kind| kind == self).unwrap();
+        Self::ALL[(i + 1) % Self::ALL.len()]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CarKind::Sedan => "Sedan",
+            CarKind::Sport => "Sport",
+            CarKind::Van => "Van",
+        }
+    }
+
+    fn tuning(&self) -> CarTuning {
+        match self {
+            CarKind::Sedan => CarTuning {
+                mass: 2400.0,
+                acceleration: 120.0,
+                max_friction: 180.0,
+                wheel_grip: 800.0,
+            },
+            CarKind::Sport => CarTuning {
+                mass: 2100.0,
+                acceleration: 135.0,
+                max_friction: 205.0,
+                wheel_grip: 880.0,
+            },
+            CarKind::Van => CarTuning {
+                mass: 2700.0,
+                acceleration: 105.0,
+                max_friction: 165.0,
+                wheel_grip: 720.0,
+            },
+        }
+    }
+}
+
+/// What sets a kind of car apart
+struct CarTuning {
+    mass: f32,
+    /// Drive impulse at full throttle
+    acceleration: f32,
+    /// Most force a tire can take before it slips
+    max_friction: f32,
+    /// Tire grip coefficient
+    wheel_grip: f32,
+}
 
 pub struct CarHandler {
     pub handle: RigidBodyHandle,
+    tuning: CarTuning,
     pub(super) throttle: f32,
     pub(super) turn_angle: f32,
 
     wheels_slipping: [bool; 4],
+    /// Where the wheels attach to the body
+    wheel_offsets: [[f32; 3]; 4],
 
     pub wheels_grounded: u32,
+    /// Suspension raycasts from the last step, with how far along each ray the ground was hit
+    pub suspension_rays: [(Ray, Option<f32>); 4],
+    /// Every impulse applied to the car in the last step
+    pub applied_impulses: Vec<AppliedImpulse>,
+    /// Per-wheel state from the last step
+    pub wheel_telemetry: [WheelTelemetry; 4],
+    /// Strength of each impact the body took in the last step, from 0 to 1
+    pub impacts: Vec<f32>,
+    /// Collider pairs the body was hitting hard enough to be an impact in the last step, so a lasting contact only
+    /// counts once
+    impact_contacts: Vec<(ColliderHandle, ColliderHandle)>,
     pub drive_input: DriveInputState,
     pub turn_input: TurnInputState,
 }
 impl CarHandler {
-    pub fn new(physics: &mut PhysicsHandler) -> CarHandler {
+    pub fn new(physics: &mut PhysicsHandler, kind: CarKind, assets: &Assets) -> CarHandler {
+        let tuning = kind.tuning();
         let rbody = RigidBodyBuilder::dynamic()
-            .additional_mass(MASS)
+            .additional_mass(tuning.mass)
             .position(Isometry3::from_parts(
                 Point3::new(0.0, 5.0, 8.0).into(),
                 Rotation3::identity().into(),
             ))
             .can_sleep(false) // car doesn't sleep
             .build();
-        let collider = Car::get_collision_box().build();
+        let collider = Car::get_collision_box(assets)
+            .active_events(ActiveEvents::CONTACT_FORCE_EVENTS)
+            .contact_force_event_threshold(IMPACT_FORCE_THRESHOLD)
+            .build();
         let (handle, _) = physics.insert_object(rbody, Some(collider));
 
         CarHandler {
             handle,
+            tuning,
             turn_angle: 0.0,
             throttle: 0.0,
             wheels_slipping: [false; 4],
+            wheel_offsets: Car::wheel_offsets(assets),
             wheels_grounded: 0,
+            suspension_rays: [(Ray::new(Point3::origin(), -Vector3::y()), None); 4],
+            applied_impulses: Vec::new(),
+            wheel_telemetry: [WheelTelemetry::default(); 4],
+            impacts: Vec::new(),
+            impact_contacts: Vec::new(),
             drive_input: DriveInputState::Coasting,
             turn_input: TurnInputState::None,
         }
@@ -78,8 +175,9 @@ impl CarHandler {
         adjusted_dt: f32,
         physics: &mut PhysicsHandler,
         controller: Option<&CarController>,
+        assists: &DrivingAssists,
     ) -> ([Isometry3<f32>; 4], [Option<Point3<f32>>; 4]) {
-        use assets::objects::Car;
+        self.update_impacts(physics);
 
         let car_transform = *physics.rigid_bodies[self.handle].position();
         let car_up_dir: Vector3<f32> = (car_transform.rotation * Vector3::y()).normalize();
@@ -90,14 +188,12 @@ impl CarHandler {
         let hits = {
             let query_pipeline =
                 physics.create_query_pipeline(QueryFilter::new().exclude_rigid_body(self.handle));
-            Car::WHEEL_OFFSETS.map(|wheel_offset| {
+            self.wheel_offsets.map(|wheel_offset| {
                 let ray_origin = car_transform * Point3::from(wheel_offset);
                 let ray = Ray::new(ray_origin, -car_up_dir);
-                if let Some((_collider, hit_dist)) = query_pipeline.cast_ray_and_get_normal(
-                    &ray,
-                    SUSPENSION_MAX + WHEEL_RADIUS,
-                    false,
-                ) {
+                if let Some((_collider, hit_dist)) =
+                    query_pipeline.cast_ray_and_get_normal(&ray, SUSPENSION_RAY_LENGTH, false)
+                {
                     self.wheels_grounded += 1;
                     (ray, Some(hit_dist))
                 } else {
@@ -106,31 +202,31 @@ impl CarHandler {
             })
         };
 
+        self.suspension_rays =
+            hits.map(|(ray, maybe_hit)| (ray, maybe_hit.map(|hit| hit.time_of_impact)));
+        self.applied_impulses.clear();
+
         let car_rb = &mut physics.rigid_bodies[self.handle];
         let car_linvel = *car_rb.linvel();
 
         // parsing player input
-        self.drive_input = if let Some(controller) = controller {
-            if controller.shift_pressed || (controller.w_pressed && controller.s_pressed) {
+        let idle = CarController::new();
+        let controller = controller.unwrap_or(&idle);
+        self.drive_input =
+            if controller.handbrake || (controller.throttle > 0.0 && controller.brake > 0.0) {
                 DriveInputState::HardBraking
-            } else if controller.w_pressed {
+            } else if controller.throttle > 0.0 {
                 DriveInputState::Accelerating
-            } else if controller.s_pressed {
+            } else if controller.brake > 0.0 {
                 DriveInputState::Reversing
             } else {
                 DriveInputState::Coasting
-            }
-        } else {
-            DriveInputState::Coasting
-        };
-        self.turn_input = if let Some(controller) = controller {
-            if controller.a_pressed && !controller.d_pressed {
-                TurnInputState::Left
-            } else if !controller.a_pressed && controller.d_pressed {
-                TurnInputState::Right
-            } else {
-                TurnInputState::None
-            }
+            };
+        let steer = controller.steer.clamp(-1.0, 1.0);
+        self.turn_input = if steer < 0.0 {
+            TurnInputState::Left
+        } else if steer > 0.0 {
+            TurnInputState::Right
         } else {
             TurnInputState::None
         };
@@ -143,20 +239,22 @@ impl CarHandler {
                 (TURN_RADIUS_SLOW, TURN_RESPONSIVENESS_SLOW * adjusted_dt)
             };
         self.turn_angle = match self.turn_input {
-            TurnInputState::Left => {
-                self.turn_angle * (1.0 - turn_response) + (max_turn_radius * turn_response)
-            }
-            TurnInputState::Right => {
-                self.turn_angle * (1.0 - turn_response) + (-max_turn_radius * turn_response)
+            TurnInputState::Left | TurnInputState::Right => {
+                // turning left is a positive angle
+                self.turn_angle * (1.0 - turn_response) + (-steer * max_turn_radius * turn_response)
             }
             TurnInputState::None => self.turn_angle * (1.0 - (turn_response * 1.5)),
         };
         // lerp throttle
         let throttle_response = THROTTLE_RESPONSIVENESS * adjusted_dt;
         let target_throttle = match self.drive_input {
-            DriveInputState::Accelerating => ACCELERATION,
+            DriveInputState::Accelerating => {
+                self.tuning.acceleration * controller.throttle.min(1.0)
+            }
             DriveInputState::HardBraking => 0.0,
-            DriveInputState::Reversing => -ACCELERATION * 0.8,
+            DriveInputState::Reversing => {
+                -self.tuning.acceleration * 0.8 * controller.brake.min(1.0)
+            }
             DriveInputState::Coasting => 0.0,
         };
         self.throttle =
@@ -171,6 +269,7 @@ impl CarHandler {
         let mut wheel_positions: [Point3<f32>; 4] = [Point3::origin(); 4];
         let mut skid_contact_points: [Option<Point3<f32>>; 4] = [None; 4];
         for wheel_i in 0..4 {
+            self.wheel_telemetry[wheel_i] = WheelTelemetry::default();
             let (ray, maybe_hit) = hits[wheel_i];
             if let Some(intersection) = maybe_hit {
                 // tire is on the ground
@@ -187,6 +286,11 @@ impl CarHandler {
                 let damper_impulse = spring_velocity * SUSPENSION_DAMPER;
                 let suspension_impulse = car_up_dir * (spring_impulse + damper_impulse);
                 car_rb.apply_impulse_at_point(suspension_impulse * adjusted_dt, ray.origin, false);
+                self.applied_impulses.push(AppliedImpulse {
+                    kind: ImpulseKind::Suspension,
+                    point: ray.origin,
+                    impulse: suspension_impulse * adjusted_dt,
+                });
 
                 // calculating tire orientation
                 let wheel_forward_dir = if wheel_i < 2 && self.turn_angle.abs() > 0.01 {
@@ -198,20 +302,29 @@ impl CarHandler {
                 let tire_velocity: Vector3<f32> = car_rb.velocity_at_point(&contact_point);
 
                 // friction forces
-                let lat_force = tire_velocity.normalize().dot(&wheel_right_dir) * -WHEEL_GRIP;
-                let long_force = if wheel_i >= 2 {
+                let lat_force =
+                    tire_velocity.normalize().dot(&wheel_right_dir) * -self.tuning.wheel_grip;
+                let mut long_force = if wheel_i >= 2 {
                     // rwd
                     self.throttle
                 } else {
                     0.0
                 };
+                if assists.traction_control {
+                    // cutting power before the driven wheels lose grip
+                    let max_long_force = (self.tuning.max_friction.powi(2) - lat_force.powi(2))
+                        .max(0.0)
+                        .sqrt();
+                    long_force = long_force.clamp(-max_long_force, max_long_force);
+                }
                 let mut wheel_forces = Vector2::new(lat_force, long_force);
                 let wheel_forces_mag_squared = wheel_forces.magnitude_squared();
 
-                self.wheels_slipping[wheel_i] = wheel_forces_mag_squared > MAX_FRICTION.powi(2);
+                self.wheels_slipping[wheel_i] =
+                    wheel_forces_mag_squared > self.tuning.max_friction.powi(2);
                 if self.wheels_slipping[wheel_i] {
                     // wheel is slipping clamping forces
-                    wheel_forces = wheel_forces.normalize() * MAX_FRICTION * 0.95;
+                    wheel_forces = wheel_forces.normalize() * self.tuning.max_friction * 0.95;
                     // boost acceleration when drifting
                     wheel_forces.y *= 1.1;
 
@@ -219,16 +332,37 @@ impl CarHandler {
                         skid_contact_points[wheel_i] = Some(contact_point);
                     }
                 }
-                car_rb.apply_impulse_at_point(
-                    wheel_right_dir * wheel_forces.x * adjusted_dt,
-                    contact_point,
-                    false,
-                );
-                car_rb.apply_impulse_at_point(
-                    wheel_forward_dir * wheel_forces.y * adjusted_dt,
-                    contact_point,
-                    false,
-                );
+
+                let slip_angle = if tire_velocity.magnitude() > 0.5 {
+                    f32::atan2(
+                        tire_velocity.dot(&wheel_right_dir),
+                        tire_velocity.dot(&wheel_forward_dir).abs(),
+                    )
+                } else {
+                    0.0
+                };
+                self.wheel_telemetry[wheel_i] = WheelTelemetry {
+                    grounded: true,
+                    compression,
+                    load: spring_impulse + damper_impulse,
+                    slip_angle,
+                    slipping: self.wheels_slipping[wheel_i],
+                };
+
+                let friction_impulse = wheel_right_dir * wheel_forces.x * adjusted_dt;
+                let drive_impulse = wheel_forward_dir * wheel_forces.y * adjusted_dt;
+                car_rb.apply_impulse_at_point(friction_impulse, contact_point, false);
+                car_rb.apply_impulse_at_point(drive_impulse, contact_point, false);
+                self.applied_impulses.push(AppliedImpulse {
+                    kind: ImpulseKind::Friction,
+                    point: contact_point,
+                    impulse: friction_impulse,
+                });
+                self.applied_impulses.push(AppliedImpulse {
+                    kind: ImpulseKind::Drive,
+                    point: contact_point,
+                    impulse: drive_impulse,
+                });
             }
 
             wheel_positions[wheel_i] =
@@ -238,15 +372,24 @@ impl CarHandler {
         }
 
         // drag force
-        car_rb.apply_impulse(
-            car_linvel.scale(-car_linvel.magnitude_squared() * DRAG_COEFFICIENT * adjusted_dt),
-            false,
-        );
+        let drag_impulse =
+            car_linvel.scale(-car_linvel.magnitude_squared() * DRAG_COEFFICIENT * adjusted_dt);
+        car_rb.apply_impulse(drag_impulse, false);
+        self.applied_impulses.push(AppliedImpulse {
+            kind: ImpulseKind::Drag,
+            point: *car_rb.center_of_mass(),
+            impulse: drag_impulse,
+        });
 
         // apply downforce if car is grounded and moving fast
         if self.wheels_grounded > 0 {
             let downforce = car_linvel.magnitude() * DOWNFORCE_COEFFICIENT * adjusted_dt;
             car_rb.apply_impulse(-car_up_dir.scale(downforce), false);
+            self.applied_impulses.push(AppliedImpulse {
+                kind: ImpulseKind::Downforce,
+                point: *car_rb.center_of_mass(),
+                impulse: -car_up_dir.scale(downforce),
+            });
         }
 
         let mut wheel_transforms =
@@ -264,16 +407,101 @@ impl CarHandler {
 
         (wheel_transforms, skid_contact_points)
     }
+
+    /// Throttle as a fraction of full acceleration, negative when reversing
+    pub fn throttle_fraction(&self) -> f32 {
+        self.throttle / self.tuning.acceleration
+    }
+
+    /// Gear to show the driver, given the car's speed along its heading
+    pub fn gear(&self, forward_speed: f32) -> Gear {
+        if forward_speed < -STOPPED_SPEED
+            || (forward_speed < STOPPED_SPEED && self.drive_input == DriveInputState::Reversing)
+        {
+            Gear::Reverse
+        } else if forward_speed < STOPPED_SPEED && self.drive_input != DriveInputState::Accelerating
+        {
+            Gear::Neutral
+        } else {
+            Gear::Drive
+        }
+    }
+
+    /// Engine speed to play, given the car's speed along its heading
+    pub fn engine_rpm(&self, forward_speed: f32) -> f32 {
+        let speed = forward_speed.abs();
+        let gear = SHIFT_SPEEDS
+            .iter()
+            .position(|shift| speed < *shift)
+            .unwrap_or(SHIFT_SPEEDS.len() - 1);
+        let rpm = REDLINE_RPM * speed / SHIFT_SPEEDS[gear];
+        let free_rev = IDLE_RPM + self.throttle_fraction().abs() * FREE_REV_RPM;
+        rpm.max(free_rev).min(REDLINE_RPM)
+    }
+
+    /// How hard each tire is sliding from 0 to 1, for the squeal
+    pub fn tire_slip(&self) -> [f32; 4] {
+        self.wheel_telemetry.map(|wheel| {
+            if wheel.grounded && wheel.slipping {
+                (wheel.slip_angle.abs() / FULL_SQUEAL_SLIP_ANGLE).clamp(0.3, 1.0)
+            } else {
+                0.0
+            }
+        })
+    }
+
+    /// Picks out impacts from the body's contact forces during the last physics step
+    fn update_impacts(&mut self, physics: &PhysicsHandler) {
+        self.impacts.clear();
+        let mut contacts = Vec::new();
+        for event in physics.contact_force_events() {
+            let pair = (event.collider1, event.collider2);
+            if !self.impact_contacts.contains(&pair) {
+                self.impacts
+                    .push((event.total_force_magnitude / IMPACT_FORCE_FULL).min(1.0));
+            }
+            contacts.push(pair);
+        }
+        self.impact_contacts = contacts;
+    }
+}
+
+/// Driving aids the player can turn on
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DrivingAssists {
+    /// Limits drive force so the rear wheels don't spin up and slide out
+    pub traction_control: bool,
+}
+
+/// What an impulse applied to the car comes from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImpulseKind {
+    Suspension,
+    /// Lateral tire grip
+    Friction,
+    /// Longitudinal tire force from the throttle
+    Drive,
+    Drag,
+    Downforce,
+}
+
+/// An impulse applied to the car during a step, in world space
+#[derive(Clone, Copy, Debug)]
+pub struct AppliedImpulse {
+    pub kind: ImpulseKind,
+    pub point: Point3<f32>,
+    pub impulse: Vector3<f32>,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
 pub enum TurnInputState {
     Left,
     None,
     Right,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
 pub enum DriveInputState {
     Coasting,
     Accelerating,
@@ -1,31 +1,25 @@
-use winit::keyboard::KeyCode;
-
+/// The driver's inputs, fed from whatever the player is controlling the car with
 pub struct CarController {
-    pub w_pressed: bool,
-    pub a_pressed: bool,
-    pub s_pressed: bool,
-    pub d_pressed: bool,
-    pub shift_pressed: bool,
+    /// How far the accelerator is pressed, from 0 to 1
+    pub throttle: f32,
+    /// How far the brake is pressed, from 0 to 1. Held once stopped, it reverses
+    pub brake: f32,
+    /// Steering from -1 (full left) to 1 (full right)
+    pub steer: f32,
+    pub handbrake: bool,
 }
 impl CarController {
     pub fn new() -> Self {
         CarController {
-            w_pressed: false,
-            a_pressed: false,
-            s_pressed: false,
-            d_pressed: false,
-            shift_pressed: false,
+            throttle: 0.0,
+            brake: 0.0,
+            steer: 0.0,
+            handbrake: false,
         }
     }
-
-    pub fn handle_key_event(&mut self, key: KeyCode, pressed: bool) {
-        match key {
-            KeyCode::KeyW => self.w_pressed = pressed,
-            KeyCode::KeyA => self.a_pressed = pressed,
-            KeyCode::KeyS => self.s_pressed = pressed,
-            KeyCode::KeyD => self.d_pressed = pressed,
-            KeyCode::ShiftLeft => self.shift_pressed = pressed,
-            _ => {}
-        }
+}
+impl Default for CarController {
+    fn default() -> Self {
+        Self::new()
     }
 }
@@ -0,0 +1,189 @@
+use nalgebra::{Isometry3, Point3};
+use rapier3d::prelude::*;
+use utils::DebugDraw;
+
+use crate::{
+    car::{CarHandler, ImpulseKind, SUSPENSION_RAY_LENGTH},
+    physics::PhysicsHandler,
+};
+
+/// Only collider triangles this close to the car are drawn, the world meshes are too big to draw whole every frame
+const COLLIDER_DRAW_RADIUS: f32 = 60.0;
+/// Length of impulse arrows per unit of impulse, in meters
+const IMPULSE_ARROW_SCALE: f32 = 0.01;
+const HIT_MARKER_SIZE: f32 = 0.1;
+
+const COLLIDER_COLOR: [f32; 3] = [0.2, 0.9, 0.9];
+const RAY_COLOR: [f32; 3] = [0.6, 0.6, 0.6];
+const RAY_HIT_COLOR: [f32; 3] = [1.0, 1.0, 0.0];
+
+/// Something that can be drawn by the physics debug view
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PhysicsDebugLayer {
+    Colliders,
+    SuspensionRays,
+    SuspensionImpulses,
+    FrictionImpulses,
+    DriveImpulses,
+    DragImpulses,
+    DownforceImpulses,
+}
+impl PhysicsDebugLayer {
+    pub const ALL: [PhysicsDebugLayer; 7] = [
+        PhysicsDebugLayer::Colliders,
+        PhysicsDebugLayer::SuspensionRays,
+        PhysicsDebugLayer::SuspensionImpulses,
+        PhysicsDebugLayer::FrictionImpulses,
+        PhysicsDebugLayer::DriveImpulses,
+        PhysicsDebugLayer::DragImpulses,
+        PhysicsDebugLayer::DownforceImpulses,
+    ];
+
+    fn from_impulse_kind(kind: ImpulseKind) -> PhysicsDebugLayer {
+        match kind {
+            ImpulseKind::Suspension => PhysicsDebugLayer::SuspensionImpulses,
+            ImpulseKind::Friction => PhysicsDebugLayer::FrictionImpulses,
+            ImpulseKind::Drive => PhysicsDebugLayer::DriveImpulses,
+            ImpulseKind::Drag => PhysicsDebugLayer::DragImpulses,
+            ImpulseKind::Downforce => PhysicsDebugLayer::DownforceImpulses,
+        }
+    }
+
+    fn color(&self) -> [f32; 3] {
+        match self {
+            PhysicsDebugLayer::Colliders => COLLIDER_COLOR,
+            PhysicsDebugLayer::SuspensionRays => RAY_COLOR,
+            PhysicsDebugLayer::SuspensionImpulses => [0.2, 1.0, 0.2],
+            PhysicsDebugLayer::FrictionImpulses => [1.0, 0.2, 0.2],
+            PhysicsDebugLayer::DriveImpulses => [0.2, 0.4, 1.0],
+            PhysicsDebugLayer::DragImpulses => [1.0, 0.6, 0.0],
+            PhysicsDebugLayer::DownforceImpulses => [0.8, 0.2, 1.0],
+        }
+    }
+}
+
+/// Wireframe visualization of what the physics engine sees, with each layer toggled separately
+pub struct PhysicsDebugView {
+    enabled: [bool; PhysicsDebugLayer::ALL.len()],
+}
+impl PhysicsDebugView {
+    pub fn new() -> PhysicsDebugView {
+        PhysicsDebugView {
+            enabled: [false; PhysicsDebugLayer::ALL.len()],
+        }
+    }
+
+    pub fn is_enabled(&self, layer: PhysicsDebugLayer) -> bool {
+        self.enabled[layer as usize]
+    }
+
+    pub fn toggle(&mut self, layer: PhysicsDebugLayer) {
+        self.enabled[layer as usize] = !self.enabled[layer as usize];
+    }
+
+    /// Draws the enabled layers for the current frame
+    pub fn draw(&self, physics: &PhysicsHandler, car: &CarHandler, draw: &mut DebugDraw) {
+        if self.is_enabled(PhysicsDebugLayer::Colliders) {
+            let car_pos = Point3::from(*physics.rigid_bodies[car.handle].translation());
+            for (_, collider) in physics.colliders.iter() {
+                draw_shape(
+                    draw,
+                    collider.shape(),
+                    collider.position(),
+                    car_pos,
+                    COLLIDER_COLOR,
+                );
+            }
+        }
+
+        if self.is_enabled(PhysicsDebugLayer::SuspensionRays) {
+            for (ray, maybe_hit) in car.suspension_rays.iter() {
+                match maybe_hit {
+                    Some(hit_dist) => {
+                        let hit_point = ray.point_at(*hit_dist);
+                        draw.line(ray.origin, hit_point, RAY_HIT_COLOR);
+                        draw.sphere(hit_point, HIT_MARKER_SIZE, RAY_HIT_COLOR);
+                        draw.label(
+                            ray.origin,
+                            &format!("{:.0}%", (1.0 - hit_dist / SUSPENSION_RAY_LENGTH) * 100.0),
+                            RAY_HIT_COLOR,
+                        );
+                    }
+                    None => {
+                        draw.line(ray.origin, ray.point_at(SUSPENSION_RAY_LENGTH), RAY_COLOR);
+                    }
+                }
+            }
+        }
+
+        for applied in car.applied_impulses.iter() {
+            let layer = PhysicsDebugLayer::from_impulse_kind(applied.kind);
+            if self.is_enabled(layer) {
+                draw.arrow(
+                    applied.point,
+                    applied.impulse * IMPULSE_ARROW_SCALE,
+                    layer.color(),
+                );
+            }
+        }
+    }
+}
+
+/// Draws the edges of a collider shape, skipping triangles of meshes that are far from `focus`
+fn draw_shape(
+    draw: &mut DebugDraw,
+    shape: &dyn Shape,
+    transform: &Isometry3<f32>,
+    focus: Point3<f32>,
+    col: [f32; 3],
+) {
+    let mut add_edge = |a: &Point3<f32>, b: &Point3<f32>| {
+        draw.line(transform * a, transform * b, col);
+    };
+    match shape.as_typed_shape() {
+        TypedShape::TriMesh(trimesh) => {
+            let local_focus = transform.inverse_transform_point(&focus);
+            for tri in trimesh.triangles() {
+                if nalgebra::distance_squared(&tri.a, &local_focus) > COLLIDER_DRAW_RADIUS.powi(2) {
+                    continue;
+                }
+                add_edge(&tri.a, &tri.b);
+                add_edge(&tri.b, &tri.c);
+                add_edge(&tri.c, &tri.a);
+            }
+        }
+        TypedShape::ConvexPolyhedron(convex) => {
+            let points = convex.points();
+            for edge in convex.edges() {
+                add_edge(
+                    &points[edge.vertices.x as usize],
+                    &points[edge.vertices.y as usize],
+                );
+            }
+        }
+        TypedShape::Cuboid(cuboid) => {
+            let (points, edges) = cuboid.to_outline();
+            for [a, b] in edges {
+                add_edge(&points[a as usize], &points[b as usize]);
+            }
+        }
+        TypedShape::Ball(ball) => {
+            let (points, edges) = ball.to_outline(16);
+            for [a, b] in edges {
+                add_edge(&points[a as usize], &points[b as usize]);
+            }
+        }
+        TypedShape::Compound(compound) => {
+            for (sub_transform, sub_shape) in compound.shapes() {
+                draw_shape(
+                    draw,
+                    sub_shape.as_ref(),
+                    &(transform * sub_transform),
+                    focus,
+                    col,
+                );
+            }
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,179 @@
+use nalgebra::Point3;
+
+use crate::roads::{RoadNetwork, Route};
+
+/// How far the car can stray from the route before it is re-planned, in meters
+const OFF_ROUTE_DISTANCE: f32 = 15.0;
+/// Minimum time between re-plans, in frames
+const REPLAN_COOLDOWN: f32 = 60.0;
+/// How close to the destination the car needs to get to have arrived, in meters
+const ARRIVAL_DISTANCE: f32 = 10.0;
+
+/// Guides the car to a destination along the road network
+pub struct Gps {
+    destination: Option<Point3<f32>>,
+    route: Option<Route>,
+    replan_cooldown: f32,
+    /// Index of the road node last picked by `cycle_destination`
+    cycled_node: Option<usize>,
+}
+impl Gps {
+    pub fn new() -> Gps {
+        Gps {
+            destination: None,
+            route: None,
+            replan_cooldown: 0.0,
+            cycled_node: None,
+        }
+    }
+
+    pub fn set_destination(&mut self, destination: Option<Point3<f32>>) {
+        self.destination = destination;
+        self.route = None;
+        self.replan_cooldown = 0.0;
+    }
+
+    pub fn destination(&self) -> Option<Point3<f32>> {
+        self.destination
+    }
+
+    /// Steps the destination through each junction in the road network, then back to no destination
+    pub fn cycle_destination(&mut self, roads: &RoadNetwork) {
+        self.cycled_node = match self.cycled_node {
+            Some(node) if node + 1 < roads.nodes.len() => Some(node + 1),
+            Some(_) => None,
+            None if !roads.nodes.is_empty() => Some(0),
+            None => None,
+        };
+        log::debug!("GPS destination: {:?}", self.cycled_node);
+        self.set_destination(self.cycled_node.map(|node| roads.nodes[node].pos));
+    }
+
+    pub fn update(&mut self, adjusted_dt: f32, roads: &RoadNetwork, car_pos: Point3<f32>) {
+        let Some(destination) = self.destination else {
+            return;
+        };
+        if (car_pos.xz() - destination.xz()).magnitude() < ARRIVAL_DISTANCE {
+            log::info!("Arrived at destination");
+            self.cycled_node = None;
+            self.set_destination(None);
+            return;
+        }
+
+        self.replan_cooldown -= adjusted_dt;
+        let off_route = self
+            .route
+            .as_ref()
+            .is_none_or(|route| route.project(car_pos).2 > OFF_ROUTE_DISTANCE);
+        if off_route && self.replan_cooldown <= 0.0 {
+            self.route = roads.plan_route(car_pos, destination);
+            self.replan_cooldown = REPLAN_COOLDOWN;
+            if let Some(route) = &self.route {
+                log::debug!("Planned route ({:.0}m)", route.length);
+            }
+        }
+    }
+
+    /// Polyline of the route still ahead of the car
+    pub fn remaining_route(&self, car_pos: Point3<f32>) -> Option<Vec<Point3<f32>>> {
+        self.route.as_ref().map(|route| route.remaining(car_pos))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(x: f32, z: f32) -> Point3<f32> {
+        Point3::new(x, 0.0, z)
+    }
+
+    /// A straight 200m road along x, and another one along z from its middle
+    fn roads() -> RoadNetwork {
+        RoadNetwork::from_straight_edges(
+            &[
+                point(0.0, 0.0),
+                point(100.0, 0.0),
+                point(200.0, 0.0),
+                point(100.0, 100.0),
+            ],
+            &[(0, 1), (1, 2), (1, 3)],
+        )
+    }
+
+    fn route_start(gps: &Gps) -> Point3<f32> {
+        gps.route.as_ref().unwrap().points[0]
+    }
+
+    #[test]
+    fn plans_a_route() {
+        let roads = roads();
+        let mut gps = Gps::new();
+        gps.update(1.0, &roads, point(0.0, 0.0));
+        assert!(gps.remaining_route(point(0.0, 0.0)).is_none());
+
+        gps.set_destination(Some(point(200.0, 0.0)));
+        gps.update(1.0, &roads, point(10.0, 0.0));
+        let remaining = gps.remaining_route(point(50.0, 1.0)).unwrap();
+        assert_eq!(remaining.first(), Some(&point(50.0, 0.0)));
+        assert_eq!(remaining.last(), Some(&point(200.0, 0.0)));
+    }
+
+    #[test]
+    fn replans_off_route_after_cooldown() {
+        let roads = roads();
+        let mut gps = Gps::new();
+        gps.set_destination(Some(point(200.0, 0.0)));
+        gps.update(1.0, &roads, point(10.0, 0.0));
+        assert_eq!(route_start(&gps), point(10.0, 0.0));
+
+        // close enough to the route to keep it
+        for _ in 0..(REPLAN_COOLDOWN as usize * 2) {
+            gps.update(1.0, &roads, point(20.0, OFF_ROUTE_DISTANCE - 1.0));
+        }
+        assert_eq!(route_start(&gps), point(10.0, 0.0));
+
+        // onto the other road, off the route, but it was only just replanned
+        let mut gps = Gps::new();
+        gps.set_destination(Some(point(200.0, 0.0)));
+        gps.update(1.0, &roads, point(10.0, 0.0));
+        let off_route = point(100.0, 50.0);
+        gps.update(REPLAN_COOLDOWN / 2.0, &roads, off_route);
+        assert_eq!(route_start(&gps), point(10.0, 0.0));
+        gps.update(REPLAN_COOLDOWN / 2.0, &roads, off_route);
+        assert_eq!(route_start(&gps), off_route);
+    }
+
+    #[test]
+    fn arriving_clears_the_destination() {
+        let roads = roads();
+        let mut gps = Gps::new();
+        gps.set_destination(Some(point(200.0, 0.0)));
+        gps.update(1.0, &roads, point(10.0, 0.0));
+        gps.update(1.0, &roads, point(200.0 - ARRIVAL_DISTANCE / 2.0, 0.0));
+        assert!(gps.destination().is_none());
+        assert!(gps.remaining_route(point(200.0, 0.0)).is_none());
+    }
+
+    #[test]
+    fn cycles_through_nodes() {
+        let roads = roads();
+        let mut gps = Gps::new();
+        let destinations: Vec<_> = (0..5)
+            .map(|_| {
+                gps.cycle_destination(&roads);
+                gps.destination()
+            })
+            .collect();
+        assert_eq!(
+            destinations,
+            [
+                Some(roads.nodes[0].pos),
+                Some(roads.nodes[1].pos),
+                Some(roads.nodes[2].pos),
+                Some(roads.nodes[3].pos),
+                None
+            ]
+        );
+    }
+}
@@ -0,0 +1,52 @@
+use nalgebra::Point3;
+
+/// How far the car needs to get from the start line before a lap can be completed, in meters
+const DEPARTURE_DISTANCE: f32 = 60.0;
+/// How close to the start line the car needs to come back to complete a lap, in meters
+const ARRIVAL_DISTANCE: f32 = 12.0;
+
+/// Times laps that start and finish at a fixed point
+///
+/// A lap is counted each time the car leaves the start line behind and then returns to it
+pub struct LapTimer {
+    start: Point3<f32>,
+    /// Whether the car has gotten far enough away from the start line for a lap to count
+    departed: bool,
+    /// Time spent on the current lap, in seconds
+    pub lap_time: f32,
+    pub last_lap: Option<f32>,
+    pub best_lap: Option<f32>,
+    /// Times of every lap completed so far, in seconds
+    pub laps: Vec<f32>,
+}
+impl LapTimer {
+    pub fn new(start: Point3<f32>) -> LapTimer {
+        LapTimer {
+            start,
+            departed: false,
+            lap_time: 0.0,
+            last_lap: None,
+            best_lap: None,
+            laps: Vec::new(),
+        }
+    }
+
+    pub fn update(&mut self, adjusted_dt: f32, car_pos: Point3<f32>) {
+        // adjusted dt is in 60fps frames
+        self.lap_time += adjusted_dt / 60.0;
+
+        let dist = (car_pos.xz() - self.start.xz()).magnitude();
+        if !self.departed {
+            self.departed = dist > DEPARTURE_DISTANCE;
+        } else if dist < ARRIVAL_DISTANCE {
+            log::info!("Lap completed in {:.2}s", self.lap_time);
+            self.last_lap = Some(self.lap_time);
+            self.laps.push(self.lap_time);
+            if self.best_lap.is_none_or(|best| self.lap_time < best) {
+                self.best_lap = Some(self.lap_time);
+            }
+            self.lap_time = 0.0;
+            self.departed = false;
+        }
+    }
+}
@@ -1,53 +1,117 @@
+mod camera_collision;
+mod camera_rigs;
 mod car;
 mod controller;
+mod debug_view;
+mod gps;
+mod laps;
 mod physics;
+mod roads;
+mod telemetry;
 
-use assets::GameObject;
+use assets::{Assets, GameObject};
 use nalgebra::{Point3, Vector3};
 use rapier3d::prelude::*;
 use utils::*;
 
+use camera_rigs::CameraRigs;
 use car::CarHandler;
-use controller::CarController;
+use debug_view::PhysicsDebugView;
+use gps::Gps;
+use laps::LapTimer;
 
 use crate::physics::PhysicsHandler;
+pub use camera_rigs::CameraRig;
+pub use car::{CarKind, DriveInputState, DrivingAssists, TurnInputState};
+pub use controller::CarController;
+pub use debug_view::PhysicsDebugLayer;
+pub use roads::{RoadEdge, RoadNetwork, RoadNode, RoadPoint, Route};
+pub use telemetry::{Telemetry, TelemetryFormat, TelemetryRecorder, WheelTelemetry};
 
 pub struct GameSimulation {
     physics_handler: PhysicsHandler,
     car_handler: CarHandler,
+    roads: RoadNetwork,
+    gps: Gps,
+    lap_timer: LapTimer,
+    telemetry: Telemetry,
+    pub physics_debug: PhysicsDebugView,
+    pub camera_rigs: CameraRigs,
+    /// Debug shapes drawn over the scene, anything in the sim can draw to this
+    pub debug_draw: DebugDraw,
     pub controller: CarController,
+    pub assists: DrivingAssists,
 }
 
 impl GameSimulation {
-    pub fn new() -> GameSimulation {
+    pub fn new(car: CarKind, assets: &Assets) -> GameSimulation {
         let mut physics_handler = PhysicsHandler::new();
 
         // ground
         physics_handler.insert_object(
             RigidBodyBuilder::new(RigidBodyType::Fixed).build(),
-            Some(assets::objects::Ground::get_collision_box().build()),
+            Some(assets::objects::Ground::get_collision_box(assets).build()),
         );
         // roads
         physics_handler.insert_object(
             RigidBodyBuilder::new(RigidBodyType::Fixed).build(),
-            Some(assets::objects::Roads::get_collision_box().build()),
+            Some(assets::objects::Roads::get_collision_box(assets).build()),
         );
-        // decor
-        physics_handler.insert_object(
-            RigidBodyBuilder::new(RigidBodyType::Fixed).build(),
-            Some(assets::objects::WorldDecor::get_collision_box().build()),
+        // decor, with a collider for each part such as the houses and the streetlights
+        for part in assets
+            .model(assets::objects::WorldDecor::collision_model_file)
+            .parts
+            .iter()
+        {
+            physics_handler.insert_object(
+                RigidBodyBuilder::new(RigidBodyType::Fixed).build(),
+                Some(part.collision_box().build()),
+            );
+        }
+
+        let car_handler = CarHandler::new(&mut physics_handler, car, assets);
+        // laps start and finish where the car spawns
+        let lap_timer = LapTimer::new(
+            physics_handler.rigid_bodies[car_handler.handle]
+                .position()
+                .translation
+                .vector
+                .into(),
         );
 
-        let car_handler = CarHandler::new(&mut physics_handler);
+        let roads = RoadNetwork::from_object::<assets::objects::Roads>(assets);
 
         GameSimulation {
             physics_handler,
             car_handler,
+            roads,
+            gps: Gps::new(),
+            lap_timer,
+            telemetry: Telemetry {
+                tick: 0,
+                time: 0.0,
+                speed: 0.0,
+                forward_speed: 0.0,
+                throttle: 0.0,
+                steer: 0.0,
+                drive_input: DriveInputState::Coasting,
+                turn_input: TurnInputState::None,
+                wheels: [WheelTelemetry::default(); 4],
+                yaw_rate: 0.0,
+                lateral_accel: 0.0,
+                longitudinal_accel: 0.0,
+            },
+            physics_debug: PhysicsDebugView::new(),
+            camera_rigs: CameraRigs::new(),
+            debug_draw: DebugDraw::new(),
             controller: CarController::new(),
+            assists: DrivingAssists::default(),
         }
     }
 
     pub fn step(&mut self, adjusted_dt: f32, controller_activated: bool) -> RenderSnapshot {
+        let prev_linvel = *self.physics_handler.rigid_bodies[self.car_handler.handle].linvel();
+        self.debug_draw.advance(adjusted_dt / 60.0);
         self.physics_handler.step(adjusted_dt);
 
         let (wheel_transforms, skid_contact_points) = self.car_handler.step(
@@ -58,76 +122,136 @@ impl GameSimulation {
             } else {
                 None
             },
+            &self.assists,
+        );
+
+        let car_transform = *self.physics_handler.rigid_bodies[self.car_handler.handle].position();
+        let car_pos: Point3<f32> = car_transform.translation.vector.into();
+        self.gps.update(adjusted_dt, &self.roads, car_pos);
+        self.lap_timer.update(adjusted_dt, car_pos);
+
+        let car_rb = &self.physics_handler.rigid_bodies[self.car_handler.handle];
+        let forward_speed = car_rb
+            .linvel()
+            .dot(&car_transform.rotation.transform_vector(&Vector3::z()));
+        self.update_telemetry(adjusted_dt, prev_linvel);
+        self.physics_debug.draw(
+            &self.physics_handler,
+            &self.car_handler,
+            &mut self.debug_draw,
         );
+        let (debug_lines, debug_labels) = self.debug_draw.collect();
 
         RenderSnapshot {
-            car_transform: *self.physics_handler.rigid_bodies[self.car_handler.handle].position(),
+            car_transform,
             wheel_transforms,
             skid_contact_points,
+            route: self.gps.remaining_route(car_pos),
+            checkpoints: self.gps.destination().into_iter().collect(),
+            hud: HudInfo {
+                speed: forward_speed,
+                throttle: self.car_handler.throttle_fraction(),
+                gear: self.car_handler.gear(forward_speed),
+                lap_time: self.lap_timer.lap_time,
+                last_lap: self.lap_timer.last_lap,
+                best_lap: self.lap_timer.best_lap,
+            },
+            telemetry: self.telemetry.sample(),
+            debug_lines,
+            debug_labels,
         }
     }
 
-    pub fn update_camera(&mut self, adjusted_dt: f32, cam: &mut Camera) {
-        const CAM_EYE_LERP: f32 = 0.06;
-        const CAM_TARGET_LERP: f32 = 0.3;
+    fn update_telemetry(&mut self, adjusted_dt: f32, prev_linvel: Vector3<f32>) {
+        let car_rb = &self.physics_handler.rigid_bodies[self.car_handler.handle];
+        let rotation = car_rb.position().rotation;
+        let forward_dir = rotation.transform_vector(&Vector3::z());
+        let right_dir = rotation.transform_vector(&-Vector3::x());
+        let up_dir = rotation.transform_vector(&Vector3::y());
 
-        //const CAM_EYE_OFFSET: Vector3<f32> = Vector3::new(0.0, 5.0, -8.0);
-        const CAM_EYE_HEIGHT: f32 = 5.0;
-        const CAM_EYE_DIST: f32 = 6.25;
-        const CAM_TARGET_HEIGHT: f32 = 2.0;
+        // adjusted dt is in 60ths of a second
+        let dt_secs = adjusted_dt / 60.0;
+        let accel = if dt_secs > 0.0 {
+            (car_rb.linvel() - prev_linvel) / dt_secs
+        } else {
+            Vector3::zeros()
+        };
 
-        let car_transform = *self.physics_handler.rigid_bodies[self.car_handler.handle].position();
-        let car_linear_vel = *self.physics_handler.rigid_bodies[self.car_handler.handle].linvel();
+        self.telemetry = Telemetry {
+            tick: self.telemetry.tick + 1,
+            time: self.telemetry.time + dt_secs,
+            speed: car_rb.linvel().magnitude(),
+            forward_speed: car_rb.linvel().dot(&forward_dir),
+            throttle: self.car_handler.throttle_fraction(),
+            steer: self.car_handler.turn_angle,
+            drive_input: self.car_handler.drive_input,
+            turn_input: self.car_handler.turn_input,
+            wheels: self.car_handler.wheel_telemetry,
+            yaw_rate: car_rb.angvel().dot(&up_dir),
+            lateral_accel: accel.dot(&right_dir),
+            longitudinal_accel: accel.dot(&forward_dir),
+        };
+    }
+
+    /// The car's state as of the last step
+    pub fn telemetry(&self) -> &Telemetry {
+        &self.telemetry
+    }
 
-        let forward_dir: Vector3<f32> = {
-            let mut car_forward = car_transform.rotation.transform_vector(&Vector3::z());
-            car_forward.y = 0.0;
-            let mut linvel_forward = car_linear_vel;
-            linvel_forward.y = 0.0;
+    /// What the car sounds like as of the last step
+    pub fn audio_info(&self) -> AudioInfo {
+        AudioInfo {
+            engine_rpm: self.car_handler.engine_rpm(self.telemetry.forward_speed),
+            engine_load: self.telemetry.throttle.abs(),
+            tire_slip: self.car_handler.tire_slip(),
+            speed: self.telemetry.speed,
+            impacts: self.car_handler.impacts.clone(),
+        }
+    }
 
-            // use linear velocity as forward direction if not grounded
-            if self.car_handler.wheels_grounded > 1 || linvel_forward.magnitude() < 0.5 {
-                car_forward
-            } else {
-                linvel_forward
-            }
-            .normalize()
-        };
-        let mut target_eye: Point3<f32> =
-            car_transform.translation * Point3::new(0.0, CAM_EYE_HEIGHT, 0.0);
-        // casting ray backwards
-        let dist = if let Some((_, dist)) = self
-            .physics_handler
-            .create_query_pipeline(QueryFilter::new().exclude_rigid_body(self.car_handler.handle))
-            .cast_ray(&Ray::new(target_eye, -forward_dir), CAM_EYE_DIST, true)
-        {
-            dist
-        } else {
-            CAM_EYE_DIST
-        };
-        target_eye -= forward_dir * dist;
+    pub fn update_camera(&mut self, adjusted_dt: f32, cam: &mut Camera) {
+        self.camera_rigs.update(
+            adjusted_dt / 60.0,
+            &mut self.physics_handler,
+            &self.car_handler,
+            cam,
+        );
+    }
 
-        cam.eye = cam.eye.lerp(&target_eye, CAM_EYE_LERP * adjusted_dt);
+    pub fn cycle_camera_rig(&mut self) {
+        self.camera_rigs.cycle();
+        log::debug!("Camera rig: {:?}", self.camera_rigs.current);
+    }
 
-        let target_target: Point3<f32> =
-            (car_transform.translation.vector + Vector3::new(0.0, CAM_TARGET_HEIGHT, 0.0)).into();
-        cam.target = cam
-            .target
-            .lerp(&target_target, CAM_TARGET_LERP * adjusted_dt);
+    pub fn roads(&self) -> &RoadNetwork {
+        &self.roads
+    }
+
+    /// Sets where the GPS should guide the car to, planning a route there along the roads
+    pub fn set_gps_destination(&mut self, destination: Option<Point3<f32>>) {
+        self.gps.set_destination(destination);
+    }
+
+    /// Times of every lap completed so far, in seconds
+    pub fn completed_laps(&self) -> &[f32] {
+        &self.lap_timer.laps
+    }
 
-        cam.up = cam.up.lerp(&Vector3::y(), CAM_TARGET_LERP * adjusted_dt);
+    pub fn cycle_gps_destination(&mut self) {
+        self.gps.cycle_destination(&self.roads);
     }
 
     pub fn get_debug_string(&self) -> String {
+        let car_rb = &self.physics_handler.rigid_bodies[self.car_handler.handle];
         format!(
-            "throttle input: {:?}\nsteer input: {:?}\nthrottle: {:.2}\nsteer: {:.2}\nspeed: {:.2}\n",
+            "throttle input: {:?}\nsteer input: {:?}\nthrottle: {:.2}\nsteer: {:.2}\nspeed: {:.2}\non road: {}\n",
             self.car_handler.drive_input,
             self.car_handler.turn_input,
             self.car_handler.throttle,
             self.car_handler.turn_angle,
-            self.physics_handler.rigid_bodies[self.car_handler.handle]
-                .linvel()
-                .magnitude(),
+            car_rb.linvel().magnitude(),
+            self.roads
+                .is_on_road(car_rb.position().translation.vector.into()),
         )
     }
 }
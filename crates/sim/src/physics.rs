@@ -1,3 +1,5 @@
+use std::sync::mpsc::{self, Receiver};
+
 use rapier3d::prelude::*;
 
 const GRAVITY: f32 = 9.81;
@@ -13,9 +15,14 @@ pub struct PhysicsHandler {
     impulse_joints: ImpulseJointSet,
     multibody_joints: MultibodyJointSet,
     ccd_solver: CCDSolver,
+    event_collector: ChannelEventCollector,
+    contact_force_events: Receiver<ContactForceEvent>,
 }
 impl PhysicsHandler {
     pub fn new() -> PhysicsHandler {
+        // only contact forces are used, nothing enables collision events
+        let (collision_sender, _) = mpsc::channel();
+        let (contact_force_sender, contact_force_events) = mpsc::channel();
         PhysicsHandler {
             rigid_bodies: RigidBodySet::new(),
             colliders: ColliderSet::new(),
@@ -27,6 +34,8 @@ impl PhysicsHandler {
             impulse_joints: ImpulseJointSet::new(),
             multibody_joints: MultibodyJointSet::new(),
             ccd_solver: CCDSolver::new(),
+            event_collector: ChannelEventCollector::new(collision_sender, contact_force_sender),
+            contact_force_events,
         }
     }
 
@@ -45,10 +54,15 @@ impl PhysicsHandler {
             &mut self.multibody_joints,
             &mut self.ccd_solver,
             &(),
-            &(),
+            &self.event_collector,
         );
     }
 
+    /// Contact force events since this was last called, from colliders with `ActiveEvents::CONTACT_FORCE_EVENTS`
+    pub fn contact_force_events(&self) -> impl Iterator<Item = ContactForceEvent> + '_ {
+        self.contact_force_events.try_iter()
+    }
+
     /// Insert a rigid body and optionally an associated collider into the scene. Returns the respective handle(s)
     pub fn insert_object(
         &mut self,
@@ -0,0 +1,570 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use nalgebra::{Point2, Point3, Vector2};
+
+use super::{RoadEdge, RoadNode, xz};
+
+/// Size of a surface grid cell, in meters
+const CELL_SIZE: f32 = 0.5;
+/// Triangles with a flatter normal than this are curb sides, not road surface
+const MIN_SURFACE_NORMAL_Y: f32 = 0.7;
+/// Max distance a simplified centerline may stray from the raw one, in meters
+const SIMPLIFY_TOLERANCE: f32 = 0.5;
+
+/// 8-neighbourhood offsets in (x, z), clockwise starting from -z
+const NEIGHBORS: [(isize, isize); 8] = [
+    (0, -1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+];
+
+/// The road surface rasterized onto a horizontal grid, which the road graph is traced from
+///
+/// The grid is padded so that cells along its border are never road, meaning neighbours of road cells can be
+/// indexed without bounds checks
+pub(super) struct RoadSurface {
+    origin: Point2<f32>,
+    width: usize,
+    depth: usize,
+    /// Surface height of each cell, `None` if the cell isn't road
+    heights: Vec<Option<f32>>,
+}
+impl RoadSurface {
+    pub fn rasterize<I: Iterator<Item = [Point3<f32>; 3]>>(triangles: I) -> RoadSurface {
+        let triangles: Vec<[Point3<f32>; 3]> = triangles
+            .filter(|[a, b, c]| {
+                let normal = (b - a).cross(&(c - a));
+                normal.y.abs() > normal.magnitude() * MIN_SURFACE_NORMAL_Y
+            })
+            .collect();
+
+        let mut min = Point2::new(f32::INFINITY, f32::INFINITY);
+        let mut max = Point2::new(f32::NEG_INFINITY, f32::NEG_INFINITY);
+        for p in triangles.iter().flatten() {
+            min = min.inf(&xz(p));
+            max = max.sup(&xz(p));
+        }
+        if triangles.is_empty() {
+            min = Point2::origin();
+            max = Point2::origin();
+        }
+        let origin = min - Vector2::repeat(CELL_SIZE * 2.0);
+        let width = ((max.x - min.x) / CELL_SIZE).ceil() as usize + 4;
+        let depth = ((max.y - min.y) / CELL_SIZE).ceil() as usize + 4;
+
+        let mut surface = RoadSurface {
+            origin,
+            width,
+            depth,
+            heights: vec![None; width * depth],
+        };
+        for [a, b, c] in triangles {
+            let (a2, b2, c2) = (xz(&a), xz(&b), xz(&c));
+            let det = (b2.y - c2.y) * (a2.x - c2.x) + (c2.x - b2.x) * (a2.y - c2.y);
+            if det.abs() < f32::EPSILON {
+                continue; // degenerate from above
+            }
+            let (x0, z0) = surface.cell_coords(a2.inf(&b2).inf(&c2));
+            let (x1, z1) = surface.cell_coords(a2.sup(&b2).sup(&c2));
+            for z in z0..=z1 {
+                for x in x0..=x1 {
+                    let p = surface.cell_center(z * width + x);
+                    // barycentric coords
+                    let l1 = ((b2.y - c2.y) * (p.x - c2.x) + (c2.x - b2.x) * (p.y - c2.y)) / det;
+                    let l2 = ((c2.y - a2.y) * (p.x - c2.x) + (a2.x - c2.x) * (p.y - c2.y)) / det;
+                    let l3 = 1.0 - l1 - l2;
+                    if l1 < -1e-4 || l2 < -1e-4 || l3 < -1e-4 {
+                        continue;
+                    }
+                    let height = a.y * l1 + b.y * l2 + c.y * l3;
+                    let cell = &mut surface.heights[z * width + x];
+                    *cell = Some(cell.map_or(height, |h| h.max(height)));
+                }
+            }
+        }
+        surface
+    }
+
+    pub fn contains(&self, pos: Point2<f32>) -> bool {
+        let x = ((pos.x - self.origin.x) / CELL_SIZE).floor();
+        let z = ((pos.y - self.origin.y) / CELL_SIZE).floor();
+        if x < 0.0 || z < 0.0 || x >= self.width as f32 || z >= self.depth as f32 {
+            return false;
+        }
+        self.heights[z as usize * self.width + x as usize].is_some()
+    }
+
+    /// Traces the centerlines of the surface into a graph of nodes and edges
+    pub fn extract_graph(&self) -> (Vec<RoadNode>, Vec<RoadEdge>) {
+        let dist = self.distance_field();
+        let skeleton = self.skeleton();
+        let (clusters, traces) = self.trace_skeleton(&skeleton);
+
+        // turning grid cells into world points
+        let cell_point = |i: usize| {
+            let p = self.cell_center(i);
+            let point = Point3::new(p.x, self.heights[i].unwrap_or(0.0), p.y);
+            let width = ((2.0 * dist[i] - 1.0) * CELL_SIZE).max(CELL_SIZE);
+            (point, width)
+        };
+        let nodes: Vec<(Point3<f32>, f32)> = clusters
+            .iter()
+            .map(|cells| {
+                let n = cells.len() as f32;
+                let pos = cells
+                    .iter()
+                    .fold(Point3::origin(), |acc, i| acc + cell_point(*i).0.coords / n);
+                let width = cells.iter().map(|i| cell_point(*i).1).fold(0.0, f32::max);
+                (pos, width)
+            })
+            .collect();
+        let mut edges: Vec<Option<RawEdge>> = traces
+            .into_iter()
+            .map(|(start, end, cells)| {
+                Some(RawEdge {
+                    start,
+                    end,
+                    points: cells.into_iter().map(cell_point).collect(),
+                })
+            })
+            .collect();
+
+        prune_spurs(&nodes, &mut edges);
+        merge_pass_through_nodes(&nodes, &mut edges);
+
+        // compacting everything that survived
+        let mut node_remap: HashMap<usize, usize> = HashMap::new();
+        let mut road_nodes: Vec<RoadNode> = Vec::new();
+        let mut road_edges: Vec<RoadEdge> = Vec::new();
+        for edge in edges.into_iter().flatten() {
+            let [start, end] = [edge.start, edge.end].map(|node| {
+                *node_remap.entry(node).or_insert_with(|| {
+                    road_nodes.push(RoadNode {
+                        pos: nodes[node].0,
+                        edges: Vec::new(),
+                    });
+                    road_nodes.len() - 1
+                })
+            });
+            let mut points = vec![nodes[edge.start]];
+            points.extend(edge.points);
+            points.push(nodes[edge.end]);
+            let points = simplify(&points, SIMPLIFY_TOLERANCE);
+
+            road_edges.push(RoadEdge::new(
+                start,
+                end,
+                points.iter().map(|(p, _)| *p).collect(),
+                points.iter().map(|(_, w)| *w).collect(),
+            ));
+            road_nodes[start].edges.push(road_edges.len() - 1);
+            if end != start {
+                road_nodes[end].edges.push(road_edges.len() - 1);
+            }
+        }
+        (road_nodes, road_edges)
+    }
+
+    fn cell_coords(&self, pos: Point2<f32>) -> (usize, usize) {
+        let x = ((pos.x - self.origin.x) / CELL_SIZE).floor().max(0.0) as usize;
+        let z = ((pos.y - self.origin.y) / CELL_SIZE).floor().max(0.0) as usize;
+        (x.min(self.width - 1), z.min(self.depth - 1))
+    }
+
+    fn cell_center(&self, i: usize) -> Point2<f32> {
+        let (x, z) = (i % self.width, i / self.width);
+        self.origin + Vector2::new(x as f32 + 0.5, z as f32 + 0.5) * CELL_SIZE
+    }
+
+    fn neighbors(&self, i: usize) -> [usize; 8] {
+        NEIGHBORS.map(|(dx, dz)| (i as isize + dx + dz * self.width as isize) as usize)
+    }
+
+    /// Approximate distance (in cells) from each cell to the nearest non-road cell, via a two-pass chamfer
+    fn distance_field(&self) -> Vec<f32> {
+        const ORTHO: f32 = 1.0;
+        const DIAG: f32 = std::f32::consts::SQRT_2;
+
+        let mut dist: Vec<f32> = self
+            .heights
+            .iter()
+            .map(|h| if h.is_some() { f32::INFINITY } else { 0.0 })
+            .collect();
+        let w = self.width as isize;
+        let forward = [(-w - 1, DIAG), (-w, ORTHO), (-w + 1, DIAG), (-1, ORTHO)];
+        let backward = [(w + 1, DIAG), (w, ORTHO), (w - 1, DIAG), (1, ORTHO)];
+        for i in 0..dist.len() {
+            if dist[i] > 0.0 {
+                for (offset, cost) in forward {
+                    dist[i] = dist[i].min(dist[(i as isize + offset) as usize] + cost);
+                }
+            }
+        }
+        for i in (0..dist.len()).rev() {
+            if dist[i] > 0.0 {
+                for (offset, cost) in backward {
+                    dist[i] = dist[i].min(dist[(i as isize + offset) as usize] + cost);
+                }
+            }
+        }
+        dist
+    }
+
+    /// Thins the surface down to a one cell wide skeleton (Zhang-Suen)
+    fn skeleton(&self) -> Vec<bool> {
+        let mut skeleton: Vec<bool> = self.heights.iter().map(Option::is_some).collect();
+        let mut active: Vec<usize> = (0..skeleton.len()).filter(|i| skeleton[*i]).collect();
+        loop {
+            let mut changed = false;
+            for step in 0..2 {
+                let removable: Vec<usize> = active
+                    .iter()
+                    .copied()
+                    .filter(|i| {
+                        let n = self.neighbors(*i).map(|n| skeleton[n]);
+                        let count = n.iter().filter(|set| **set).count();
+                        let [p2, _, p4, _, p6, _, p8, _] = n;
+                        (2..=6).contains(&count)
+                            && crossing_number(&n) == 1
+                            && if step == 0 {
+                                !(p4 && p6 && (p2 || p8))
+                            } else {
+                                !(p2 && p8 && (p4 || p6))
+                            }
+                    })
+                    .collect();
+                changed |= !removable.is_empty();
+                for i in removable {
+                    skeleton[i] = false;
+                }
+                active.retain(|i| skeleton[*i]);
+            }
+            if !changed {
+                break;
+            }
+        }
+        skeleton
+    }
+
+    /// Walks the skeleton, returning clusters of node cells and the paths of cells between them
+    fn trace_skeleton(&self, skeleton: &[bool]) -> (Vec<Vec<usize>>, Vec<CellPath>) {
+        let skeleton_cells: Vec<usize> = (0..skeleton.len()).filter(|i| skeleton[*i]).collect();
+
+        // dead ends and junctions become nodes, with touching junction cells being grouped together
+        let mut node_of: HashMap<usize, usize> = HashMap::new();
+        let mut clusters: Vec<Vec<usize>> = Vec::new();
+        let is_node_cell = |i: usize| {
+            let n = self.neighbors(i).map(|n| skeleton[n]);
+            n.iter().filter(|set| **set).count() == 1 || crossing_number(&n) >= 3
+        };
+        for &cell in skeleton_cells.iter() {
+            if node_of.contains_key(&cell) || !is_node_cell(cell) {
+                continue;
+            }
+            let id = clusters.len();
+            let mut cluster = Vec::new();
+            let mut queue = VecDeque::from([cell]);
+            node_of.insert(cell, id);
+            while let Some(cur) = queue.pop_front() {
+                cluster.push(cur);
+                for n in self.neighbors(cur) {
+                    if skeleton[n] && !node_of.contains_key(&n) && is_node_cell(n) {
+                        node_of.insert(n, id);
+                        queue.push_back(n);
+                    }
+                }
+            }
+            clusters.push(cluster);
+        }
+
+        let mut visited: HashSet<usize> = HashSet::new();
+        let mut traces = Vec::new();
+        let mut node = 0;
+        loop {
+            if node == clusters.len() {
+                // anything left over is a closed loop without any nodes, so give it one
+                let Some(&cell) = skeleton_cells
+                    .iter()
+                    .find(|i| !node_of.contains_key(i) && !visited.contains(i))
+                else {
+                    break;
+                };
+                node_of.insert(cell, clusters.len());
+                clusters.push(vec![cell]);
+            }
+            for cell in clusters[node].clone() {
+                for first in self.neighbors(cell) {
+                    if skeleton[first] && !node_of.contains_key(&first) && !visited.contains(&first)
+                    {
+                        traces.push(self.trace_path(
+                            skeleton,
+                            &mut node_of,
+                            &mut clusters,
+                            &mut visited,
+                            node,
+                            first,
+                        ));
+                    }
+                }
+            }
+            node += 1;
+        }
+        (clusters, traces)
+    }
+
+    /// Follows a path of skeleton cells away from a node until it reaches another one
+    fn trace_path(
+        &self,
+        skeleton: &[bool],
+        node_of: &mut HashMap<usize, usize>,
+        clusters: &mut Vec<Vec<usize>>,
+        visited: &mut HashSet<usize>,
+        start: usize,
+        first: usize,
+    ) -> CellPath {
+        let mut cells = vec![first];
+        visited.insert(first);
+        let mut cur = first;
+        loop {
+            let neighbors = self.neighbors(cur);
+            // the start node is only a valid end once the path has properly left it
+            if let Some(&end) = neighbors
+                .iter()
+                .filter_map(|n| node_of.get(n))
+                .find(|node| **node != start || cells.len() > 2)
+            {
+                return (start, end, cells);
+            }
+
+            let next: Vec<(usize, usize)> = neighbors
+                .into_iter()
+                .enumerate()
+                .filter(|(_, n)| skeleton[*n] && !node_of.contains_key(n) && !visited.contains(n))
+                .collect();
+            // prefer orthogonal steps, so that staircased lines are walked one cell at a time instead of cutting
+            // across onto a neighbouring branch
+            let Some(&(_, step)) = next.iter().find(|(dir, _)| dir % 2 == 0).or(next.first())
+            else {
+                // dead end that wasn't picked up as a node
+                cells.pop();
+                node_of.insert(cur, clusters.len());
+                clusters.push(vec![cur]);
+                return (start, clusters.len() - 1, cells);
+            };
+            visited.insert(step);
+            cells.push(step);
+            cur = step;
+        }
+    }
+}
+
+/// Path of skeleton cells between two nodes, as `(start node, end node, cells)`
+type CellPath = (usize, usize, Vec<usize>);
+
+/// Number of separate runs of set cells around a cell
+fn crossing_number(neighbors: &[bool; 8]) -> usize {
+    (0..8)
+        .filter(|i| !neighbors[*i] && neighbors[(i + 1) % 8])
+        .count()
+}
+
+/// Edge between two nodes, holding the interior points of its centerline along with the road width at each
+struct RawEdge {
+    start: usize,
+    end: usize,
+    points: Vec<(Point3<f32>, f32)>,
+}
+impl RawEdge {
+    fn length(&self, nodes: &[(Point3<f32>, f32)]) -> f32 {
+        let mut points = vec![nodes[self.start].0];
+        points.extend(self.points.iter().map(|(p, _)| *p));
+        points.push(nodes[self.end].0);
+        points.windows(2).map(|w| (w[1] - w[0]).magnitude()).sum()
+    }
+
+    /// Widest the road gets along this edge, including at its nodes
+    fn max_width(&self, nodes: &[(Point3<f32>, f32)]) -> f32 {
+        self.points
+            .iter()
+            .map(|(_, w)| *w)
+            .fold(nodes[self.start].1.max(nodes[self.end].1), f32::max)
+    }
+}
+
+fn node_degrees(n_nodes: usize, edges: &[Option<RawEdge>]) -> Vec<usize> {
+    let mut degrees = vec![0; n_nodes];
+    for edge in edges.iter().flatten() {
+        degrees[edge.start] += 1;
+        degrees[edge.end] += 1;
+    }
+    degrees
+}
+
+/// Removes the short dead-end branches (and tiny loops) that thinning leaves behind in wide areas of road, which
+/// never make it further than the width of the road they sprout from
+fn prune_spurs(nodes: &[(Point3<f32>, f32)], edges: &mut [Option<RawEdge>]) {
+    loop {
+        let degrees = node_degrees(nodes.len(), edges);
+        let mut pruned = false;
+        for slot in edges.iter_mut() {
+            let Some(edge) = slot else {
+                continue;
+            };
+            let length = edge.length(nodes);
+            let max_width = edge.max_width(nodes);
+            let is_spur = if edge.start == edge.end {
+                length < max_width * std::f32::consts::PI
+            } else {
+                (degrees[edge.start] == 1 || degrees[edge.end] == 1) && length < max_width
+            };
+            if is_spur {
+                *slot = None;
+                pruned = true;
+            }
+        }
+        if !pruned {
+            break;
+        }
+    }
+}
+
+/// Joins together pairs of edges that meet at a node without any other edges
+fn merge_pass_through_nodes(nodes: &[(Point3<f32>, f32)], edges: &mut [Option<RawEdge>]) {
+    for (node, node_point) in nodes.iter().enumerate() {
+        let connected: Vec<usize> = (0..edges.len())
+            .filter(|i| {
+                edges[*i]
+                    .as_ref()
+                    .is_some_and(|e| e.start == node || e.end == node)
+            })
+            .collect();
+        let [a, b] = connected[..] else {
+            continue;
+        };
+        let mut first = edges[a].take().unwrap();
+        let mut second = edges[b].take().unwrap();
+        if first.start == first.end || second.start == second.end {
+            // loop, leave it be
+            edges[a] = Some(first);
+            edges[b] = Some(second);
+            continue;
+        }
+        // orient so that first ends and second starts at this node
+        if first.end != node {
+            first.points.reverse();
+            (first.start, first.end) = (first.end, first.start);
+        }
+        if second.start != node {
+            second.points.reverse();
+            (second.start, second.end) = (second.end, second.start);
+        }
+        first.points.push(*node_point);
+        first.points.extend(second.points);
+        first.end = second.end;
+        edges[a] = Some(first);
+    }
+}
+
+/// Douglas-Peucker simplification in the horizontal plane, always keeping the first and last points
+fn simplify(points: &[(Point3<f32>, f32)], tolerance: f32) -> Vec<(Point3<f32>, f32)> {
+    if points.len() < 3 {
+        return points.to_vec();
+    }
+    let mut keep = vec![false; points.len()];
+    keep[0] = true;
+    keep[points.len() - 1] = true;
+    let mut stack = vec![(0, points.len() - 1)];
+    while let Some((first, last)) = stack.pop() {
+        let (a, b) = (xz(&points[first].0), xz(&points[last].0));
+        let ab = b - a;
+        let furthest = (first + 1..last)
+            .map(|i| {
+                let p = xz(&points[i].0);
+                let t = if ab.magnitude_squared() > 0.0 {
+                    ((p - a).dot(&ab) / ab.magnitude_squared()).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                (i, (p - (a + ab * t)).magnitude())
+            })
+            .max_by(|x, y| x.1.total_cmp(&y.1));
+        if let Some((i, dist)) = furthest
+            && dist > tolerance
+        {
+            keep[i] = true;
+            stack.push((first, i));
+            stack.push((i, last));
+        }
+    }
+    points
+        .iter()
+        .zip(keep)
+        .filter_map(|(p, keep)| keep.then_some(*p))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edge(start: usize, end: usize) -> Option<RawEdge> {
+        Some(RawEdge {
+            start,
+            end,
+            points: Vec::new(),
+        })
+    }
+
+    /// Position and road width of a node
+    type Node = (Point3<f32>, f32);
+
+    /// A 6m wide road along x, with a 2m long branch off its middle
+    fn junction_with_spur() -> (Vec<Node>, Vec<Option<RawEdge>>) {
+        let nodes = [[0.0, 0.0], [20.0, 0.0], [40.0, 0.0], [20.0, 2.0]]
+            .map(|[x, z]| (Point3::new(x, 0.0, z), 6.0))
+            .to_vec();
+        (nodes, vec![edge(0, 1), edge(1, 2), edge(1, 3)])
+    }
+
+    #[test]
+    fn short_spurs_are_pruned() {
+        let (nodes, mut edges) = junction_with_spur();
+        prune_spurs(&nodes, &mut edges);
+        assert!(edges[0].is_some() && edges[1].is_some());
+        assert!(edges[2].is_none());
+    }
+
+    #[test]
+    fn long_branches_are_kept() {
+        let (mut nodes, mut edges) = junction_with_spur();
+        nodes[3].0.z = 20.0;
+        prune_spurs(&nodes, &mut edges);
+        assert!(edges.iter().all(Option::is_some));
+    }
+
+    #[test]
+    fn pass_through_nodes_are_merged() {
+        let (nodes, mut edges) = junction_with_spur();
+        prune_spurs(&nodes, &mut edges);
+        merge_pass_through_nodes(&nodes, &mut edges);
+        let merged: Vec<&RawEdge> = edges.iter().flatten().collect();
+        assert_eq!(merged.len(), 1);
+        assert_eq!((merged[0].start, merged[0].end), (0, 2));
+        assert_eq!(merged[0].points, [nodes[1]]);
+    }
+
+    #[test]
+    fn simplify_keeps_corners() {
+        let points: Vec<Node> = [[0.0, 0.0], [5.0, 0.1], [10.0, 0.0], [10.0, 10.0]]
+            .iter()
+            .map(|[x, z]| (Point3::new(*x, 0.0, *z), 6.0))
+            .collect();
+        let simplified = simplify(&points, SIMPLIFY_TOLERANCE);
+        assert_eq!(simplified, [points[0], points[2], points[3]]);
+    }
+}
@@ -0,0 +1,323 @@
+mod extract;
+mod route;
+
+use assets::{Assets, GameObject};
+use nalgebra::{Point2, Point3};
+
+use extract::RoadSurface;
+pub use route::Route;
+
+/// A junction or dead end in the road network
+pub struct RoadNode {
+    pub pos: Point3<f32>,
+    /// Indices of the edges connected to this node
+    pub edges: Vec<usize>,
+}
+
+/// A stretch of road between two nodes, described by its centerline
+pub struct RoadEdge {
+    pub start: usize,
+    pub end: usize,
+    /// Centerline polyline, running from the start node to the end node
+    pub points: Vec<Point3<f32>>,
+    /// Width of the road at each centerline point
+    pub widths: Vec<f32>,
+    /// Distance along the centerline at each point
+    distances: Vec<f32>,
+}
+impl RoadEdge {
+    fn new(start: usize, end: usize, points: Vec<Point3<f32>>, widths: Vec<f32>) -> RoadEdge {
+        let mut distances = Vec::with_capacity(points.len());
+        let mut total = 0.0;
+        for (i, p) in points.iter().enumerate() {
+            if i > 0 {
+                total += (p - points[i - 1]).magnitude();
+            }
+            distances.push(total);
+        }
+        RoadEdge {
+            start,
+            end,
+            points,
+            widths,
+            distances,
+        }
+    }
+
+    /// Length of the centerline
+    pub fn length(&self) -> f32 {
+        self.distances.last().copied().unwrap_or(0.0)
+    }
+
+    /// Gets the point on the centerline a certain distance from the start node
+    pub fn point_at(&self, distance: f32) -> Point3<f32> {
+        let distance = distance.clamp(0.0, self.length());
+        let seg = self
+            .distances
+            .partition_point(|d| *d <= distance)
+            .clamp(1, self.points.len() - 1);
+        let seg_len = self.distances[seg] - self.distances[seg - 1];
+        let t = if seg_len > 0.0 {
+            (distance - self.distances[seg - 1]) / seg_len
+        } else {
+            0.0
+        };
+        self.points[seg - 1] + (self.points[seg] - self.points[seg - 1]) * t
+    }
+}
+
+/// The result of a nearest road point query
+#[derive(Clone, Copy, Debug)]
+pub struct RoadPoint {
+    /// Index of the edge this point lies on
+    pub edge: usize,
+    /// Position on the edge's centerline
+    pub pos: Point3<f32>,
+    /// Distance along the edge's centerline, measured from its start node
+    pub distance_along: f32,
+    /// Horizontal distance between the queried point and `pos`
+    pub offset: f32,
+    /// Road width at this point
+    pub width: f32,
+}
+
+/// Graph of road centerlines and junctions, derived from a road mesh
+///
+/// All queries are done in the horizontal (XZ) plane
+pub struct RoadNetwork {
+    pub nodes: Vec<RoadNode>,
+    pub edges: Vec<RoadEdge>,
+    surface: RoadSurface,
+}
+impl RoadNetwork {
+    /// Extracts the road network from the up-facing triangles of an object's render meshes
+    pub fn from_object<GO: GameObject>(assets: &Assets) -> RoadNetwork {
+        let triangles = GO::render_meshes(assets).iter().flat_map(|mesh| {
+            mesh.indices
+                .chunks_exact(3)
+                .map(|face| [0, 1, 2].map(|i| Point3::from(mesh.verts[face[i] as usize].pos)))
+        });
+        RoadNetwork::from_triangles(triangles)
+    }
+
+    /// Extracts the road network from a mesh's triangles, ignoring any that don't face up or down
+    fn from_triangles<I: Iterator<Item = [Point3<f32>; 3]>>(triangles: I) -> RoadNetwork {
+        let surface = RoadSurface::rasterize(triangles);
+        let (nodes, edges) = surface.extract_graph();
+
+        log::debug!(
+            "Extracted road network ({} nodes, {} edges, {:.0}m of road)",
+            nodes.len(),
+            edges.len(),
+            edges.iter().map(RoadEdge::length).sum::<f32>()
+        );
+        RoadNetwork {
+            nodes,
+            edges,
+            surface,
+        }
+    }
+
+    /// Finds the closest point on any road centerline
+    pub fn nearest_point(&self, pos: Point3<f32>) -> Option<RoadPoint> {
+        let query = xz(&pos);
+        let mut nearest: Option<RoadPoint> = None;
+        for (edge_i, edge) in self.edges.iter().enumerate() {
+            for seg in 1..edge.points.len() {
+                let (a, b) = (edge.points[seg - 1], edge.points[seg]);
+                let ab = xz(&b) - xz(&a);
+                let t = if ab.magnitude_squared() > 0.0 {
+                    ((query - xz(&a)).dot(&ab) / ab.magnitude_squared()).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                let point = a + (b - a) * t;
+                let offset = (query - xz(&point)).magnitude();
+                if nearest.is_none_or(|n| offset < n.offset) {
+                    nearest = Some(RoadPoint {
+                        edge: edge_i,
+                        pos: point,
+                        distance_along: edge.distances[seg - 1]
+                            + (edge.distances[seg] - edge.distances[seg - 1]) * t,
+                        offset,
+                        width: edge.widths[seg - 1] + (edge.widths[seg] - edge.widths[seg - 1]) * t,
+                    });
+                }
+            }
+        }
+        nearest
+    }
+
+    /// How far along its road a point is, as `(edge index, distance from the edge's start node)`
+    pub fn distance_along(&self, pos: Point3<f32>) -> Option<(usize, f32)> {
+        self.nearest_point(pos)
+            .map(|point| (point.edge, point.distance_along))
+    }
+
+    /// Whether a point lies above or below the road surface
+    pub fn is_on_road(&self, pos: Point3<f32>) -> bool {
+        self.surface.contains(xz(&pos))
+    }
+}
+
+#[cfg(test)]
+impl RoadNetwork {
+    /// Network of straight 6m wide roads between nodes, without any road surface
+    pub(crate) fn from_straight_edges(
+        nodes: &[Point3<f32>],
+        edges: &[(usize, usize)],
+    ) -> RoadNetwork {
+        let mut network = RoadNetwork {
+            nodes: nodes
+                .iter()
+                .map(|pos| RoadNode {
+                    pos: *pos,
+                    edges: Vec::new(),
+                })
+                .collect(),
+            edges: Vec::new(),
+            surface: RoadSurface::rasterize(std::iter::empty()),
+        };
+        for (i, &(start, end)) in edges.iter().enumerate() {
+            network.edges.push(RoadEdge::new(
+                start,
+                end,
+                vec![nodes[start], nodes[end]],
+                vec![6.0, 6.0],
+            ));
+            network.nodes[start].edges.push(i);
+            network.nodes[end].edges.push(i);
+        }
+        network
+    }
+}
+
+fn xz(p: &Point3<f32>) -> Point2<f32> {
+    Point2::new(p.x, p.z)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two flat triangles covering a rectangle between two corners in the XZ plane
+    fn rect(min: [f32; 2], max: [f32; 2]) -> [[Point3<f32>; 3]; 2] {
+        let corner = |x: f32, z: f32| Point3::new(x, 0.0, z);
+        [
+            [
+                corner(min[0], min[1]),
+                corner(max[0], min[1]),
+                corner(max[0], max[1]),
+            ],
+            [
+                corner(min[0], min[1]),
+                corner(max[0], max[1]),
+                corner(min[0], max[1]),
+            ],
+        ]
+    }
+
+    fn network(rects: &[([f32; 2], [f32; 2])]) -> RoadNetwork {
+        RoadNetwork::from_triangles(rects.iter().flat_map(|(min, max)| rect(*min, *max)))
+    }
+
+    fn degrees(network: &RoadNetwork) -> Vec<usize> {
+        let mut degrees: Vec<usize> = network.nodes.iter().map(|n| n.edges.len()).collect();
+        degrees.sort();
+        degrees
+    }
+
+    /// A 6m wide road running 40m along x
+    const STRIP: ([f32; 2], [f32; 2]) = ([0.0, -3.0], [40.0, 3.0]);
+
+    fn assert_near(a: Point3<f32>, b: Point3<f32>) {
+        assert!((a - b).magnitude() < 1.0, "{a} isn't near {b}");
+    }
+
+    #[test]
+    fn straight_strip() {
+        let network = network(&[STRIP]);
+        assert_eq!(network.nodes.len(), 2);
+        assert_eq!(network.edges.len(), 1);
+        let edge = &network.edges[0];
+        assert_eq!(edge.points.len(), 2);
+        // the centerline runs down the middle, stopping about half a road width from the ends
+        assert!(edge.points.iter().all(|p| p.z.abs() < 0.5));
+        assert!((edge.length() - 34.0).abs() < 1.0);
+        assert!(edge.widths.iter().all(|w| (w - 6.0).abs() < 1.0));
+    }
+
+    #[test]
+    fn t_junction() {
+        let network = network(&[STRIP, ([17.0, 3.0], [23.0, 30.0])]);
+        assert_eq!(degrees(&network), [1, 1, 1, 3]);
+        assert_eq!(network.edges.len(), 3);
+        let junction = network.nodes.iter().find(|n| n.edges.len() == 3).unwrap();
+        assert_near(junction.pos, Point3::new(20.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn ring_closes() {
+        let network = network(&[
+            ([0.0, 0.0], [40.0, 6.0]),
+            ([0.0, 34.0], [40.0, 40.0]),
+            ([0.0, 6.0], [6.0, 34.0]),
+            ([34.0, 6.0], [40.0, 34.0]),
+        ]);
+        assert_eq!(network.nodes.len(), 1);
+        assert_eq!(network.edges.len(), 1);
+        let edge = &network.edges[0];
+        assert_eq!(edge.start, edge.end);
+        assert_eq!(edge.points.first(), edge.points.last());
+        // around the middle of the ring
+        assert!((edge.length() - 4.0 * 34.0).abs() < 2.0);
+    }
+
+    #[test]
+    fn short_spur_is_pruned() {
+        let network = network(&[STRIP, ([18.0, 3.0], [22.0, 5.0])]);
+        assert_eq!(network.nodes.len(), 2);
+        assert_eq!(network.edges.len(), 1);
+    }
+
+    #[test]
+    fn nearest_point_on_and_off_the_strip() {
+        let network = network(&[STRIP]);
+        let on = network.nearest_point(Point3::new(20.0, 0.0, 1.0)).unwrap();
+        assert_eq!(on.edge, 0);
+        assert_near(on.pos, Point3::new(20.0, 0.0, 0.0));
+        assert!((on.offset - 1.0).abs() < 0.5);
+        assert!((on.width - 6.0).abs() < 1.0);
+
+        // past the end of the centerline, the nearest point is its end
+        let off = network.nearest_point(Point3::new(60.0, 5.0, 10.0)).unwrap();
+        let end = *network.edges[0].points.last().unwrap();
+        assert_near(off.pos, end);
+        assert!((off.distance_along - network.edges[0].length()).abs() < 1e-3);
+        // height doesn't count
+        assert!((off.offset - (Point2::new(60.0, 10.0) - xz(&end)).magnitude()).abs() < 1e-3);
+
+        let (edge, along) = network.distance_along(Point3::new(20.0, 0.0, 1.0)).unwrap();
+        assert_eq!(edge, 0);
+        let start = network.edges[0].points[0];
+        assert!((along - (20.0 - start.x)).abs() < 0.5);
+    }
+
+    #[test]
+    fn nothing_near_an_empty_network() {
+        let network = network(&[]);
+        assert!(network.nodes.is_empty());
+        assert!(network.nearest_point(Point3::origin()).is_none());
+        assert!(!network.is_on_road(Point3::origin()));
+    }
+
+    #[test]
+    fn on_road() {
+        let network = network(&[STRIP]);
+        assert!(network.is_on_road(Point3::new(20.0, 0.0, 0.0)));
+        assert!(network.is_on_road(Point3::new(1.0, 10.0, 2.5)));
+        assert!(!network.is_on_road(Point3::new(20.0, 0.0, 4.0)));
+        assert!(!network.is_on_road(Point3::new(-1.0, 0.0, 0.0)));
+        assert!(!network.is_on_road(Point3::new(1000.0, 0.0, -1000.0)));
+    }
+}
@@ -0,0 +1,368 @@
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+use nalgebra::Point3;
+
+use super::{RoadEdge, RoadNetwork, xz};
+
+/// A planned path along the road network
+pub struct Route {
+    /// Polyline running along road centerlines, from the start point to the destination
+    pub points: Vec<Point3<f32>>,
+    /// Total distance along the road
+    pub length: f32,
+}
+impl Route {
+    /// Finds where a point is along the route, as `(segment index, position on the route, horizontal distance from
+    /// the route)`
+    pub fn project(&self, pos: Point3<f32>) -> (usize, Point3<f32>, f32) {
+        let query = xz(&pos);
+        let mut nearest = (0, self.points[0], f32::INFINITY);
+        for seg in 1..self.points.len() {
+            let (a, b) = (self.points[seg - 1], self.points[seg]);
+            let ab = xz(&b) - xz(&a);
+            let t = if ab.magnitude_squared() > 0.0 {
+                ((query - xz(&a)).dot(&ab) / ab.magnitude_squared()).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            let point = a + (b - a) * t;
+            let offset = (query - xz(&point)).magnitude();
+            if offset < nearest.2 {
+                nearest = (seg, point, offset);
+            }
+        }
+        nearest
+    }
+
+    /// The part of the route that is still ahead of a point
+    pub fn remaining(&self, pos: Point3<f32>) -> Vec<Point3<f32>> {
+        let (seg, point, _) = self.project(pos);
+        let mut points = vec![point];
+        points.extend_from_slice(&self.points[seg.max(1)..]);
+        points
+    }
+}
+
+/// Traversal of (part of) an edge during route planning
+#[derive(Clone, Copy)]
+struct Step {
+    /// Node the step starts at, `None` for the start point
+    prev: Option<usize>,
+    edge: usize,
+    /// Distance along the edge the step starts at
+    from: f32,
+    /// Distance along the edge the step ends at
+    to: f32,
+}
+
+/// Entry in the A* open set, ordered so that the lowest estimated cost is popped first
+struct Candidate {
+    estimate: f32,
+    cost: f32,
+    state: usize,
+}
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.estimate == other.estimate
+    }
+}
+impl Eq for Candidate {}
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.estimate.total_cmp(&self.estimate)
+    }
+}
+
+/// State of an A* search over the road nodes
+struct Search {
+    best_cost: Vec<f32>,
+    best_step: Vec<Option<Step>>,
+    open: BinaryHeap<Candidate>,
+}
+impl Search {
+    fn new(n_states: usize) -> Search {
+        Search {
+            best_cost: vec![f32::INFINITY; n_states],
+            best_step: vec![None; n_states],
+            open: BinaryHeap::new(),
+        }
+    }
+
+    /// Records a way to reach a state, if it beats the best one found so far
+    fn push(&mut self, state: usize, cost: f32, heuristic: f32, step: Step) {
+        if cost < self.best_cost[state] {
+            self.best_cost[state] = cost;
+            self.best_step[state] = Some(step);
+            self.open.push(Candidate {
+                estimate: cost + heuristic,
+                cost,
+                state,
+            });
+        }
+    }
+}
+
+impl RoadNetwork {
+    /// Plans the shortest route along the roads between the nearest road points to `from` and `to`, using A*
+    pub fn plan_route(&self, from: Point3<f32>, to: Point3<f32>) -> Option<Route> {
+        let start = self.nearest_point(from)?;
+        let goal = self.nearest_point(to)?;
+        let start_edge = &self.edges[start.edge];
+
+        // states are the nodes, plus the goal point at the end
+        let goal_state = self.nodes.len();
+        let heuristic = |state: usize| {
+            if state == goal_state {
+                0.0
+            } else {
+                (xz(&self.nodes[state].pos) - xz(&goal.pos)).magnitude()
+            }
+        };
+        let mut search = Search::new(self.nodes.len() + 1);
+        let push = |search: &mut Search, state: usize, cost: f32, step: Step| {
+            search.push(state, cost, heuristic(state), step)
+        };
+
+        // leaving the start point in either direction, or heading straight to the goal if it's on the same edge
+        for (node, to) in [
+            (start_edge.start, 0.0),
+            (start_edge.end, start_edge.length()),
+        ] {
+            let step = Step {
+                prev: None,
+                edge: start.edge,
+                from: start.distance_along,
+                to,
+            };
+            push(&mut search, node, (to - start.distance_along).abs(), step);
+        }
+        if start.edge == goal.edge {
+            let step = Step {
+                prev: None,
+                edge: start.edge,
+                from: start.distance_along,
+                to: goal.distance_along,
+            };
+            let cost = (goal.distance_along - start.distance_along).abs();
+            push(&mut search, goal_state, cost, step);
+        }
+
+        while let Some(Candidate { cost, state, .. }) = search.open.pop() {
+            if state == goal_state {
+                break;
+            }
+            if cost > search.best_cost[state] {
+                continue; // stale entry
+            }
+            for &edge_i in self.nodes[state].edges.iter() {
+                let edge = &self.edges[edge_i];
+                // loops can be taken either way round
+                let mut traversals = Vec::with_capacity(2);
+                if edge.start == state {
+                    traversals.push((edge.end, 0.0, edge.length()));
+                }
+                if edge.end == state {
+                    traversals.push((edge.start, edge.length(), 0.0));
+                }
+                for (next, from, to) in traversals {
+                    let step = Step {
+                        prev: Some(state),
+                        edge: edge_i,
+                        from,
+                        to,
+                    };
+                    push(&mut search, next, cost + edge.length(), step);
+                }
+                if edge_i == goal.edge {
+                    let from = if edge.start == state {
+                        0.0
+                    } else {
+                        edge.length()
+                    };
+                    let step = Step {
+                        prev: Some(state),
+                        edge: edge_i,
+                        from,
+                        to: goal.distance_along,
+                    };
+                    let goal_cost = cost + (goal.distance_along - from).abs();
+                    push(&mut search, goal_state, goal_cost, step);
+                }
+            }
+        }
+
+        // walking back from the goal
+        let mut steps = Vec::new();
+        let mut state = goal_state;
+        while let Some(step) = search.best_step[state] {
+            steps.push(step);
+            match step.prev {
+                Some(prev) => state = prev,
+                None => break,
+            }
+        }
+        if steps.is_empty() {
+            return None; // goal is unreachable
+        }
+        let mut points: Vec<Point3<f32>> = Vec::new();
+        for step in steps.iter().rev() {
+            for point in self.edges[step.edge].sub_polyline(step.from, step.to) {
+                if points.last() != Some(&point) {
+                    points.push(point);
+                }
+            }
+        }
+        if points.len() < 2 {
+            points.push(goal.pos);
+        }
+
+        Some(Route {
+            points,
+            length: search.best_cost[goal_state],
+        })
+    }
+}
+
+impl RoadEdge {
+    /// Gets the centerline between two distances along the edge, running backwards if `from` is past `to`
+    fn sub_polyline(&self, from: f32, to: f32) -> Vec<Point3<f32>> {
+        let mut points = vec![self.point_at(from)];
+        if from <= to {
+            for (i, d) in self.distances.iter().enumerate() {
+                if *d > from && *d < to {
+                    points.push(self.points[i]);
+                }
+            }
+        } else {
+            for (i, d) in self.distances.iter().enumerate().rev() {
+                if *d < from && *d > to {
+                    points.push(self.points[i]);
+                }
+            }
+        }
+        points.push(self.point_at(to));
+        points
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(x: f32, z: f32) -> Point3<f32> {
+        Point3::new(x, 0.0, z)
+    }
+
+    /// Two ways from (0, 0) to (100, 0): a short one over (50, 30) and a long one under (50, -80), along with a
+    /// road of its own off to the side
+    fn network() -> RoadNetwork {
+        RoadNetwork::from_straight_edges(
+            &[
+                point(0.0, 0.0),
+                point(100.0, 0.0),
+                point(50.0, 30.0),
+                point(50.0, -80.0),
+                point(200.0, 0.0),
+                point(200.0, 100.0),
+            ],
+            &[(0, 2), (2, 1), (0, 3), (3, 1), (4, 5)],
+        )
+    }
+
+    fn assert_near(a: f32, b: f32) {
+        assert!((a - b).abs() < 1e-3, "{a} isn't near {b}");
+    }
+
+    #[test]
+    fn picks_the_shorter_way() {
+        let route = network()
+            .plan_route(point(0.0, 0.0), point(100.0, 0.0))
+            .unwrap();
+        assert_eq!(
+            route.points,
+            [point(0.0, 0.0), point(50.0, 30.0), point(100.0, 0.0)]
+        );
+        assert_near(route.length, 2.0 * 50.0f32.hypot(30.0));
+    }
+
+    #[test]
+    fn starts_and_ends_mid_edge() {
+        // just off the middles of the two edges over the top
+        let route = network()
+            .plan_route(point(22.0, 20.0), point(78.0, 20.0))
+            .unwrap();
+        assert_eq!(
+            route.points,
+            [point(25.0, 15.0), point(50.0, 30.0), point(75.0, 15.0)]
+        );
+        assert_near(route.length, 50.0f32.hypot(30.0));
+    }
+
+    #[test]
+    fn backwards_along_one_edge() {
+        let route = network()
+            .plan_route(point(40.0, 24.0), point(10.0, 6.0))
+            .unwrap();
+        assert_eq!(route.points, [point(40.0, 24.0), point(10.0, 6.0)]);
+        assert_near(route.length, 30.0f32.hypot(18.0));
+    }
+
+    #[test]
+    fn unreachable_destination() {
+        let network = network();
+        assert!(
+            network
+                .plan_route(point(0.0, 0.0), point(200.0, 50.0))
+                .is_none()
+        );
+        let empty = RoadNetwork::from_straight_edges(&[], &[]);
+        assert!(empty.plan_route(point(0.0, 0.0), point(1.0, 0.0)).is_none());
+    }
+
+    /// An L going 10m along x, then 10m along z
+    fn corner_route() -> Route {
+        Route {
+            points: vec![point(0.0, 0.0), point(10.0, 0.0), point(10.0, 10.0)],
+            length: 20.0,
+        }
+    }
+
+    #[test]
+    fn project_onto_route() {
+        let route = corner_route();
+        let (seg, pos, offset) = route.project(Point3::new(4.0, 3.0, 2.0));
+        assert_eq!((seg, pos), (1, point(4.0, 0.0)));
+        assert_near(offset, 2.0);
+
+        let (seg, pos, offset) = route.project(point(13.0, 6.0));
+        assert_eq!((seg, pos), (2, point(10.0, 6.0)));
+        assert_near(offset, 3.0);
+
+        // before the start
+        let (seg, pos, offset) = route.project(point(-3.0, -4.0));
+        assert_eq!((seg, pos), (1, point(0.0, 0.0)));
+        assert_near(offset, 5.0);
+    }
+
+    #[test]
+    fn remaining_route() {
+        let route = corner_route();
+        assert_eq!(
+            route.remaining(point(4.0, 1.0)),
+            [point(4.0, 0.0), point(10.0, 0.0), point(10.0, 10.0)]
+        );
+        assert_eq!(
+            route.remaining(point(11.0, 5.0)),
+            [point(10.0, 5.0), point(10.0, 10.0)]
+        );
+        assert_eq!(
+            route.remaining(point(10.0, 20.0)),
+            [point(10.0, 10.0), point(10.0, 10.0)]
+        );
+    }
+}
@@ -0,0 +1,215 @@
+use std::fmt::Write;
+
+use serde::Serialize;
+use utils::TelemetrySample;
+
+use crate::car::{DriveInputState, TurnInputState};
+
+/// State of one wheel during a sim tick
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct WheelTelemetry {
+    pub grounded: bool,
+    /// How far the suspension is compressed, from 0 (fully extended) to 1
+    pub compression: f32,
+    /// Impulse the suspension pushed the car up with, per frame
+    pub load: f32,
+    /// Angle between where the tire points and where it is moving, in radians
+    pub slip_angle: f32,
+    /// Whether the tire has exceeded its grip
+    pub slipping: bool,
+}
+
+/// Snapshot of the car's state during a sim tick, for analyzing handling
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct Telemetry {
+    pub tick: u64,
+    /// Sim time, in seconds
+    pub time: f32,
+    /// Speed in m/s
+    pub speed: f32,
+    /// Speed along the car's heading in m/s
+    pub forward_speed: f32,
+    pub throttle: f32,
+    /// Front wheel steer angle, in radians
+    pub steer: f32,
+    pub drive_input: DriveInputState,
+    pub turn_input: TurnInputState,
+    /// Wheels in order front-driver, front-pass, rear-driver, rear-pass
+    pub wheels: [WheelTelemetry; 4],
+    /// Rotation about the car's up axis in rad/s, positive when turning left
+    pub yaw_rate: f32,
+    /// Acceleration towards the car's right in m/s²
+    pub lateral_accel: f32,
+    /// Acceleration along the car's heading in m/s²
+    pub longitudinal_accel: f32,
+}
+impl Telemetry {
+    pub const CSV_HEADER: &'static str = "tick,time,speed,forward_speed,throttle,steer,drive_input,turn_input,\
+        fl_grounded,fl_compression,fl_load,fl_slip_angle,fl_slipping,\
+        fr_grounded,fr_compression,fr_load,fr_slip_angle,fr_slipping,\
+        rl_grounded,rl_compression,rl_load,rl_slip_angle,rl_slipping,\
+        rr_grounded,rr_compression,rr_load,rr_slip_angle,rr_slipping,\
+        yaw_rate,lateral_accel,longitudinal_accel";
+
+    /// The values plotted by the debug graphs
+    pub fn sample(&self) -> TelemetrySample {
+        TelemetrySample {
+            time: self.time,
+            speed: self.speed,
+            throttle: self.throttle,
+            steer: self.steer,
+            wheel_compression: self.wheels.map(|wheel| wheel.compression),
+            wheel_slip: self.wheels.map(|wheel| wheel.slip_angle),
+        }
+    }
+
+    pub fn to_csv_row(&self) -> String {
+        let mut row = format!(
+            "{},{},{},{},{},{},{:?},{:?}",
+            self.tick,
+            self.time,
+            self.speed,
+            self.forward_speed,
+            self.throttle,
+            self.steer,
+            self.drive_input,
+            self.turn_input
+        );
+        for wheel in self.wheels.iter() {
+            let _ = write!(
+                row,
+                ",{},{},{},{},{}",
+                wheel.grounded as u8,
+                wheel.compression,
+                wheel.load,
+                wheel.slip_angle,
+                wheel.slipping as u8
+            );
+        }
+        let _ = write!(
+            row,
+            ",{},{},{}",
+            self.yaw_rate, self.lateral_accel, self.longitudinal_accel
+        );
+        row
+    }
+
+    /// One line of JSON, with NaN and infinity written as `null`
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("telemetry only holds plain values")
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TelemetryFormat {
+    Csv,
+    JsonLines,
+}
+impl TelemetryFormat {
+    pub fn file_extension(&self) -> &'static str {
+        match self {
+            TelemetryFormat::Csv => "csv",
+            TelemetryFormat::JsonLines => "jsonl",
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            TelemetryFormat::Csv => "text/csv",
+            TelemetryFormat::JsonLines => "application/jsonl",
+        }
+    }
+}
+
+/// Accumulates telemetry records into a CSV or JSON Lines document
+pub struct TelemetryRecorder {
+    pub format: TelemetryFormat,
+    contents: String,
+    n_records: usize,
+}
+impl TelemetryRecorder {
+    pub fn new(format: TelemetryFormat) -> TelemetryRecorder {
+        let mut contents = String::new();
+        if format == TelemetryFormat::Csv {
+            contents.push_str(Telemetry::CSV_HEADER);
+            contents.push('\n');
+        }
+        TelemetryRecorder {
+            format,
+            contents,
+            n_records: 0,
+        }
+    }
+
+    pub fn record(&mut self, telemetry: &Telemetry) {
+        match self.format {
+            TelemetryFormat::Csv => self.contents.push_str(&telemetry.to_csv_row()),
+            TelemetryFormat::JsonLines => self.contents.push_str(&telemetry.to_json()),
+        }
+        self.contents.push('\n');
+        self.n_records += 1;
+    }
+
+    pub fn n_records(&self) -> usize {
+        self.n_records
+    }
+
+    /// The recorded document
+    pub fn contents(&self) -> &str {
+        &self.contents
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn telemetry() -> Telemetry {
+        Telemetry {
+            tick: 12,
+            time: 0.2,
+            speed: 5.5,
+            forward_speed: -5.0,
+            throttle: 1.0,
+            steer: -0.1,
+            drive_input: DriveInputState::Accelerating,
+            turn_input: TurnInputState::Left,
+            wheels: [WheelTelemetry {
+                grounded: true,
+                compression: 0.5,
+                load: 100.0,
+                slip_angle: f32::NAN,
+                slipping: false,
+            }; 4],
+            yaw_rate: 0.3,
+            lateral_accel: 1.5,
+            longitudinal_accel: -2.0,
+        }
+    }
+
+    #[test]
+    fn csv_rows_match_the_header() {
+        let columns = Telemetry::CSV_HEADER.split(',').count();
+        assert_eq!(telemetry().to_csv_row().split(',').count(), columns);
+
+        let mut recorder = TelemetryRecorder::new(TelemetryFormat::Csv);
+        recorder.record(&telemetry());
+        recorder.record(&telemetry());
+        assert_eq!(recorder.n_records(), 2);
+        for line in recorder.contents().lines() {
+            assert_eq!(line.split(',').count(), columns);
+        }
+    }
+
+    #[test]
+    fn json_lines() {
+        let mut recorder = TelemetryRecorder::new(TelemetryFormat::JsonLines);
+        recorder.record(&telemetry());
+        let json = recorder.contents().strip_suffix('\n').unwrap();
+        assert!(!json.contains('\n'));
+        assert!(json.starts_with("{\"tick\":12,\"time\":0.2,"));
+        assert!(json.contains("\"drive_input\":\"Accelerating\",\"turn_input\":\"Left\""));
+        assert!(json.contains("\"slip_angle\":null"));
+        assert!(json.ends_with("\"longitudinal_accel\":-2.0}"));
+    }
+}
@@ -0,0 +1,217 @@
+use std::f32::consts::TAU;
+
+use nalgebra::{Isometry3, Point3, Vector3};
+
+/// Segments per circle when drawing spheres
+const SPHERE_SEGMENTS: usize = 16;
+
+/// A line segment in world space, for visualizing debug info
+#[derive(Clone, Copy, Debug)]
+pub struct DebugLine {
+    pub start: Point3<f32>,
+    pub end: Point3<f32>,
+    /// RGB
+    pub col: [f32; 3],
+}
+
+/// Text anchored to a point in world space, drawn on top of the scene
+#[derive(Clone, Debug)]
+pub struct DebugLabel {
+    pub pos: Point3<f32>,
+    pub text: String,
+    /// RGB
+    pub col: [f32; 3],
+}
+
+#[derive(Clone, Debug)]
+enum DebugShape {
+    Line {
+        start: Point3<f32>,
+        end: Point3<f32>,
+    },
+    Arrow {
+        start: Point3<f32>,
+        vec: Vector3<f32>,
+    },
+    Sphere {
+        center: Point3<f32>,
+        radius: f32,
+    },
+    Box {
+        transform: Isometry3<f32>,
+        half_extents: Vector3<f32>,
+    },
+    Label {
+        pos: Point3<f32>,
+        text: String,
+    },
+}
+
+/// A shape submitted to [`DebugDraw`]
+#[derive(Clone, Debug)]
+pub struct DebugItem {
+    shape: DebugShape,
+    col: [f32; 3],
+    /// Seconds left to draw for, after the frame it was submitted
+    lifetime: f32,
+}
+impl DebugItem {
+    /// Keeps drawing this for some seconds instead of only the current frame
+    pub fn lasting(&mut self, secs: f32) -> &mut DebugItem {
+        self.lifetime = secs;
+        self
+    }
+}
+
+/// Immediate-mode debug drawing
+///
+/// Shapes are submitted every frame they should be visible, or given a lifetime to persist for. Everything is in world
+/// space and gets flattened into lines and labels for the render snapshot.
+#[derive(Default)]
+pub struct DebugDraw {
+    items: Vec<DebugItem>,
+}
+impl DebugDraw {
+    pub fn new() -> DebugDraw {
+        DebugDraw { items: Vec::new() }
+    }
+
+    /// Moves time forward, dropping shapes that have outlived their lifetime. Should be called before anything is drawn
+    /// for a frame
+    pub fn advance(&mut self, dt_secs: f32) {
+        self.items.retain_mut(|item| {
+            item.lifetime -= dt_secs;
+            item.lifetime >= 0.0
+        });
+    }
+
+    fn push(&mut self, shape: DebugShape, col: [f32; 3]) -> &mut DebugItem {
+        self.items.push(DebugItem {
+            shape,
+            col,
+            lifetime: 0.0,
+        });
+        self.items.last_mut().unwrap()
+    }
+
+    pub fn line(&mut self, start: Point3<f32>, end: Point3<f32>, col: [f32; 3]) -> &mut DebugItem {
+        self.push(DebugShape::Line { start, end }, col)
+    }
+
+    /// Arrow from `start` along `vec`
+    pub fn arrow(
+        &mut self,
+        start: Point3<f32>,
+        vec: Vector3<f32>,
+        col: [f32; 3],
+    ) -> &mut DebugItem {
+        self.push(DebugShape::Arrow { start, vec }, col)
+    }
+
+    /// Wireframe sphere, as three circles around its axes
+    pub fn sphere(&mut self, center: Point3<f32>, radius: f32, col: [f32; 3]) -> &mut DebugItem {
+        self.push(DebugShape::Sphere { center, radius }, col)
+    }
+
+    /// Wireframe oriented box
+    pub fn cuboid(
+        &mut self,
+        transform: Isometry3<f32>,
+        half_extents: Vector3<f32>,
+        col: [f32; 3],
+    ) -> &mut DebugItem {
+        self.push(
+            DebugShape::Box {
+                transform,
+                half_extents,
+            },
+            col,
+        )
+    }
+
+    pub fn label(&mut self, pos: Point3<f32>, text: &str, col: [f32; 3]) -> &mut DebugItem {
+        self.push(
+            DebugShape::Label {
+                pos,
+                text: text.to_string(),
+            },
+            col,
+        )
+    }
+
+    /// Everything currently drawn, flattened into lines and labels
+    pub fn collect(&self) -> (Vec<DebugLine>, Vec<DebugLabel>) {
+        let mut lines: Vec<DebugLine> = Vec::new();
+        let mut labels: Vec<DebugLabel> = Vec::new();
+        for item in self.items.iter() {
+            let col = item.col;
+            let mut line =
+                |start: Point3<f32>, end: Point3<f32>| lines.push(DebugLine { start, end, col });
+            match &item.shape {
+                DebugShape::Line { start, end } => line(*start, *end),
+                DebugShape::Arrow { start, vec } => {
+                    let length = vec.magnitude();
+                    if length < 1e-4 {
+                        continue;
+                    }
+                    let end = start + vec;
+                    line(*start, end);
+
+                    // arrowhead, in a plane containing the arrow
+                    let dir = vec / length;
+                    let side = if dir.y.abs() < 0.9 {
+                        dir.cross(&Vector3::y())
+                    } else {
+                        dir.cross(&Vector3::x())
+                    }
+                    .normalize();
+                    let head_length = (length * 0.25).min(0.3);
+                    line(end, end - dir * head_length + side * (head_length * 0.5));
+                    line(end, end - dir * head_length - side * (head_length * 0.5));
+                }
+                DebugShape::Sphere { center, radius } => {
+                    for (u, v) in [
+                        (Vector3::x(), Vector3::y()),
+                        (Vector3::y(), Vector3::z()),
+                        (Vector3::z(), Vector3::x()),
+                    ] {
+                        let circle_point = |i: usize| {
+                            let angle = i as f32 / SPHERE_SEGMENTS as f32 * TAU;
+                            center + (u * angle.cos() + v * angle.sin()) * *radius
+                        };
+                        for i in 0..SPHERE_SEGMENTS {
+                            line(circle_point(i), circle_point(i + 1));
+                        }
+                    }
+                }
+                DebugShape::Box {
+                    transform,
+                    half_extents,
+                } => {
+                    let corner = |i: usize| {
+                        transform
+                            * Point3::new(
+                                if i & 1 == 0 { -1.0 } else { 1.0 } * half_extents.x,
+                                if i & 2 == 0 { -1.0 } else { 1.0 } * half_extents.y,
+                                if i & 4 == 0 { -1.0 } else { 1.0 } * half_extents.z,
+                            )
+                    };
+                    // corners that differ in one axis are joined
+                    for i in 0..8 {
+                        for axis_bit in [1, 2, 4] {
+                            if i & axis_bit == 0 {
+                                line(corner(i), corner(i | axis_bit));
+                            }
+                        }
+                    }
+                }
+                DebugShape::Label { pos, text } => labels.push(DebugLabel {
+                    pos: *pos,
+                    text: text.clone(),
+                    col,
+                }),
+            }
+        }
+        (lines, labels)
+    }
+}
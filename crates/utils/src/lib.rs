@@ -1,10 +1,142 @@
-use nalgebra::{Isometry3, Point3, Vector3};
+mod debug_draw;
+
+use nalgebra::{Isometry3, Point3, Vector2, Vector3};
+use serde::{Deserialize, Serialize};
+
+pub use debug_draw::{DebugDraw, DebugItem, DebugLabel, DebugLine};
 
 pub struct RenderSnapshot {
     pub car_transform: Isometry3<f32>,
     /// How far below offset each wheel is (front-driver, front-pass, rear-driver, rear-pass)
     pub wheel_transforms: [Isometry3<f32>; 4],
     pub skid_contact_points: [Option<Point3<f32>>; 4],
+    /// Remaining GPS route ahead of the car, along the road centerlines
+    pub route: Option<Vec<Point3<f32>>>,
+    /// Points of interest to mark on the minimap, such as the GPS destination
+    pub checkpoints: Vec<Point3<f32>>,
+    pub hud: HudInfo,
+    pub telemetry: TelemetrySample,
+    /// World space lines drawn over the scene in debug builds
+    pub debug_lines: Vec<DebugLine>,
+    pub debug_labels: Vec<DebugLabel>,
+}
+
+/// Car values plotted by the debug telemetry graphs, sampled once per sim tick
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TelemetrySample {
+    /// Sim time, in seconds
+    pub time: f32,
+    /// Speed in m/s
+    pub speed: f32,
+    /// Throttle as a fraction of full acceleration, negative when reversing
+    pub throttle: f32,
+    /// Front wheel steer angle, in radians
+    pub steer: f32,
+    /// Suspension compression of each wheel, from 0 (fully extended) to 1
+    pub wheel_compression: [f32; 4],
+    /// Slip angle of each tire, in radians
+    pub wheel_slip: [f32; 4],
+}
+
+/// Car readouts shown on the HUD
+pub struct HudInfo {
+    /// Speed along the car's heading in m/s, negative when moving backwards
+    pub speed: f32,
+    /// Current throttle, from -1 (full reverse) to 1 (full forward)
+    pub throttle: f32,
+    pub gear: Gear,
+    /// Time spent on the current lap, in seconds
+    pub lap_time: f32,
+    pub last_lap: Option<f32>,
+    pub best_lap: Option<f32>,
+}
+
+/// What the car sounds like during a sim tick, for the audio to synthesize
+#[derive(Clone, Debug, Default)]
+pub struct AudioInfo {
+    /// Engine speed, in revolutions per minute
+    pub engine_rpm: f32,
+    /// How hard the engine is working, from 0 (coasting) to 1 (full throttle)
+    pub engine_load: f32,
+    /// How hard each tire is sliding, from 0 (gripping) to 1 (front-driver, front-pass, rear-driver, rear-pass)
+    pub tire_slip: [f32; 4],
+    /// Speed in m/s
+    pub speed: f32,
+    /// Impacts that started during the tick, each from 0 (a bump) to 1 (a crash)
+    pub impacts: Vec<f32>,
+}
+
+/// A menu for the HUD to draw, for when there is no page to show it on
+#[derive(Clone, Debug, PartialEq)]
+pub struct MenuOverlay {
+    pub title: String,
+    /// Text under the title, one line each
+    pub lines: Vec<String>,
+    pub items: Vec<String>,
+    pub selected: usize,
+}
+
+/// On-screen touch controls for the HUD to draw
+pub struct TouchOverlay {
+    pub elements: Vec<TouchElement>,
+}
+
+/// A single touch control, positioned in physical pixels from the top left of the screen
+pub struct TouchElement {
+    pub pos: Vector2<f32>,
+    pub size: Vector2<f32>,
+    pub label: &'static str,
+    pub kind: TouchElementKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TouchElementKind {
+    /// A horizontal slider, with its value from -1 (left) to 1 (right)
+    Slider(f32),
+    /// A pedal filling up from the bottom, with how far it is pressed from 0 to 1
+    Pedal(f32),
+    /// A button, and whether it is held down
+    Button(bool),
+}
+
+/// Units speeds are shown to the player in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SpeedUnit {
+    #[default]
+    Kmh,
+    Mph,
+}
+impl SpeedUnit {
+    /// Converts a speed in m/s to this unit
+    pub fn from_mps(&self, speed: f32) -> f32 {
+        match self {
+            SpeedUnit::Kmh => speed * 3.6,
+            SpeedUnit::Mph => speed * 2.236936,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            SpeedUnit::Kmh => "km/h",
+            SpeedUnit::Mph => "mph",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Gear {
+    Drive,
+    Neutral,
+    Reverse,
+}
+
+/// A capsule of world space between the camera and what it is following, where geometry is faded out so it does not
+/// block the view
+#[derive(Clone, Copy, Debug)]
+pub struct OcclusionFade {
+    pub start: Point3<f32>,
+    pub end: Point3<f32>,
+    pub radius: f32,
 }
 
 pub struct Camera {
@@ -13,6 +145,7 @@ pub struct Camera {
     pub up: Vector3<f32>,
     pub aspect_ratio: f32,
     pub fovy: f32,
+    pub occlusion_fade: Option<OcclusionFade>,
 }
 impl Camera {
     pub const DEFAULT_FOVY: f32 = 70.0f32.to_radians();
@@ -32,6 +165,7 @@ impl Camera {
             up: up.into(),
             aspect_ratio: width / height,
             fovy: Self::DEFAULT_FOVY.into(),
+            occlusion_fade: None,
         }
     }
 
@@ -19,7 +19,10 @@ updateCanvasDPR();
 	).addEventListener("change", onChange, { once: true });
 })();
 
-import init, { run_game } from "../pkg/car_game.js";
+import init, { run_game, menuClick } from "../pkg/car_game.js";
+
+// menu items call back into the game when clicked
+window.menuClick = menuClick;
 
 const runWasm = async () => {
 	await init();
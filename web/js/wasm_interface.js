@@ -1,13 +1,71 @@
+window.setMenu = (json) => {
+	const menu = document.getElementById("menu");
+	const view = JSON.parse(json);
+	if (view === null) {
+		menu.style.display = "none";
+		return;
+	}
+	menu.style.display = "flex";
+	document.getElementById("menu-title").innerText = view.title;
+	document.getElementById("menu-lines").innerText = view.lines.join("\n");
+	const items = document.getElementById("menu-items");
+	items.replaceChildren(...view.items.map((label, i) => {
+		const button = document.createElement("button");
+		button.innerText = label;
+		if (i === view.selected) {
+			button.classList.add("selected");
+		}
+		// the game decides what each item does
+		button.addEventListener("click", () => window.menuClick(i));
+		return button;
+	}));
+	// help for driving is only useful before and between runs
+	const showControls = view.state === "MainMenu" || view.state === "Paused";
+	document.getElementById("menu-controls").style.display = showControls ? "flex" : "none";
+};
 
-window.showPauseMenu = (show) => {
-	const pauseMenu = document.getElementById("pause-menu");
+window.showLoading = (show) => {
+	const loading = document.getElementById("loading");
 	if (show) {
-		pauseMenu.style.display = "flex";
+		loading.style.display = "block";
 	} else {
-		pauseMenu.style.display = "none";
+		loading.style.display = "none";
 	}
 };
 
+window.setLoadingText = (text) => {
+	const loading = document.getElementById("loading");
+	loading.innerText = text;
+}
+
+window.showPhotoMode = (show) => {
+	const photoMode = document.getElementById("photo-mode");
+	if (show) {
+		photoMode.style.display = "flex";
+	} else {
+		photoMode.style.display = "none";
+	}
+};
+
+window.setPhotoModeInfo = (text) => {
+	const photoModeInfo = document.getElementById("photo-mode-info");
+	photoModeInfo.innerText = text;
+}
+
+window.showKeyBindings = (show) => {
+	const keyBindings = document.getElementById("key-bindings");
+	if (show) {
+		keyBindings.style.display = "flex";
+	} else {
+		keyBindings.style.display = "none";
+	}
+};
+
+window.setKeyBindingsText = (text) => {
+	const keyBindingsText = document.getElementById("key-bindings-text");
+	keyBindingsText.innerText = text;
+}
+
 window.showDebugText = (show) => {
 	const debugTextBox = document.getElementById("debug-text")
 	if (show) {
@@ -20,4 +78,15 @@ window.showDebugText = (show) => {
 window.setDebugText = (text) => {
 	const debugTextBox = document.getElementById("debug-text")
 	debugTextBox.innerText = text;
-}
\ No newline at end of file
+}
+
+window.downloadFile = (fileName, mimeType, contents) => {
+	const blob = new Blob([contents], { type: mimeType });
+	const url = URL.createObjectURL(blob);
+	const link = document.createElement("a");
+	link.href = url;
+	link.download = fileName;
+	link.click();
+	// revoking straight away can cancel the download in some browsers
+	setTimeout(() => URL.revokeObjectURL(url), 0);
+}